@@ -1,3 +1,6 @@
+use clap::Parser;
+use engine::{ModArgs, get_target};
+use enginelib::{api::EngineAPI, config::Config, event::warn};
 use proto::engine_client;
 //use enginelib::EventHandler;
 
@@ -7,8 +10,22 @@ pub mod proto {
     tonic::include_proto!("engine");
 }
 
+#[derive(Parser, Debug)]
+#[command(name = "client")]
+#[command(about = "Connects to an engine server")]
+struct Cli {
+    #[command(flatten)]
+    mods: ModArgs,
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+    // Only the task registry of the local mods is needed, so the server's database stays closed.
+    let mut api = EngineAPI::test_default();
+    let mut cfg = Config::new();
+    cli.mods.apply(&mut cfg.config_toml);
+    EngineAPI::init_packer_with_config(&mut api, cfg);
     let url = "http://[::1]:50051";
     let mut client = engine_client::EngineClient::connect(url).await?;
 
@@ -16,5 +33,10 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let request = tonic::Request::new(req);
     let response = client.aquire_task_reg(request).await?;
     let vec = response.get_ref().tasks.clone();
+    for task in vec {
+        if !api.task_registry.tasks.contains_key(&get_target(&task)) {
+            warn!("Server task {} is not provided by any local module", task);
+        }
+    }
     Ok(())
 }
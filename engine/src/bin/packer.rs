@@ -2,12 +2,19 @@ use clap::{Args, CommandFactory, Subcommand, ValueHint};
 use clap::{Command, Parser};
 use clap_complete::{Generator, Shell, generate};
 use colored::*;
+use engine::ModArgs;
+use enginelib::abi::ABI_VERSION;
+use enginelib::config::Config;
 use enginelib::events::ID;
+use enginelib::package::{
+    PackageBuilder, generate_signing_key, public_key_hex, read_signing_key, signing_key_hex,
+};
+use enginelib::plugin::{LibraryMetadata, MOD_LIBRARY, ModInterface, inspect_library};
 // For coloring the output
 use enginelib::Registry;
 use enginelib::api::postcard;
 use enginelib::prelude::error;
-use enginelib::task::{StoredTask, Task, TaskQueue};
+use enginelib::task::{STORE_VERSION, StoredTask, Task, TaskQueue};
 use enginelib::{api::EngineAPI, event::info};
 use serde::Deserialize;
 use std::collections::BTreeMap;
@@ -16,7 +23,7 @@ use std::ffi::OsString;
 use std::fs::File;
 use std::io::Write;
 use std::io::{self, Read};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use toml::Value;
 
 #[derive(Debug)]
@@ -64,6 +71,8 @@ struct Cli {
     command: Option<Commands>,
     #[arg(long = "generate", value_enum)]
     generator: Option<Shell>,
+    #[command(flatten)]
+    mods: ModArgs,
 }
 
 #[derive(Subcommand, Debug, PartialEq)]
@@ -74,11 +83,185 @@ enum Commands {
     Unpack(PackArgs),
     #[command()]
     Schema,
+    /// Generates an Ed25519 key pair for signing mod packages
+    #[command()]
+    Keygen(KeygenArgs),
+    /// Builds a *.rustforge.tar mod package, signed if a key is given
+    #[command()]
+    Package(PackageArgs),
+    /// Builds or inspects native mods
+    #[command()]
+    Mod {
+        #[command(subcommand)]
+        command: ModCommands,
+    },
+}
+#[derive(Subcommand, Debug, PartialEq)]
+enum ModCommands {
+    /// Packages a compiled cdylib using the metadata it exports
+    #[command()]
+    Build(ModBuildArgs),
+    /// Prints a cdylib's metadata and whether this engine can load it, without running it
+    #[command()]
+    Inspect(ModInspectArgs),
+}
+#[derive(Args, Debug, PartialEq)]
+struct ModBuildArgs {
+    /// Compiled mod library, e.g. target/release/libmy_mod.so
+    #[arg(required = true, value_hint = ValueHint::FilePath)]
+    library: PathBuf,
+    /// Secret key written by `keygen`, the package is left unsigned without one
+    #[arg(short, long, value_hint = ValueHint::FilePath)]
+    key: Option<PathBuf>,
+    /// Defaults to <mod_id>.rustforge.tar
+    #[arg(short, long, value_hint = ValueHint::FilePath)]
+    output: Option<PathBuf>,
+}
+#[derive(Args, Debug, PartialEq)]
+struct ModInspectArgs {
+    #[arg(required = true, value_hint = ValueHint::FilePath)]
+    library: PathBuf,
+}
+#[derive(Args, Debug, PartialEq)]
+struct KeygenArgs {
+    /// File the hex encoded secret key is written to
+    #[arg(short, long, required = true, value_hint = ValueHint::FilePath)]
+    output: PathBuf,
+}
+#[derive(Args, Debug, PartialEq)]
+struct PackageArgs {
+    /// TOML file with the mod's `LibraryMetadata`
+    #[arg(short, long, required = true, value_hint = ValueHint::FilePath)]
+    metadata: PathBuf,
+    /// Secret key written by `keygen`, the package is left unsigned without one
+    #[arg(short, long, value_hint = ValueHint::FilePath)]
+    key: Option<PathBuf>,
+    #[arg(short, long, required = true, value_hint = ValueHint::FilePath)]
+    output: PathBuf,
+    /// Files to include under their file name, e.g. mod.so or wasm.toml and its modules
+    #[arg(required = true, value_hint = ValueHint::FilePath)]
+    files: Vec<PathBuf>,
 }
 #[derive(Args, Debug, PartialEq)]
 struct PackArgs {
     #[arg(short,required=true,value_hint=ValueHint::FilePath)]
     input: PathBuf,
+    /// Uid recorded as the creator of packed tasks, who can then list and delete them
+    #[arg(long)]
+    owner: Option<String>,
+}
+/// Writes a file only the current user can read.
+fn write_secret(path: &PathBuf, contents: &str) -> io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents.as_bytes())
+}
+fn build_package(args: &PackageArgs) -> Result<(), String> {
+    let metadata = std::fs::read_to_string(&args.metadata)
+        .map_err(|e| format!("Failed to read {}: {}", args.metadata.display(), e))?;
+    let metadata: LibraryMetadata = toml::from_str(&metadata)
+        .map_err(|e| format!("Invalid metadata {}: {}", args.metadata.display(), e))?;
+    let mut builder = PackageBuilder::new(metadata);
+    for path in &args.files {
+        let Some(name) = path.file_name() else {
+            return Err(format!("Invalid file {}", path.display()));
+        };
+        let bytes =
+            std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        builder = builder.file(name.to_string_lossy(), bytes);
+    }
+    write_package(builder, args.key.as_deref(), &args.output)
+}
+fn write_package(builder: PackageBuilder, key: Option<&Path>, output: &Path) -> Result<(), String> {
+    let key = key.map(read_signing_key).transpose()?;
+    let package = builder.build(key.as_ref())?;
+    std::fs::write(output, package)
+        .map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+    match key {
+        Some(key) => info!(
+            "Wrote {} signed by {}",
+            output.display(),
+            public_key_hex(&key)
+        ),
+        None => info!("Wrote unsigned {}", output.display()),
+    }
+    Ok(())
+}
+fn build_mod(args: &ModBuildArgs) -> Result<(), String> {
+    let inspection = inspect_library(&args.library)?;
+    let metadata = inspection
+        .metadata
+        .map_err(|e| format!("Cannot package {}: {}", args.library.display(), e))?;
+    let bytes = std::fs::read(&args.library)
+        .map_err(|e| format!("Failed to read {}: {}", args.library.display(), e))?;
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(format!("{}.rustforge.tar", metadata.mod_id)));
+    let builder = PackageBuilder::new(metadata).file(MOD_LIBRARY, bytes);
+    write_package(builder, args.key.as_deref(), &output)
+}
+fn inspect_mod(args: &ModInspectArgs) -> Result<(), String> {
+    let inspection = inspect_library(&args.library)?;
+    match &inspection.interface {
+        ModInterface::C { abi_version } => {
+            println!("{} C, ABI {}", "Interface:".bold(), abi_version)
+        }
+        ModInterface::Rust {
+            abi_version,
+            rustc_version,
+        } => println!(
+            "{} Rust, ABI {}, rustc {}",
+            "Interface:".bold(),
+            abi_version,
+            rustc_version
+        ),
+    }
+    let metadata = match inspection.metadata {
+        Ok(metadata) => {
+            println!("{} {}", "Engine ABI:".bold(), ABI_VERSION);
+            println!("{} {}", "Compatible:".bold(), "yes".green());
+            metadata
+        }
+        Err(e) => {
+            println!("{} {}", "Engine ABI:".bold(), ABI_VERSION);
+            println!("{} {} ({})", "Compatible:".bold(), "no".red(), e);
+            return Ok(());
+        }
+    };
+    for (field, value) in [
+        ("mod_id", &metadata.mod_id),
+        ("mod_name", &metadata.mod_name),
+        ("mod_version", &metadata.mod_version),
+        ("mod_author", &metadata.mod_author),
+        ("mod_description", &metadata.mod_description),
+        ("mod_license", &metadata.mod_license),
+        ("mod_credits", &metadata.mod_credits),
+        ("mod_display_url", &metadata.mod_display_url),
+        ("mod_issue_tracker", &metadata.mod_issue_tracker),
+        ("rustc_version", &metadata.rustc_version),
+        ("api_version", &metadata.api_version),
+    ] {
+        println!("{} {}", format!("{}:", field).bold(), value);
+    }
+    println!("{}", "Dependencies:".bold());
+    if metadata.mod_dependencies.is_empty() {
+        println!("  none");
+    }
+    for dep in &metadata.mod_dependencies {
+        let req = if dep.version_req.is_empty() {
+            "*"
+        } else {
+            &dep.version_req
+        };
+        println!("  {} {}", dep.mod_id, req);
+        if !dep.mod_git_repo.is_empty() {
+            println!("    {} @ {}", dep.mod_git_repo, dep.mod_git_commit);
+        }
+    }
+    Ok(())
 }
 fn print_completions<G: Generator>(generator: G, cmd: &mut Command) {
     generate(
@@ -97,12 +280,38 @@ async fn main() {
         print_completions(generator, &mut cmd);
     }
     let mut api = EngineAPI::default();
-    EngineAPI::init_packer(&mut api);
+    let mut cfg = Config::new();
+    cli.mods.apply(&mut cfg.config_toml);
+    EngineAPI::init_packer_with_config(&mut api, cfg);
     for (id, tsk) in api.task_registry.tasks.iter() {
         api.task_queue.tasks.entry(id.clone()).or_default();
     }
     if let Some(command) = cli.command {
         match command {
+            Commands::Keygen(args) => match generate_signing_key() {
+                Ok(key) => match write_secret(&args.output, &signing_key_hex(&key)) {
+                    Ok(()) => {
+                        info!("Wrote secret key to {}", args.output.display());
+                        println!("{}", public_key_hex(&key));
+                    }
+                    Err(e) => error!("Failed to write {}: {}", args.output.display(), e),
+                },
+                Err(e) => error!("Failed to generate key: {}", e),
+            },
+            Commands::Package(args) => {
+                if let Err(e) = build_package(&args) {
+                    error!("{}", e);
+                }
+            }
+            Commands::Mod { command } => {
+                let result = match command {
+                    ModCommands::Build(args) => build_mod(&args),
+                    ModCommands::Inspect(args) => inspect_mod(&args),
+                };
+                if let Err(e) = result {
+                    error!("{}", e);
+                }
+            }
             Commands::Schema => {
                 let mut buf: Vec<String> = Vec::new();
                 for tsk in api.task_registry.tasks {
@@ -153,7 +362,10 @@ async fn main() {
                     // Try to deserialize. Only on successful deserialization do we
                     // process entries and write the output TOML file.
                     let maybe_queue: Option<TaskQueue> =
-                        match postcard::from_bytes::<TaskQueue>(&buf) {
+                        // Files packed before tasks recorded their owner use the first layout
+                        match TaskQueue::decode(&buf, STORE_VERSION)
+                            .or_else(|_| TaskQueue::decode(&buf, 1))
+                        {
                             Ok(k) => Some(k),
                             Err(e) => {
                                 error!("Failed to deserialize task queue: {}", e);
@@ -206,6 +418,10 @@ async fn main() {
                 }
             }
             Commands::Pack(input) => {
+                let Some(owner) = input.owner.clone() else {
+                    error!("Packing needs --owner, the uid the packed tasks belong to");
+                    return;
+                };
                 if input.input.exists() {
                     info!("Packing File: {}", input.input.to_string_lossy());
                     match std::fs::read_to_string(&input.input) {
@@ -235,6 +451,7 @@ async fn main() {
                                                         vec.push(StoredTask {
                                                             id: "".into(), //ids are minted on the server
                                                             bytes: t.to_bytes(),
+                                                            created_by: owner.clone(),
                                                         });
                                                         api.task_queue.tasks.insert(key, vec);
                                                     }
@@ -1,13 +1,25 @@
-use engine::{get_auth, get_uid};
+use clap::Parser;
+use engine::{ModArgs, get_auth, get_peer, get_target, get_uid, resource_exhausted};
 use enginelib::api::postcard;
 use enginelib::{
     Identifier, RawIdentier, Registry,
     api::EngineAPI,
-    chrono::Utc,
+    audit::{AuditEntry, AuditFilter, AuditOutcome},
+    cgrpc::{CgrpcCode, CgrpcRequest, CgrpcResponse, CgrpcSchema, CgrpcStream, ContentType},
+    chrono::{DateTime, Utc},
+    config::Config,
     event::{debug, info, warn},
-    events::{self, Events, ID},
-    plugin::LibraryManager,
+    events::{
+        self, Events, ID,
+        task_event::{
+            TaskAcquireEvent, TaskAcquiredEvent, TaskCreateEvent, TaskCreatedEvent,
+            TaskDeleteEvent, TaskDeletedEvent, TaskPublishEvent, TaskPublishedEvent,
+        },
+    },
+    limits::{leased_by, queued_by},
+    plugin::{LibraryManager, ModContributions},
     task::{SolvedTasks, StoredExecutingTask, StoredTask, Task, TaskQueue},
+    tenant::Tenant,
 };
 use proto::{
     TaskState,
@@ -18,69 +30,733 @@ use std::{
     env::consts::OS,
     io::Read,
     net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4},
+    path::Path,
     sync::{Arc, RwLock as RS_RwLock},
+    time::Duration,
+};
+use tokio::{
+    sync::{RwLock, mpsc},
+    time::{Instant, sleep},
+};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{
+    Response, Status, Streaming,
+    metadata::{MetadataKey, MetadataMap, MetadataValue},
+    transport::Server,
 };
-use tokio::sync::RwLock;
-use tonic::{Request, Response, Status, metadata::MetadataValue, transport::Server};
 
 mod proto {
     tonic::include_proto!("engine");
     pub(crate) const FILE_DESCRIPTOR_SET: &[u8] =
         tonic::include_file_descriptor_set!("engine_descriptor");
 }
+/// Enforces the tenant boundary for a non-admin `uid` acting on `namespace`.
+fn check_tenant(
+    api: &EngineAPI,
+    uid: &str,
+    challenge: &str,
+    namespace: &str,
+) -> Result<(), Status> {
+    api.tenants
+        .authorize(uid, challenge, namespace)
+        .map_err(|e| {
+            info!("Tenant check failed for user {}: {}", uid, e);
+            Status::permission_denied(e)
+        })
+}
+/// Checks the queue quotas of `uid` and of its tenant before another task is created.
+fn check_queue_quota(api: &EngineAPI, uid: &str) -> Result<(), Status> {
+    if let Some(max_queued) = api.cfg.config_toml.limits_for(uid).max_queued
+        && queued_by(&api.task_queue, uid) >= max_queued
+    {
+        info!("Create Task denied - queue quota reached for user: {}", uid);
+        return Err(resource_exhausted("Queue quota exceeded", None));
+    }
+    if let Some(tenant) = api.tenants.of_uid(uid)
+        && let Some(max_queued) = tenant.max_queued
+        && tenant.queued(&api.task_queue) >= max_queued
+    {
+        info!(
+            "Create Task denied - queue quota reached for tenant: {}",
+            tenant.id
+        );
+        return Err(resource_exhausted("Tenant queue quota exceeded", None));
+    }
+    Ok(())
+}
+/// The task `uid` leased and is publishing, once the tenant and the task type are checked.
+fn leased_task(
+    api: &EngineAPI,
+    uid: &str,
+    challenge: &str,
+    key: &Identifier,
+    task_id: &str,
+) -> Result<StoredExecutingTask, Status> {
+    check_tenant(api, uid, challenge, &key.0)?;
+    if !api.task_registry.tasks.contains_key(key) {
+        warn!(
+            "Task acquisition failed - task does not exist: {}:{}",
+            key.0, key.1
+        );
+        return Err(Status::invalid_argument("Task Does not Exist"));
+    }
+    api.executing_tasks
+        .tasks
+        .get(key)
+        .and_then(|leased| {
+            leased
+                .iter()
+                .find(|f| f.id == task_id && f.user_id == uid)
+                .cloned()
+        })
+        .ok_or_else(|| Status::not_found("Invalid taskid or userid"))
+}
+/// Frames buffered per direction of a `CgrpcStream` call.
+const CGRPC_STREAM_BUFFER: usize = 16;
+
+/// The ASCII request metadata handed to cgrpc handlers, without the `authorization` secret.
+fn cgrpc_request_metadata(metadata: &MetadataMap) -> HashMap<String, String> {
+    metadata
+        .clone()
+        .into_headers()
+        .iter()
+        .filter(|(key, _)| *key != "authorization")
+        .filter_map(|(key, value)| Some((key.to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
+
+/// The status for a failed cgrpc response, carrying its metadata.
+fn cgrpc_status(response: CgrpcResponse) -> Status {
+    Status::with_metadata(
+        tonic::Code::from(response.code as i32),
+        response.message,
+        cgrpc_metadata(response.metadata),
+    )
+}
+
+/// Converts the metadata a cgrpc handler returned, skipping entries that are not valid ASCII
+/// metadata.
+fn cgrpc_metadata(entries: HashMap<String, String>) -> MetadataMap {
+    let mut metadata = MetadataMap::new();
+    for (key, value) in entries {
+        match (
+            MetadataKey::from_bytes(key.as_bytes()),
+            MetadataValue::try_from(value.as_str()),
+        ) {
+            (Ok(key), Ok(value)) => {
+                metadata.insert(key, value);
+            }
+            _ => warn!("Skipping invalid CGRPC response metadata {}", key),
+        }
+    }
+    metadata
+}
+fn tenant_from_proto(tenant: proto::Tenant) -> Tenant {
+    Tenant {
+        id: tenant.id,
+        namespaces: tenant.namespaces,
+        credentials: tenant.credentials,
+        max_queued: (tenant.max_queued != 0).then_some(tenant.max_queued as usize),
+        max_leased: (tenant.max_leased != 0).then_some(tenant.max_leased as usize),
+        lease_seconds: (tenant.lease_seconds != 0).then_some(tenant.lease_seconds),
+        weight: tenant.weight.max(1),
+    }
+}
+fn tenant_to_proto(tenant: &Tenant) -> proto::Tenant {
+    proto::Tenant {
+        id: tenant.id.clone(),
+        namespaces: tenant.namespaces.clone(),
+        credentials: tenant
+            .credentials
+            .keys()
+            .map(|uid| (uid.clone(), String::new()))
+            .collect(),
+        max_queued: tenant.max_queued.unwrap_or_default() as u64,
+        max_leased: tenant.max_leased.unwrap_or_default() as u64,
+        lease_seconds: tenant.lease_seconds.unwrap_or_default(),
+        weight: tenant.weight,
+    }
+}
 #[allow(non_snake_case)]
 struct EngineService {
     pub EngineAPI: Arc<RwLock<EngineAPI>>,
 }
+impl EngineService {
+    /// Runs the admin auth handlers, which may await, without holding the engine lock.
+    async fn is_admin(&self, challenge: String) -> bool {
+        Events::CheckAdminAuthAsync(&self.EngineAPI, challenge, ("".into(), "".into())).await
+    }
+    /// Runs the user auth handlers, which may await, without holding the engine lock.
+    async fn is_user(&self, uid: &str, challenge: String) -> bool {
+        Events::CheckAuthAsync(&self.EngineAPI, uid.to_string(), challenge).await
+    }
+    /// Authorizes an admin or a regular user, returning the uid whose tasks a non-admin is limited to.
+    async fn authorize_owner(
+        &self,
+        peer: &str,
+        rpc: &str,
+        uid: &str,
+        challenge: String,
+    ) -> Result<Option<String>, Status> {
+        if self.is_admin(challenge.clone()).await {
+            return Ok(None);
+        }
+        // Tasks without a recorded owner are owned by "", which only admins may touch.
+        if !uid.is_empty() && self.is_user(uid, challenge).await {
+            return Ok(Some(uid.to_string()));
+        }
+        warn!("Auth check failed - permission denied");
+        Err(self
+            .deny(
+                peer,
+                rpc,
+                Status::permission_denied("Invalid authentication"),
+            )
+            .await)
+    }
+    /// Runs an RPC handler, recording its outcome in the audit log.
+    async fn audited<T>(
+        &self,
+        uid: &str,
+        rpc: &str,
+        target: Identifier,
+        task_id: String,
+        subject: String,
+        handler: impl Future<Output = Result<Response<T>, Status>>,
+    ) -> Result<Response<T>, Status> {
+        let result = handler.await;
+        self.audit(uid, rpc, target, task_id, subject, &result)
+            .await;
+        result
+    }
+    async fn audit<T>(
+        &self,
+        uid: &str,
+        rpc: &str,
+        target: Identifier,
+        task_id: String,
+        subject: String,
+        result: &Result<Response<T>, Status>,
+    ) {
+        let outcome = match result {
+            Ok(_) => AuditOutcome::Success,
+            Err(status) => {
+                AuditOutcome::Failure(format!("{:?}: {}", status.code(), status.message()))
+            }
+        };
+        let api = self.EngineAPI.read().await;
+        api.audit_log
+            .record(AuditEntry::new(uid, rpc, target, task_id, outcome).with_subject(subject));
+    }
+    /// Stops handing out the given task types and waits up to `seconds` for their leased tasks.
+    async fn drain(&self, task_types: &[Identifier], seconds: u64) {
+        if seconds == 0 || task_types.is_empty() {
+            return;
+        }
+        let mut api = self.EngineAPI.write().await;
+        api.lib_manager.draining.extend(task_types.iter().cloned());
+        drop(api);
+        let deadline = Instant::now() + Duration::from_secs(seconds);
+        while Instant::now() < deadline {
+            let api = self.EngineAPI.read().await;
+            if task_types.iter().all(|key| {
+                api.executing_tasks
+                    .tasks
+                    .get(key)
+                    .is_none_or(|leased| leased.is_empty())
+            }) {
+                return;
+            }
+            drop(api);
+            sleep(Duration::from_millis(250)).await;
+        }
+        info!("Drain timed out, remaining leased tasks are requeued");
+    }
+    /// Checks that `mod_id` is loaded, returning the task types to drain.
+    async fn prepare_unload(
+        &self,
+        mod_id: &str,
+        with_dependents: bool,
+    ) -> Result<Vec<Identifier>, Status> {
+        let api = self.EngineAPI.read().await;
+        if api.lib_manager.loaded(mod_id).is_none() {
+            return Err(Status::not_found("Mod is not loaded"));
+        }
+        let mut mod_ids = vec![mod_id.to_string()];
+        if with_dependents {
+            mod_ids.extend(api.lib_manager.dependents(mod_id));
+        }
+        Ok(api.lib_manager.task_types(&mod_ids))
+    }
+    /// Takes a token from the bucket of an authenticated `uid`.
+    async fn check_rate_limit(&self, uid: &str, rpc: &str) -> Result<(), Status> {
+        let api = self.EngineAPI.read().await;
+        let limits = api.cfg.config_toml.limits_for(uid);
+        api.rate_limiter
+            .check(uid, rpc, limits.rate_limits.get(rpc))
+            .map_err(|retry_after| {
+                info!("{} denied for user {} - rate limit exceeded", rpc, uid);
+                resource_exhausted("Rate limit exceeded", Some(retry_after))
+            })
+    }
+    /// Charges a call that failed authentication to the bucket of its peer address rather than
+    /// its unverified uid, so nobody can exhaust the limits of a uid by naming it.
+    async fn deny(&self, peer: &str, rpc: &str, denied: Status) -> Status {
+        let api = self.EngineAPI.read().await;
+        let limits = &api.cfg.config_toml.limits;
+        let key = format!("peer:{}", peer);
+        match api
+            .rate_limiter
+            .check(&key, rpc, limits.rate_limits.get(rpc))
+        {
+            Ok(()) => denied,
+            Err(retry_after) => {
+                info!("{} denied for peer {} - rate limit exceeded", rpc, peer);
+                resource_exhausted("Rate limit exceeded", Some(retry_after))
+            }
+        }
+    }
+}
 #[tonic::async_trait]
 impl Engine for EngineService {
     async fn check_auth(
         &self,
         request: tonic::Request<proto::Empty>,
     ) -> Result<Response<proto::Empty>, Status> {
+        let uid = get_uid(&request);
+        self.audited(
+            &uid,
+            "CheckAuth",
+            ID("", ""),
+            String::new(),
+            String::new(),
+            self.handle_check_auth(request),
+        )
+        .await
+    }
+    async fn delete_task(
+        &self,
+        request: tonic::Request<proto::TaskSelector>,
+    ) -> Result<Response<proto::Empty>, Status> {
+        let uid = get_uid(&request);
+        let target = ID(&request.get_ref().namespace, &request.get_ref().task);
+        let task_id = request.get_ref().id.clone();
+        self.audited(
+            &uid,
+            "DeleteTask",
+            target,
+            task_id,
+            String::new(),
+            self.handle_delete_task(request),
+        )
+        .await
+    }
+    /// Retrieves a paginated list of tasks filtered by namespace, task name, and state.
+    ///
+    /// Authenticates the request and, if authorized, returns tasks in the specified state
+    /// (`Processing`, `Queued`, or `Solved`) for the given namespace and task name. The results
+    /// are sorted by task ID and paginated according to the requested page and page size.
+    /// Admins see every task, other users only the tasks they created.
+    ///
+    /// Returns a `TaskPage` containing the filtered tasks and pagination metadata, or a
+    /// permission denied error if authentication fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// // Example usage within a tonic gRPC client context:
+    /// let request = proto::TaskPageRequest {
+    ///     namespace: "example_ns".to_string(),
+    ///     task: "example_task".to_string(),
+    ///     state: proto::TaskState::Queued as i32,
+    ///     page: 0,
+    ///     page_size: 10,
+    /// };
+    /// let response = engine_client.get_tasks(request).await?;
+    /// assert!(response.get_ref().tasks.len() <= 10);
+    /// ```
+    async fn get_tasks(
+        &self,
+        request: tonic::Request<proto::TaskPageRequest>,
+    ) -> std::result::Result<tonic::Response<proto::TaskPage>, tonic::Status> {
+        let uid = get_uid(&request);
+        let target = ID(&request.get_ref().namespace, &request.get_ref().task);
+        self.audited(
+            &uid,
+            "GetTasks",
+            target,
+            String::new(),
+            String::new(),
+            self.handle_get_tasks(request),
+        )
+        .await
+    }
+    /// Handles custom gRPC messages with admin-level authentication.
+    ///
+    /// Processes a CGRPC request by verifying admin credentials and dispatching the event payload to the appropriate handler. Returns the processed event payload in the response. If authentication fails, returns a permission denied error.
+    ///
+    /// # Returns
+    /// A `Cgrpcmsg` response containing the processed event payload, or a permission denied gRPC status on failed authentication.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// // Example usage within a gRPC client context:
+    /// let request = proto::Cgrpcmsg {
+    ///     handler_mod_id: "mod".to_string(),
+    ///     handler_id: "handler".to_string(),
+    ///     event_payload: vec![1, 2, 3],
+    ///     // ... other fields ...
+    /// };
+    /// let response = engine_service.cgrpc(tonic::Request::new(request)).await?;
+    /// assert_eq!(response.get_ref().handler_mod_id, "mod");
+    /// ```
+    async fn cgrpc(
+        &self,
+        request: tonic::Request<proto::Cgrpcmsg>,
+    ) -> std::result::Result<tonic::Response<proto::Cgrpcmsg>, tonic::Status> {
+        let uid = get_uid(&request);
+        let target = ID(
+            &request.get_ref().handler_mod_id,
+            &request.get_ref().handler_id,
+        );
+        self.audited(
+            &uid,
+            "Cgrpc",
+            target,
+            String::new(),
+            String::new(),
+            self.handle_cgrpc(request),
+        )
+        .await
+    }
+    type CgrpcStreamStream = ReceiverStream<Result<proto::Cgrpcmsg, Status>>;
+    /// Opens a stream to the handler addressed by the first message, whose payload is passed
+    /// on as the first frame unless it is empty. Every later message is one frame.
+    async fn cgrpc_stream(
+        &self,
+        request: tonic::Request<Streaming<proto::Cgrpcmsg>>,
+    ) -> Result<tonic::Response<Self::CgrpcStreamStream>, tonic::Status> {
+        let uid = get_uid(&request);
+        let mut target = ID("", "");
+        let result = self.handle_cgrpc_stream(request, &mut target).await;
+        self.audit(
+            &uid,
+            "CgrpcStream",
+            target,
+            String::new(),
+            String::new(),
+            &result,
+        )
+        .await;
+        result
+    }
+    async fn aquire_task_reg(
+        &self,
+        request: tonic::Request<proto::Empty>,
+    ) -> Result<tonic::Response<proto::TaskRegistry>, tonic::Status> {
+        let uid = get_uid(&request);
+        self.audited(
+            &uid,
+            "AquireTaskReg",
+            ID("", ""),
+            String::new(),
+            String::new(),
+            self.handle_aquire_task_reg(request),
+        )
+        .await
+    }
+    async fn aquire_task(
+        &self,
+        request: tonic::Request<proto::TaskRequest>,
+    ) -> Result<tonic::Response<proto::Task>, tonic::Status> {
+        let uid = get_uid(&request);
+        let target = get_target(&request.get_ref().task_id);
+        let result = self.handle_aquire_task(request).await;
+        let task_id = result
+            .as_ref()
+            .map(|r| r.get_ref().id.clone())
+            .unwrap_or_default();
+        self.audit(&uid, "AquireTask", target, task_id, String::new(), &result)
+            .await;
+        result
+    }
+    async fn publish_task(
+        &self,
+        request: tonic::Request<proto::Task>,
+    ) -> Result<tonic::Response<proto::Empty>, tonic::Status> {
+        let uid = get_uid(&request);
+        let target = get_target(&request.get_ref().task_id);
+        let task_id = request.get_ref().id.clone();
+        self.audited(
+            &uid,
+            "PublishTask",
+            target,
+            task_id,
+            String::new(),
+            self.handle_publish_task(request),
+        )
+        .await
+    }
+    async fn create_task(
+        &self,
+        request: tonic::Request<proto::Task>,
+    ) -> Result<tonic::Response<proto::Task>, tonic::Status> {
+        let uid = get_uid(&request);
+        let target = get_target(&request.get_ref().task_id);
+        let result = self.handle_create_task(request).await;
+        let task_id = result
+            .as_ref()
+            .map(|r| r.get_ref().id.clone())
+            .unwrap_or_default();
+        self.audit(&uid, "CreateTask", target, task_id, String::new(), &result)
+            .await;
+        result
+    }
+    async fn query_audit_log(
+        &self,
+        request: tonic::Request<proto::AuditQuery>,
+    ) -> Result<tonic::Response<proto::AuditLog>, tonic::Status> {
+        let uid = get_uid(&request);
+        self.audited(
+            &uid,
+            "QueryAuditLog",
+            ID("", ""),
+            String::new(),
+            String::new(),
+            self.handle_query_audit_log(request),
+        )
+        .await
+    }
+    async fn put_tenant(
+        &self,
+        request: tonic::Request<proto::Tenant>,
+    ) -> Result<tonic::Response<proto::Empty>, tonic::Status> {
+        let uid = get_uid(&request);
+        let tenant_id = request.get_ref().id.clone();
+        self.audited(
+            &uid,
+            "PutTenant",
+            ID("", ""),
+            String::new(),
+            tenant_id,
+            self.handle_put_tenant(request),
+        )
+        .await
+    }
+    async fn delete_tenant(
+        &self,
+        request: tonic::Request<proto::TenantSelector>,
+    ) -> Result<tonic::Response<proto::Empty>, tonic::Status> {
+        let uid = get_uid(&request);
+        let tenant_id = request.get_ref().id.clone();
+        self.audited(
+            &uid,
+            "DeleteTenant",
+            ID("", ""),
+            String::new(),
+            tenant_id,
+            self.handle_delete_tenant(request),
+        )
+        .await
+    }
+    async fn list_tenants(
+        &self,
+        request: tonic::Request<proto::Empty>,
+    ) -> Result<tonic::Response<proto::TenantList>, tonic::Status> {
+        let uid = get_uid(&request);
+        self.audited(
+            &uid,
+            "ListTenants",
+            ID("", ""),
+            String::new(),
+            String::new(),
+            self.handle_list_tenants(request),
+        )
+        .await
+    }
+    async fn load_mod(
+        &self,
+        request: tonic::Request<proto::ModSource>,
+    ) -> Result<tonic::Response<proto::Empty>, tonic::Status> {
+        let uid = get_uid(&request);
+        let path = request.get_ref().path.clone();
+        self.audited(
+            &uid,
+            "LoadMod",
+            ID("", ""),
+            String::new(),
+            path,
+            self.handle_load_mod(request),
+        )
+        .await
+    }
+    async fn unload_mod(
+        &self,
+        request: tonic::Request<proto::ModSelector>,
+    ) -> Result<tonic::Response<proto::Empty>, tonic::Status> {
+        let uid = get_uid(&request);
+        let mod_id = request.get_ref().mod_id.clone();
+        self.audited(
+            &uid,
+            "UnloadMod",
+            ID("", ""),
+            String::new(),
+            mod_id,
+            self.handle_unload_mod(request),
+        )
+        .await
+    }
+    async fn list_mods(
+        &self,
+        request: tonic::Request<proto::Empty>,
+    ) -> Result<tonic::Response<proto::ModList>, tonic::Status> {
+        let uid = get_uid(&request);
+        self.audited(
+            &uid,
+            "ListMods",
+            ID("", ""),
+            String::new(),
+            String::new(),
+            self.handle_list_mods(request),
+        )
+        .await
+    }
+    async fn list_cgrpc_handlers(
+        &self,
+        request: tonic::Request<proto::Empty>,
+    ) -> Result<tonic::Response<proto::CgrpcHandlerList>, tonic::Status> {
+        let uid = get_uid(&request);
+        self.audited(
+            &uid,
+            "ListCgrpcHandlers",
+            ID("", ""),
+            String::new(),
+            String::new(),
+            self.handle_list_cgrpc_handlers(request),
+        )
+        .await
+    }
+    async fn reload_mod(
+        &self,
+        request: tonic::Request<proto::ModSelector>,
+    ) -> Result<tonic::Response<proto::Empty>, tonic::Status> {
+        let uid = get_uid(&request);
+        let mod_id = request.get_ref().mod_id.clone();
+        self.audited(
+            &uid,
+            "ReloadMod",
+            ID("", ""),
+            String::new(),
+            mod_id,
+            self.handle_reload_mod(request),
+        )
+        .await
+    }
+}
+/// The RPC handlers, audited by the [`Engine`] impl.
+impl EngineService {
+    async fn handle_check_auth(
+        &self,
+        request: tonic::Request<proto::Empty>,
+    ) -> Result<Response<proto::Empty>, Status> {
+        let uid = get_uid(&request);
+        let peer = get_peer(&request);
         let challenge = get_auth(&request);
-        let mut api = self.EngineAPI.write().await;
-        let db = api.db.clone();
-        let output = Events::CheckAdminAuth(&mut api, challenge, ("".into(), "".into()), db);
-        if !output {
+        if !self.is_admin(challenge).await {
             warn!("Auth check failed - permission denied");
-            return Err(tonic::Status::permission_denied("Invalid Auth"));
+            let denied = tonic::Status::permission_denied("Invalid Auth");
+            return Err(self.deny(&peer, "CheckAuth", denied).await);
         };
-        return Ok(tonic::Response::new(proto::Empty {}));
+        self.check_rate_limit(&uid, "CheckAuth").await?;
+        Ok(tonic::Response::new(proto::Empty {}))
     }
-    async fn delete_task(
+    async fn handle_delete_task(
         &self,
         request: tonic::Request<proto::TaskSelector>,
     ) -> Result<Response<proto::Empty>, Status> {
-        let mut api = self.EngineAPI.write().await;
+        let uid = get_uid(&request);
+        let peer = get_peer(&request);
+        let target = ID(&request.get_ref().namespace, &request.get_ref().task);
         let data = request.get_ref();
         let challenge = get_auth(&request);
-        let db = api.db.clone();
         let id = ID(&data.namespace, &data.task);
 
-        let output = Events::CheckAdminAuth(&mut api, challenge, ("".into(), "".into()), db);
-        if !output {
-            warn!("Auth check failed - permission denied");
-            return Err(tonic::Status::permission_denied("Invalid Auth"));
+        let owner = self
+            .authorize_owner(&peer, "DeleteTask", &uid, challenge.clone())
+            .await?;
+        self.check_rate_limit(&uid, "DeleteTask").await?;
+        // Mods only see the payload once the caller is known to own the task.
+        let payload = {
+            let api = self.EngineAPI.read().await;
+            if owner.is_some() {
+                check_tenant(&api, &uid, &challenge, &target.0)?;
+            }
+            let found = match data.state() {
+                TaskState::Processing => api.executing_tasks.tasks.get(&id).and_then(|q| {
+                    q.iter()
+                        .find(|f| f.id == data.id)
+                        .map(|f| (f.created_by.clone(), f.bytes.clone()))
+                }),
+                TaskState::Solved => api.solved_tasks.tasks.get(&id).and_then(|q| {
+                    q.iter()
+                        .find(|f| f.id == data.id)
+                        .map(|f| (f.created_by.clone(), f.bytes.clone()))
+                }),
+                TaskState::Queued => api.task_queue.tasks.get(&id).and_then(|q| {
+                    q.iter()
+                        .find(|f| f.id == data.id)
+                        .map(|f| (f.created_by.clone(), f.bytes.clone()))
+                }),
+            };
+            if let Some((created_by, _)) = &found
+                && owner.as_ref().is_some_and(|owner| owner != created_by)
+            {
+                info!(
+                    "DeleteTask: User {} does not own task with id {}",
+                    uid, data.id
+                );
+                return Err(Status::permission_denied("Task is owned by another user"));
+            }
+            found.map(|(_, bytes)| bytes)
         };
-        // Generic helper for removing a task by id from a collection, using an id extractor closure
-        fn delete_task_from_collection<T, F>(
+        // Unknown ids fall through to the not found errors below
+        if let Some(payload) = &payload
+            && Events::dispatch(
+                &self.EngineAPI,
+                &mut TaskDeleteEvent::new(
+                    id.clone(),
+                    data.id.clone(),
+                    uid.clone(),
+                    payload.clone(),
+                ),
+            )
+            .await
+        {
+            info!("DeleteTask: Deleting task {} cancelled by a mod", data.id);
+            return Err(Status::aborted("Task deletion cancelled"));
+        }
+        let mut api = self.EngineAPI.write().await;
+        // Generic helper for removing a task by id from a collection, using id and owner extractor closures
+        #[allow(clippy::too_many_arguments)]
+        fn delete_task_from_collection<T, F, O>(
             collection: &mut HashMap<(String, String), Vec<T>>,
             id: &(String, String),
             task_id: &str,
+            owner: Option<&str>,
             state_name: &str,
             namespace: &str,
             task: &str,
             id_extractor: F,
+            owner_extractor: O,
         ) -> Result<(), Status>
         where
             F: Fn(&T) -> &str,
+            O: Fn(&T) -> &str,
         {
             match collection.get_mut(id) {
                 Some(query) => {
-                    let orig_len = query.len();
-                    query.retain(|f| id_extractor(f) != task_id);
-                    if query.len() == orig_len {
+                    let Some(pos) = query.iter().position(|f| id_extractor(f) == task_id) else {
                         info!(
                             "DeleteTask: Task with id {} not found in {} state for namespace: {}, task: {}",
                             task_id, state_name, namespace, task
@@ -89,7 +765,17 @@ impl Engine for EngineService {
                             "Task with id {} not found in {} state",
                             task_id, state_name
                         )));
+                    };
+                    if let Some(owner) = owner
+                        && owner_extractor(&query[pos]) != owner
+                    {
+                        info!(
+                            "DeleteTask: User {} does not own task with id {} in {} state",
+                            owner, task_id, state_name
+                        );
+                        return Err(Status::permission_denied("Task is owned by another user"));
                     }
+                    query.remove(pos);
                     Ok(())
                 }
                 None => {
@@ -111,28 +797,34 @@ impl Engine for EngineService {
                 &mut api.executing_tasks.tasks,
                 &id,
                 &data.id,
+                owner.as_deref(),
                 "Processing",
                 &data.namespace,
                 &data.task,
                 |f| &f.id,
+                |f| &f.created_by,
             ),
             TaskState::Solved => delete_task_from_collection(
                 &mut api.solved_tasks.tasks,
                 &id,
                 &data.id,
+                owner.as_deref(),
                 "Solved",
                 &data.namespace,
                 &data.task,
                 |f| &f.id,
+                |f| &f.created_by,
             ),
             TaskState::Queued => delete_task_from_collection(
                 &mut api.task_queue.tasks,
                 &id,
                 &data.id,
+                owner.as_deref(),
                 "Queued",
                 &data.namespace,
                 &data.task,
                 |f| &f.id,
+                |f| &f.created_by,
             ),
         };
 
@@ -149,43 +841,36 @@ impl Engine for EngineService {
             data.namespace,
             data.task
         );
+        drop(api);
+        Events::dispatch(
+            &self.EngineAPI,
+            &mut TaskDeletedEvent::new(
+                id,
+                data.id.clone(),
+                uid.clone(),
+                payload.unwrap_or_default(),
+            ),
+        )
+        .await;
         Ok(tonic::Response::new(proto::Empty {}))
     }
-    /// Retrieves a paginated list of tasks filtered by namespace, task name, and state.
-    ///
-    /// Authenticates the request and, if authorized, returns tasks in the specified state
-    /// (`Processing`, `Queued`, or `Solved`) for the given namespace and task name. The results
-    /// are sorted by task ID and paginated according to the requested page and page size.
-    ///
-    /// Returns a `TaskPage` containing the filtered tasks and pagination metadata, or a
-    /// permission denied error if authentication fails.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// // Example usage within a tonic gRPC client context:
-    /// let request = proto::TaskPageRequest {
-    ///     namespace: "example_ns".to_string(),
-    ///     task: "example_task".to_string(),
-    ///     state: proto::TaskState::Queued as i32,
-    ///     page: 0,
-    ///     page_size: 10,
-    /// };
-    /// let response = engine_client.get_tasks(request).await?;
-    /// assert!(response.get_ref().tasks.len() <= 10);
-    /// ```
-    async fn get_tasks(
+    async fn handle_get_tasks(
         &self,
         request: tonic::Request<proto::TaskPageRequest>,
     ) -> std::result::Result<tonic::Response<proto::TaskPage>, tonic::Status> {
-        let mut api = self.EngineAPI.write().await;
+        let uid = get_uid(&request);
+        let peer = get_peer(&request);
+        let target = ID(&request.get_ref().namespace, &request.get_ref().task);
         let challenge = get_auth(&request);
 
-        let db = api.db.clone();
-        if !Events::CheckAdminAuth(&mut api, challenge, ("".into(), "".into()), db) {
-            info!("GetTask denied due to Invalid Auth");
-            return Err(Status::permission_denied("Invalid authentication"));
-        };
+        let owner = self
+            .authorize_owner(&peer, "GetTasks", &uid, challenge.clone())
+            .await?;
+        self.check_rate_limit(&uid, "GetTasks").await?;
+        let api = self.EngineAPI.read().await;
+        if owner.is_some() {
+            check_tenant(&api, &uid, &challenge, &target.0)?;
+        }
         let data = request.get_ref();
 
         let q: Vec<proto::Task> = match data.clone().state() {
@@ -196,7 +881,10 @@ impl Engine for EngineService {
                     .get(&(data.namespace.clone(), data.task.clone()))
                 {
                     Some(tasks) => {
-                        let mut task_refs: Vec<_> = tasks.iter().collect();
+                        let mut task_refs: Vec<_> = tasks
+                            .iter()
+                            .filter(|f| owner.as_ref().is_none_or(|uid| &f.created_by == uid))
+                            .collect();
                         task_refs.sort_by_key(|f| &f.id);
                         task_refs
                             .iter()
@@ -224,7 +912,11 @@ impl Engine for EngineService {
                     .get(&(data.namespace.clone(), data.task.clone()))
                 {
                     Some(tasks) => {
-                        let mut d = tasks.clone();
+                        let mut d: Vec<_> = tasks
+                            .iter()
+                            .filter(|f| owner.as_ref().is_none_or(|uid| &f.created_by == uid))
+                            .cloned()
+                            .collect();
                         d.sort_by_key(|f| f.id.clone());
                         d.iter()
                             .map(|f| proto::Task {
@@ -251,7 +943,11 @@ impl Engine for EngineService {
                     .get(&(data.namespace.clone(), data.task.clone()))
                 {
                     Some(tasks) => {
-                        let mut d = tasks.clone();
+                        let mut d: Vec<_> = tasks
+                            .iter()
+                            .filter(|f| owner.as_ref().is_none_or(|uid| &f.created_by == uid))
+                            .cloned()
+                            .collect();
                         d.sort_by_key(|f| f.id.clone());
                         d.iter()
                             .map(|f| proto::Task {
@@ -280,100 +976,202 @@ impl Engine for EngineService {
             .take(data.page_size as usize)
             .cloned()
             .collect();
-        return Ok(tonic::Response::new(proto::TaskPage {
+        Ok(tonic::Response::new(proto::TaskPage {
             namespace: data.namespace.clone(),
             task: data.task.clone(),
             page: data.page,
             page_size: data.page_size,
             state: data.state,
             tasks: final_vec,
-        }));
+        }))
     }
-    /// Handles custom gRPC messages with admin-level authentication.
-    ///
-    /// Processes a CGRPC request by verifying admin credentials and dispatching the event payload to the appropriate handler. Returns the processed event payload in the response. If authentication fails, returns a permission denied error.
-    ///
-    /// # Returns
-    /// A `Cgrpcmsg` response containing the processed event payload, or a permission denied gRPC status on failed authentication.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// // Example usage within a gRPC client context:
-    /// let request = proto::Cgrpcmsg {
-    ///     handler_mod_id: "mod".to_string(),
-    ///     handler_id: "handler".to_string(),
-    ///     event_payload: vec![1, 2, 3],
-    ///     // ... other fields ...
-    /// };
-    /// let response = engine_service.cgrpc(tonic::Request::new(request)).await?;
-    /// assert_eq!(response.get_ref().handler_mod_id, "mod");
-    /// ```
-    async fn cgrpc(
+    async fn handle_cgrpc(
         &self,
         request: tonic::Request<proto::Cgrpcmsg>,
     ) -> std::result::Result<tonic::Response<proto::Cgrpcmsg>, tonic::Status> {
+        let uid = get_uid(&request);
+        let peer = get_peer(&request);
+        let target = ID(
+            &request.get_ref().handler_mod_id,
+            &request.get_ref().handler_id,
+        );
         info!(
             "CGRPC request received for handler: {}:{}",
             request.get_ref().handler_mod_id,
             request.get_ref().handler_id
         );
-        let mut api = self.EngineAPI.write().await;
         let challenge = get_auth(&request);
-        let db = api.db.clone();
         debug!("Checking admin authentication for CGRPC request");
-        let output = Events::CheckAdminAuth(
-            &mut api,
+        let output = Events::CheckAdminAuthAsync(
+            &self.EngineAPI,
             challenge,
             (
                 request.get_ref().handler_mod_id.clone(),
                 request.get_ref().handler_id.clone(),
             ),
-            db,
-        );
+        )
+        .await;
         if !output {
             warn!("CGRPC auth check failed - permission denied");
-            return Err(tonic::Status::permission_denied("Invalid CGRPC Auth"));
+            let denied = tonic::Status::permission_denied("Invalid CGRPC Auth");
+            return Err(self.deny(&peer, "Cgrpc", denied).await);
         };
-        let out = Arc::new(std::sync::RwLock::new(Vec::new()));
-        debug!("Dispatching CGRPC event to handler");
-        Events::CgrpcEvent(
-            &mut api,
-            ID("engine_core", "grpc"),
-            request.get_ref().event_payload.clone(),
-            out.clone(),
-        );
+        self.check_rate_limit(&uid, "Cgrpc").await?;
+        let metadata = cgrpc_request_metadata(request.metadata());
+        let content_type = ContentType::parse(&request.get_ref().content_type)
+            .map_err(Status::invalid_argument)?;
+        let cgrpc_request = CgrpcRequest {
+            handler: target.clone(),
+            uid: uid.clone(),
+            content_type,
+            payload: request.get_ref().event_payload.clone(),
+            metadata,
+        };
+        debug!("Dispatching CGRPC request to handler");
+        // Handlers run under the read lock so their mod can't be unloaded meanwhile.
+        let api = self.EngineAPI.read().await;
+        let Some(handler) = api.cgrpc_registry.get(&target) else {
+            info!("CGRPC handler {}:{} not found", target.0, target.1);
+            return Err(Status::not_found("CGRPC handler not found"));
+        };
+        let response = handler.handle(cgrpc_request);
+        drop(handler);
+        drop(api);
+        if response.code != CgrpcCode::Ok {
+            info!(
+                "CGRPC handler {}:{} failed: {:?} {}",
+                target.0, target.1, response.code, response.message
+            );
+            return Err(cgrpc_status(response));
+        }
         let mut res = request.get_ref().clone();
-        res.event_payload = match out.read() {
-            Ok(g) => g.clone(),
-            Err(_) => {
-                warn!("CGRPC response lock poisoned, returning empty payload");
-                Vec::new()
+        res.event_payload = response.payload;
+        res.content_type = content_type.as_str().to_string();
+        let mut res = tonic::Response::new(res);
+        *res.metadata_mut() = cgrpc_metadata(response.metadata);
+        info!("CGRPC request processed successfully");
+        Ok(res)
+    }
+    /// Serves [`Engine::cgrpc_stream`], setting `target` once the first message names it.
+    async fn handle_cgrpc_stream(
+        &self,
+        request: tonic::Request<Streaming<proto::Cgrpcmsg>>,
+        target: &mut Identifier,
+    ) -> Result<tonic::Response<ReceiverStream<Result<proto::Cgrpcmsg, Status>>>, tonic::Status>
+    {
+        let uid = get_uid(&request);
+        let peer = get_peer(&request);
+        let challenge = get_auth(&request);
+        let metadata = cgrpc_request_metadata(request.metadata());
+        let mut inbound = request.into_inner();
+        let first = inbound
+            .message()
+            .await?
+            .ok_or_else(|| Status::invalid_argument("Empty CGRPC stream"))?;
+        *target = ID(&first.handler_mod_id, &first.handler_id);
+        info!(
+            "CGRPC stream requested for handler: {}:{}",
+            target.0, target.1
+        );
+        if !Events::CheckAdminAuthAsync(&self.EngineAPI, challenge, target.clone()).await {
+            warn!("CGRPC stream auth check failed - permission denied");
+            let denied = Status::permission_denied("Invalid CGRPC Auth");
+            return Err(self.deny(&peer, "CgrpcStream", denied).await);
+        }
+        self.check_rate_limit(&uid, "CgrpcStream").await?;
+        let (library, handler) = {
+            let api = self.EngineAPI.read().await;
+            let Some(handler) = api.cgrpc_registry.get(target) else {
+                info!("CGRPC handler {}:{} not found", target.0, target.1);
+                return Err(Status::not_found("CGRPC handler not found"));
+            };
+            let owner = api.cgrpc_registry.owners.get(target);
+            let library = owner.and_then(|owner| api.lib_manager.library(owner));
+            (library, handler)
+        };
+        if !handler.streaming() {
+            return Err(Status::unimplemented(
+                "CGRPC handler does not serve streams",
+            ));
+        }
+        let (in_tx, in_rx) = mpsc::channel(CGRPC_STREAM_BUFFER);
+        let (frames_tx, mut frames_rx) = mpsc::channel::<Vec<u8>>(CGRPC_STREAM_BUFFER);
+        let (out_tx, out_rx) = mpsc::channel(CGRPC_STREAM_BUFFER);
+        if !first.event_payload.is_empty() {
+            let _ = in_tx.send(first.event_payload).await;
+        }
+        tokio::spawn(async move {
+            while let Ok(Some(msg)) = inbound.message().await {
+                if in_tx.send(msg.event_payload).await.is_err() {
+                    break;
+                }
+            }
+        });
+        // Unlike unary calls the handler runs without the engine lock, for as long as the
+        // client keeps the stream open.
+        let handling = handler.handle_stream(CgrpcStream {
+            handler: target.clone(),
+            uid: uid.clone(),
+            metadata,
+            incoming: in_rx,
+            outgoing: frames_tx,
+        });
+        let (handler_mod_id, handler_id) = target.clone();
+        let frames_out = out_tx.clone();
+        let forward = async move {
+            while let Some(payload) = frames_rx.recv().await {
+                let msg = proto::Cgrpcmsg {
+                    handler_mod_id: handler_mod_id.clone(),
+                    handler_id: handler_id.clone(),
+                    event_payload: payload,
+                    ..Default::default()
+                };
+                if frames_out.send(Ok(msg)).await.is_err() {
+                    break;
+                }
             }
         };
-        info!("CGRPC request processed successfully");
-        return Ok(tonic::Response::new(res));
+        // The handler and its future are dropped before the library of their mod, which stays
+        // mapped until then even if the mod is unloaded while the stream is open.
+        let running = (handling, handler, library);
+        tokio::spawn(async move {
+            let mut running = running;
+            let (result, ()) = tokio::join!(&mut running.0, forward);
+            if let Err(response) = result {
+                info!(
+                    "CGRPC stream failed: {:?} {}",
+                    response.code, response.message
+                );
+                let _ = out_tx.send(Err(cgrpc_status(response))).await;
+            }
+        });
+        Ok(tonic::Response::new(ReceiverStream::new(out_rx)))
     }
-    async fn aquire_task_reg(
+    async fn handle_aquire_task_reg(
         &self,
         request: tonic::Request<proto::Empty>,
     ) -> Result<tonic::Response<proto::TaskRegistry>, tonic::Status> {
         let uid = get_uid(&request);
+        let peer = get_peer(&request);
         let challenge = get_auth(&request);
         info!("Task registry request received from user: {}", uid);
-        let mut api = self.EngineAPI.write().await;
-        let db = api.db.clone();
-
         debug!("Validating authentication for task registry request");
-        if !Events::CheckAuth(&mut api, uid.clone(), challenge, db) {
+        if !self.is_user(&uid, challenge.clone()).await {
             info!(
                 "Task registry request denied - invalid authentication for user: {}",
                 uid
             );
-            return Err(Status::permission_denied("Invalid authentication"));
+            let denied = Status::permission_denied("Invalid authentication");
+            return Err(self.deny(&peer, "AquireTaskReg", denied).await);
         };
+        self.check_rate_limit(&uid, "AquireTaskReg").await?;
+        let api = self.EngineAPI.read().await;
+        check_tenant(&api, &uid, &challenge, "")?;
         let mut tasks: Vec<RawIdentier> = Vec::new();
         for (k, v) in &api.task_registry.tasks {
+            if api.tenants.authorize(&uid, &challenge, &k.0).is_err() {
+                continue;
+            }
             let js: Vec<String> = vec![k.0.clone(), k.1.clone()];
             let jstr = js.join(":");
             tasks.push(jstr);
@@ -382,30 +1180,56 @@ impl Engine for EngineService {
         let response = proto::TaskRegistry { tasks };
         Ok(tonic::Response::new(response))
     }
-
-    async fn aquire_task(
+    async fn handle_aquire_task(
         &self,
         request: tonic::Request<proto::TaskRequest>,
     ) -> Result<tonic::Response<proto::Task>, tonic::Status> {
+        let uid = get_uid(&request);
+        let peer = get_peer(&request);
+        let target = get_target(&request.get_ref().task_id);
         let challenge = get_auth(&request);
         let input = request.get_ref();
         let task_id = input.task_id.clone();
-        let uid = get_uid(&request);
         info!(
             "Task acquisition request received from user: {} for task: {}",
             uid, task_id
         );
 
-        let mut api = self.EngineAPI.write().await;
-        let db = api.db.clone();
         debug!("Validating authentication for task acquisition");
-        if !Events::CheckAuth(&mut api, uid.clone(), challenge, db) {
+        if !self.is_user(&uid, challenge.clone()).await {
             info!(
                 "Task acquisition denied - invalid authentication for user: {}",
                 uid
             );
-            return Err(Status::permission_denied("Invalid authentication"));
+            let denied = Status::permission_denied("Invalid authentication");
+            return Err(self.deny(&peer, "AquireTask", denied).await);
         };
+        self.check_rate_limit(&uid, "AquireTask").await?;
+        let mut api = self.EngineAPI.write().await;
+        if api.shutting_down {
+            info!("Task acquisition denied - engine is shutting down");
+            return Err(Status::unavailable("Engine is shutting down"));
+        }
+        check_tenant(&api, &uid, &challenge, &target.0)?;
+        if let Some(max_leased) = api.cfg.config_toml.limits_for(&uid).max_leased
+            && leased_by(&api.executing_tasks, &uid) >= max_leased
+        {
+            info!(
+                "Task acquisition denied - lease quota reached for user: {}",
+                uid
+            );
+            return Err(resource_exhausted("Lease quota exceeded", None));
+        }
+        if let Some(tenant) = api.tenants.of_uid(&uid)
+            && let Some(max_leased) = tenant.max_leased
+            && tenant.leased(&api.executing_tasks) >= max_leased
+        {
+            info!(
+                "Task acquisition denied - lease quota reached for tenant: {}",
+                tenant.id
+            );
+            return Err(resource_exhausted("Tenant lease quota exceeded", None));
+        }
 
         // Todo: check for wrong input to not cause a Panic out of bounds.
         let alen = &task_id.split(":").collect::<Vec<&str>>().len();
@@ -429,17 +1253,19 @@ impl Engine for EngineService {
             return Err(Status::invalid_argument("Task Does not Exist"));
         }
         let key = ID(namespace, task_name);
-        let mut map = match api.task_queue.tasks.get(&key) {
-            Some(v) if !v.is_empty() => v.clone(),
-            _ => {
-                info!("No queued tasks for {}:{}", namespace, task_name);
-                return Err(Status::not_found("No queued tasks available"));
-            }
+        if api.lib_manager.draining.contains(&key) {
+            info!(
+                "Task acquisition denied - {}:{} is being unloaded",
+                namespace, task_name
+            );
+            return Err(Status::unavailable("Task type is being unloaded"));
+        }
+        // Get Task and remove it from queue
+        let Some(ttask) = api.take_next_task(&key) else {
+            info!("No queued tasks for {}:{}", namespace, task_name);
+            return Err(Status::not_found("No queued tasks available"));
         };
-        let ttask = map.remove(0);
         let task_payload = ttask.bytes.clone();
-        // Get Task and remove it from queue
-        api.task_queue.tasks.insert(key.clone(), map);
         match postcard::to_allocvec(&api.task_queue.clone()) {
             Ok(store) => {
                 if let Err(e) = api.db.insert("tasks", store) {
@@ -460,7 +1286,9 @@ impl Engine for EngineService {
         exec_tsks.push(enginelib::task::StoredExecutingTask {
             bytes: task_payload.clone(),
             user_id: uid.clone(),
+            created_by: ttask.created_by.clone(),
             given_at: Utc::now(),
+            lease_seconds: api.lease_seconds_for(&uid),
             id: ttask.id.clone(),
         });
         api.executing_tasks.tasks.insert(key.clone(), exec_tsks);
@@ -474,22 +1302,47 @@ impl Engine for EngineService {
                 return Err(Status::internal(format!("Serialization error: {}", e)));
             }
         }
+        drop(api);
+        let mut event =
+            TaskAcquireEvent::new(key.clone(), ttask.id.clone(), uid.clone(), task_payload);
+        if Events::dispatch(&self.EngineAPI, &mut event).await {
+            info!(
+                "Task acquisition of {} cancelled by a mod, returning it to the queue",
+                ttask.id
+            );
+            let mut api = self.EngineAPI.write().await;
+            if let Some(exec_tsks) = api.executing_tasks.tasks.get_mut(&key) {
+                exec_tsks.retain(|f| f.id != ttask.id);
+            }
+            api.task_queue
+                .tasks
+                .entry(key)
+                .or_default()
+                .insert(0, ttask);
+            EngineAPI::sync_db(&mut api);
+            return Err(Status::aborted("Task acquisition cancelled"));
+        }
+        // A rewritten payload only goes to the worker, the lease keeps the stored one.
         let response = proto::Task {
             id: ttask.id,
             task_id: input.task_id.clone(),
-            task_payload,
+            task_payload: event.payload.clone(),
             payload: Vec::new(),
         };
+        Events::dispatch(
+            &self.EngineAPI,
+            &mut TaskAcquiredEvent::new(event.task, event.task_id, uid.clone(), event.payload),
+        )
+        .await;
         Ok(tonic::Response::new(response))
     }
-    async fn publish_task(
+    async fn handle_publish_task(
         &self,
         request: tonic::Request<proto::Task>,
     ) -> Result<tonic::Response<proto::Empty>, tonic::Status> {
-        let mut api = self.EngineAPI.write().await;
-        let challenge = get_auth(&request);
         let uid = get_uid(&request);
-        let db = api.db.clone();
+        let peer = get_peer(&request);
+        let challenge = get_auth(&request);
 
         let task_id = request.get_ref().task_id.clone();
         let alen = &task_id.split(":").collect::<Vec<&str>>().len();
@@ -499,98 +1352,109 @@ impl Engine for EngineService {
         let namespace = &task_id.split(":").collect::<Vec<&str>>()[0];
         let task_name = &task_id.split(":").collect::<Vec<&str>>()[1];
 
-        if !Events::CheckAuth(&mut api, uid.clone(), challenge, db) {
+        if !self.is_user(&uid, challenge.clone()).await {
             info!("Aquire Task denied due to Invalid Auth");
-            return Err(Status::permission_denied("Invalid authentication"));
+            let denied = Status::permission_denied("Invalid authentication");
+            return Err(self.deny(&peer, "PublishTask", denied).await);
         };
-        if !api
-            .task_registry
-            .tasks
-            .contains_key(&ID(namespace, task_name))
+        self.check_rate_limit(&uid, "PublishTask").await?;
+        let key = ID(namespace, task_name);
+        // Mods only see the publish once the caller is known to hold the lease.
         {
-            warn!(
-                "Task acquisition failed - task does not exist: {}:{}",
-                namespace, task_name
-            );
-            return Err(Status::invalid_argument("Task Does not Exist"));
+            let api = self.EngineAPI.read().await;
+            leased_task(&api, &uid, &challenge, &key, &task_id)?;
         }
-        let key = ID(namespace, task_name);
+        let mut event = TaskPublishEvent::new(
+            key.clone(),
+            request.get_ref().id.clone(),
+            uid.clone(),
+            request.get_ref().task_payload.clone(),
+        );
+        if Events::dispatch(&self.EngineAPI, &mut event).await {
+            info!("Publish Task for {} cancelled by a mod", task_id);
+            return Err(Status::aborted("Task publishing cancelled"));
+        }
+        // The lease may have expired while the handlers ran.
+        let mut api = self.EngineAPI.write().await;
+        let tsk = leased_task(&api, &uid, &challenge, &key, &task_id)?;
         let mem_tsk = api
             .executing_tasks
             .tasks
             .get(&key)
             .cloned()
             .unwrap_or_default();
-        let tsk_opt = mem_tsk
-            .iter()
-            .find(|f| f.id == task_id.clone() && f.user_id == uid.clone());
-        if let Some(tsk) = tsk_opt {
-            let reg_tsk = match api.task_registry.get(&key) {
-                Some(r) => r.clone(),
-                None => {
-                    warn!("Task registry missing for {}:{}", namespace, task_name);
-                    return Err(Status::invalid_argument("Task Does not Exist"));
-                }
-            };
-            if !reg_tsk.verify(request.get_ref().task_payload.clone()) {
-                info!("Failed to parse task");
-                return Err(Status::invalid_argument("Failed to parse given task bytes"));
+        let reg_tsk = match api.task_registry.get(&key) {
+            Some(r) => r.clone(),
+            None => {
+                warn!("Task registry missing for {}:{}", namespace, task_name);
+                return Err(Status::invalid_argument("Task Does not Exist"));
             }
-            // Exec Tasks -> DB
-            let mut nmem_tsk = mem_tsk.clone();
-            nmem_tsk.retain(|f| f.id != task_id.clone() && f.user_id != uid.clone());
-            api.executing_tasks
-                .tasks
-                .insert(key.clone(), nmem_tsk.clone());
-            let t_mem_execs = api.executing_tasks.clone();
-            match postcard::to_allocvec(&t_mem_execs) {
-                Ok(store) => {
-                    if let Err(e) = api.db.insert("executing_tasks", store) {
-                        return Err(Status::internal(format!("DB insert error: {}", e)));
-                    }
+        };
+        if !reg_tsk.verify(event.payload.clone()) {
+            info!("Failed to parse task");
+            return Err(Status::invalid_argument("Failed to parse given task bytes"));
+        }
+        // Exec Tasks -> DB
+        let mut nmem_tsk = mem_tsk.clone();
+        nmem_tsk.retain(|f| f.id != task_id.clone() && f.user_id != uid.clone());
+        api.executing_tasks
+            .tasks
+            .insert(key.clone(), nmem_tsk.clone());
+        let t_mem_execs = api.executing_tasks.clone();
+        match postcard::to_allocvec(&t_mem_execs) {
+            Ok(store) => {
+                if let Err(e) = api.db.insert("executing_tasks", store) {
+                    return Err(Status::internal(format!("DB insert error: {}", e)));
                 }
-                Err(e) => return Err(Status::internal(format!("Serialization error: {}", e))),
             }
-            // tsk-> solved Tsks
-            let mut mem_solv = api
-                .solved_tasks
-                .tasks
-                .get(&key)
-                .cloned()
-                .unwrap_or_default();
-            mem_solv.push(enginelib::task::StoredTask {
-                bytes: tsk.bytes.clone(),
-                id: tsk.id.clone(),
-            });
-            api.solved_tasks.tasks.insert(key.clone(), mem_solv);
-            // Solved tsks -> DB
-            match postcard::to_allocvec(&api.solved_tasks.tasks) {
-                Ok(e_solv) => {
-                    if let Err(e) = api.db.insert("solved_tasks", e_solv) {
-                        return Err(Status::internal(format!("DB insert error: {}", e)));
-                    }
+            Err(e) => return Err(Status::internal(format!("Serialization error: {}", e))),
+        }
+        // tsk-> solved Tsks
+        let mut mem_solv = api
+            .solved_tasks
+            .tasks
+            .get(&key)
+            .cloned()
+            .unwrap_or_default();
+        mem_solv.push(enginelib::task::StoredTask {
+            bytes: event.payload.clone(),
+            id: tsk.id.clone(),
+            created_by: tsk.created_by.clone(),
+        });
+        api.solved_tasks.tasks.insert(key.clone(), mem_solv);
+        // Solved tsks -> DB
+        match postcard::to_allocvec(&api.solved_tasks.tasks) {
+            Ok(e_solv) => {
+                if let Err(e) = api.db.insert("solved_tasks", e_solv) {
+                    return Err(Status::internal(format!("DB insert error: {}", e)));
                 }
-                Err(e) => return Err(Status::internal(format!("Serialization error: {}", e))),
             }
-            info!("Task published successfully: {} by user: {}", task_id, uid);
-            return Ok(tonic::Response::new(proto::Empty {}));
-        } else {
-            return Err(tonic::Status::not_found("Invalid taskid or userid"));
+            Err(e) => return Err(Status::internal(format!("Serialization error: {}", e))),
         }
+        info!("Task published successfully: {} by user: {}", task_id, uid);
+        drop(api);
+        Events::dispatch(
+            &self.EngineAPI,
+            &mut TaskPublishedEvent::new(event.task, event.task_id, uid.clone(), event.payload),
+        )
+        .await;
+        Ok(tonic::Response::new(proto::Empty {}))
     }
-    async fn create_task(
+    async fn handle_create_task(
         &self,
         request: tonic::Request<proto::Task>,
     ) -> Result<tonic::Response<proto::Task>, tonic::Status> {
-        let mut api = self.EngineAPI.write().await;
-        let challenge = get_auth(&request);
         let uid = get_uid(&request);
-        let db = api.db.clone();
-        if !Events::CheckAuth(&mut api, uid, challenge, db) {
+        let peer = get_peer(&request);
+        let target = get_target(&request.get_ref().task_id);
+        let challenge = get_auth(&request);
+        if !self.is_user(&uid, challenge.clone()).await {
             //TODO: change to AdminSpecific Auth
             info!("Create Task denied due to Invalid Auth");
-            return Err(Status::permission_denied("Invalid authentication"));
+            let denied = Status::permission_denied("Invalid authentication");
+            return Err(self.deny(&peer, "CreateTask", denied).await);
         };
+        self.check_rate_limit(&uid, "CreateTask").await?;
         let task = request.get_ref();
         let task_id = task.task_id.clone();
         let parts: Vec<&str> = task_id.splitn(2, ':').collect();
@@ -600,15 +1464,38 @@ impl Engine for EngineService {
             ));
         }
         let id: Identifier = (parts[0].to_string(), parts[1].to_string());
+        {
+            let api = self.EngineAPI.read().await;
+            if !api.task_registry.tasks.contains_key(&id) {
+                return Err(tonic::Status::aborted("Error"));
+            }
+            check_tenant(&api, &uid, &challenge, &target.0)?;
+            check_queue_quota(&api, &uid)?;
+        }
+        let mut event = TaskCreateEvent::new(
+            id.clone(),
+            druid::Druid::default().to_hex(),
+            uid.clone(),
+            task.task_payload.clone(),
+        );
+        if Events::dispatch(&self.EngineAPI, &mut event).await {
+            info!("Create Task for {}:{} cancelled by a mod", id.0, id.1);
+            return Err(Status::aborted("Task creation cancelled"));
+        }
+        // Checked again, tasks may have been queued while the handlers ran.
+        let mut api = self.EngineAPI.write().await;
+        check_tenant(&api, &uid, &challenge, &target.0)?;
+        check_queue_quota(&api, &uid)?;
         let tsk_reg = api.task_registry.get(&id);
         if let Some(tsk_reg) = tsk_reg {
-            if !tsk_reg.clone().verify(task.task_payload.clone()) {
+            if !tsk_reg.clone().verify(event.payload.clone()) {
                 warn!("Failed to parse given task bytes");
                 return Err(Status::invalid_argument("Failed to parse given task bytes"));
             }
             let tbp_tsk = StoredTask {
-                bytes: task.task_payload.clone(),
-                id: druid::Druid::default().to_hex(),
+                bytes: event.payload,
+                id: event.task_id,
+                created_by: uid.clone(),
             };
             let mut mem_tsks = api.task_queue.clone();
             let mut mem_tsk = mem_tsks.tasks.get(&id).cloned().unwrap_or_default();
@@ -623,23 +1510,330 @@ impl Engine for EngineService {
                 }
                 Err(e) => return Err(Status::internal(format!("Serialization error: {}", e))),
             }
-            return Ok(tonic::Response::new(proto::Task {
+            drop(api);
+            let response = proto::Task {
                 id: tbp_tsk.id.clone(),
                 task_id: task_id.clone(),
                 payload: Vec::new(),
                 task_payload: tbp_tsk.bytes.clone(),
-            }));
+            };
+            Events::dispatch(
+                &self.EngineAPI,
+                &mut TaskCreatedEvent::new(id, tbp_tsk.id, uid.clone(), tbp_tsk.bytes),
+            )
+            .await;
+            return Ok(tonic::Response::new(response));
         }
         Err(tonic::Status::aborted("Error"))
     }
+    async fn handle_query_audit_log(
+        &self,
+        request: tonic::Request<proto::AuditQuery>,
+    ) -> Result<tonic::Response<proto::AuditLog>, tonic::Status> {
+        let uid = get_uid(&request);
+        let peer = get_peer(&request);
+        let challenge = get_auth(&request);
+        if !self.is_admin(challenge).await {
+            info!("QueryAuditLog denied due to Invalid Auth");
+            let denied = Status::permission_denied("Invalid authentication");
+            return Err(self.deny(&peer, "QueryAuditLog", denied).await);
+        };
+        self.check_rate_limit(&uid, "QueryAuditLog").await?;
+        let api = self.EngineAPI.read().await;
+        let data = request.get_ref();
+        let filter = AuditFilter {
+            since: (data.since != 0)
+                .then(|| DateTime::from_timestamp(data.since, 0))
+                .flatten(),
+            until: (data.until != 0)
+                .then(|| DateTime::from_timestamp(data.until, 0))
+                .flatten(),
+            uid: (!data.uid.is_empty()).then(|| data.uid.clone()),
+            target: (!data.namespace.is_empty() || !data.task.is_empty())
+                .then(|| ID(&data.namespace, &data.task)),
+            task_id: (!data.task_id.is_empty()).then(|| data.task_id.clone()),
+            subject: (!data.subject.is_empty()).then(|| data.subject.clone()),
+            limit: Some(
+                api.cfg
+                    .config_toml
+                    .pagination_limit
+                    .min(if data.limit == 0 {
+                        u32::MAX
+                    } else {
+                        data.limit
+                    }) as usize,
+            ),
+        };
+        let entries = api
+            .audit_log
+            .query(&filter)
+            .into_iter()
+            .map(|e| {
+                let (success, outcome) = match e.outcome {
+                    AuditOutcome::Success => (true, String::new()),
+                    AuditOutcome::Failure(reason) => (false, reason),
+                };
+                proto::AuditEntry {
+                    timestamp: e.timestamp.timestamp(),
+                    uid: e.uid,
+                    rpc: e.rpc,
+                    namespace: e.target.0,
+                    task: e.target.1,
+                    task_id: e.task_id,
+                    subject: e.subject,
+                    success,
+                    outcome,
+                }
+            })
+            .collect();
+        Ok(tonic::Response::new(proto::AuditLog { entries }))
+    }
+    async fn handle_put_tenant(
+        &self,
+        request: tonic::Request<proto::Tenant>,
+    ) -> Result<tonic::Response<proto::Empty>, tonic::Status> {
+        let uid = get_uid(&request);
+        let peer = get_peer(&request);
+        let challenge = get_auth(&request);
+        if !self.is_admin(challenge).await {
+            info!("PutTenant denied due to Invalid Auth");
+            let denied = Status::permission_denied("Invalid authentication");
+            return Err(self.deny(&peer, "PutTenant", denied).await);
+        };
+        self.check_rate_limit(&uid, "PutTenant").await?;
+        let mut api = self.EngineAPI.write().await;
+        let tenant = tenant_from_proto(request.get_ref().clone());
+        api.tenants.put(tenant).map_err(|e| {
+            info!("PutTenant rejected: {}", e);
+            Status::failed_precondition(e)
+        })?;
+        Ok(tonic::Response::new(proto::Empty {}))
+    }
+    async fn handle_delete_tenant(
+        &self,
+        request: tonic::Request<proto::TenantSelector>,
+    ) -> Result<tonic::Response<proto::Empty>, tonic::Status> {
+        let uid = get_uid(&request);
+        let peer = get_peer(&request);
+        let challenge = get_auth(&request);
+        if !self.is_admin(challenge).await {
+            info!("DeleteTenant denied due to Invalid Auth");
+            let denied = Status::permission_denied("Invalid authentication");
+            return Err(self.deny(&peer, "DeleteTenant", denied).await);
+        };
+        self.check_rate_limit(&uid, "DeleteTenant").await?;
+        let mut api = self.EngineAPI.write().await;
+        match api.tenants.remove(&request.get_ref().id) {
+            Some(_) => Ok(tonic::Response::new(proto::Empty {})),
+            None => Err(Status::not_found("Tenant does not exist")),
+        }
+    }
+    async fn handle_list_tenants(
+        &self,
+        request: tonic::Request<proto::Empty>,
+    ) -> Result<tonic::Response<proto::TenantList>, tonic::Status> {
+        let uid = get_uid(&request);
+        let peer = get_peer(&request);
+        let challenge = get_auth(&request);
+        if !self.is_admin(challenge).await {
+            info!("ListTenants denied due to Invalid Auth");
+            let denied = Status::permission_denied("Invalid authentication");
+            return Err(self.deny(&peer, "ListTenants", denied).await);
+        };
+        self.check_rate_limit(&uid, "ListTenants").await?;
+        let api = self.EngineAPI.read().await;
+        let mut tenants: Vec<proto::Tenant> =
+            api.tenants.tenants.values().map(tenant_to_proto).collect();
+        tenants.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(tonic::Response::new(proto::TenantList { tenants }))
+    }
+    async fn handle_load_mod(
+        &self,
+        request: tonic::Request<proto::ModSource>,
+    ) -> Result<tonic::Response<proto::Empty>, tonic::Status> {
+        let uid = get_uid(&request);
+        let peer = get_peer(&request);
+        let path = request.get_ref().path.clone();
+        let challenge = get_auth(&request);
+        if !self.is_admin(challenge).await {
+            info!("LoadMod denied due to Invalid Auth");
+            let denied = Status::permission_denied("Invalid authentication");
+            return Err(self.deny(&peer, "LoadMod", denied).await);
+        };
+        self.check_rate_limit(&uid, "LoadMod").await?;
+        let mut api = self.EngineAPI.write().await;
+        api.with_lib_manager(|lib_manager, api| lib_manager.load_path(Path::new(&path), api))
+            .map_err(|e| {
+                info!("LoadMod failed: {}", e);
+                Status::failed_precondition(e)
+            })?;
+        Ok(tonic::Response::new(proto::Empty {}))
+    }
+    async fn handle_unload_mod(
+        &self,
+        request: tonic::Request<proto::ModSelector>,
+    ) -> Result<tonic::Response<proto::Empty>, tonic::Status> {
+        let uid = get_uid(&request);
+        let peer = get_peer(&request);
+        if !self.is_admin(get_auth(&request)).await {
+            let denied = Status::permission_denied("Invalid authentication");
+            return Err(self.deny(&peer, "UnloadMod", denied).await);
+        };
+        self.check_rate_limit(&uid, "UnloadMod").await?;
+        let mod_id = request.get_ref().mod_id.clone();
+        let task_types = self.prepare_unload(&mod_id, false).await?;
+        self.drain(&task_types, request.get_ref().drain_seconds)
+            .await;
+        let mut api = self.EngineAPI.write().await;
+        let unloaded = api.with_lib_manager(|lib_manager, api| lib_manager.unload(&mod_id, api));
+        api.lib_manager
+            .draining
+            .retain(|key| !task_types.contains(key));
+        unloaded.map_err(|e| {
+            info!("UnloadMod failed: {}", e);
+            Status::failed_precondition(e)
+        })?;
+        Ok(tonic::Response::new(proto::Empty {}))
+    }
+    async fn handle_list_mods(
+        &self,
+        request: tonic::Request<proto::Empty>,
+    ) -> Result<tonic::Response<proto::ModList>, tonic::Status> {
+        let uid = get_uid(&request);
+        let peer = get_peer(&request);
+        let challenge = get_auth(&request);
+        if !self.is_admin(challenge).await {
+            info!("ListMods denied due to Invalid Auth");
+            let denied = Status::permission_denied("Invalid authentication");
+            return Err(self.deny(&peer, "ListMods", denied).await);
+        };
+        self.check_rate_limit(&uid, "ListMods").await?;
+        let api = self.EngineAPI.read().await;
+        let join = |ids: Vec<Identifier>| -> Vec<String> {
+            ids.into_iter()
+                .map(|(namespace, name)| format!("{}:{}", namespace, name))
+                .collect()
+        };
+        let mut mods: Vec<proto::ModInfo> = api
+            .lib_manager
+            .loaded_mods()
+            .map(|metadata| {
+                let contributions = ModContributions::of(&api, &metadata.mod_id);
+                proto::ModInfo {
+                    mod_id: metadata.mod_id.clone(),
+                    name: metadata.mod_name.clone(),
+                    version: metadata.mod_version.clone(),
+                    author: metadata.mod_author.clone(),
+                    source: api
+                        .lib_manager
+                        .sources
+                        .get(&metadata.mod_id)
+                        .map(|path| path.display().to_string())
+                        .unwrap_or_default(),
+                    tasks: join(contributions.tasks),
+                    events: join(contributions.events),
+                    handlers: join(contributions.handlers),
+                    cgrpc_handlers: join(contributions.cgrpc_handlers),
+                    services: join(contributions.services),
+                }
+            })
+            .collect();
+        mods.sort_by(|a, b| a.mod_id.cmp(&b.mod_id));
+        Ok(tonic::Response::new(proto::ModList { mods }))
+    }
+    async fn handle_list_cgrpc_handlers(
+        &self,
+        request: tonic::Request<proto::Empty>,
+    ) -> Result<tonic::Response<proto::CgrpcHandlerList>, tonic::Status> {
+        let uid = get_uid(&request);
+        let peer = get_peer(&request);
+        let challenge = get_auth(&request);
+        if !self.is_admin(challenge).await {
+            info!("ListCgrpcHandlers denied due to Invalid Auth");
+            let denied = Status::permission_denied("Invalid authentication");
+            return Err(self.deny(&peer, "ListCgrpcHandlers", denied).await);
+        };
+        self.check_rate_limit(&uid, "ListCgrpcHandlers").await?;
+        let api = self.EngineAPI.read().await;
+        let schema = |schema: CgrpcSchema| proto::CgrpcSchema {
+            type_name: schema.type_name,
+            example: schema.example,
+        };
+        let mut handlers: Vec<proto::CgrpcHandlerInfo> = api
+            .cgrpc_registry
+            .handlers
+            .iter()
+            .map(|(id, handler)| {
+                let schemas = handler.schemas();
+                proto::CgrpcHandlerInfo {
+                    handler_mod_id: id.0.clone(),
+                    handler_id: id.1.clone(),
+                    owner: api
+                        .cgrpc_registry
+                        .owners
+                        .get(id)
+                        .cloned()
+                        .unwrap_or_default(),
+                    typed: schemas.is_some(),
+                    streaming: handler.streaming(),
+                    request: schemas.clone().map(|s| schema(s.request)),
+                    response: schemas.map(|s| schema(s.response)),
+                }
+            })
+            .collect();
+        handlers.sort_by(|a, b| {
+            (&a.handler_mod_id, &a.handler_id).cmp(&(&b.handler_mod_id, &b.handler_id))
+        });
+        Ok(tonic::Response::new(proto::CgrpcHandlerList { handlers }))
+    }
+    async fn handle_reload_mod(
+        &self,
+        request: tonic::Request<proto::ModSelector>,
+    ) -> Result<tonic::Response<proto::Empty>, tonic::Status> {
+        let uid = get_uid(&request);
+        let peer = get_peer(&request);
+        if !self.is_admin(get_auth(&request)).await {
+            let denied = Status::permission_denied("Invalid authentication");
+            return Err(self.deny(&peer, "ReloadMod", denied).await);
+        };
+        self.check_rate_limit(&uid, "ReloadMod").await?;
+        let mod_id = request.get_ref().mod_id.clone();
+        let task_types = self.prepare_unload(&mod_id, true).await?;
+        self.drain(&task_types, request.get_ref().drain_seconds)
+            .await;
+        let mut api = self.EngineAPI.write().await;
+        let reloaded = api.with_lib_manager(|lib_manager, api| lib_manager.reload(&mod_id, api));
+        api.lib_manager
+            .draining
+            .retain(|key| !task_types.contains(key));
+        reloaded.map_err(|e| {
+            info!("ReloadMod failed: {}", e);
+            Status::failed_precondition(e)
+        })?;
+        Ok(tonic::Response::new(proto::Empty {}))
+    }
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "server")]
+#[command(about = "Serves tasks to workers over gRPC")]
+struct Cli {
+    #[command(flatten)]
+    mods: ModArgs,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
     let mut api = EngineAPI::default();
-    EngineAPI::init(&mut api);
+    let mut cfg = Config::new();
+    cli.mods.apply(&mut cfg.config_toml);
+    EngineAPI::init_with_config(&mut api, cfg);
     Events::init_auth(&mut api);
-    Events::StartEvent(&mut api);
+    if Events::StartEvent(&mut api) {
+        info!("Start cancelled by a handler, shutting down");
+        return Ok(());
+    }
     let addr = api
         .cfg
         .config_toml
@@ -651,7 +1845,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         )));
     let apii = Arc::new(RwLock::new(api));
     EngineAPI::init_chron(apii.clone());
-    let engine = EngineService { EngineAPI: apii };
+    EngineAPI::init_mod_watcher(apii.clone());
+    let engine = EngineService {
+        EngineAPI: apii.clone(),
+    };
 
     // Build reflection service, mapping its concrete error into Box<dyn Error>
     let reflection_service = tonic_reflection::server::Builder::configure()
@@ -663,9 +1860,41 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Server::builder()
         .add_service(reflection_service)
         .add_service(EngineServer::new(engine))
-        .serve(addr)
+        .serve_with_shutdown(addr, shutdown_signal(apii.clone()))
         .await
         .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
+    EngineAPI::shutdown(&apii).await;
 
     Ok(())
 }
+
+/// Resolves on SIGINT or SIGTERM, marking the engine as shutting down so no new leases are
+/// handed out while in-flight requests finish.
+async fn shutdown_signal(api: Arc<RwLock<EngineAPI>>) {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            warn!("Failed to listen for SIGINT: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                warn!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    info!("Shutdown signal received, no longer handing out leases");
+    api.write().await.shutting_down = true;
+}
@@ -1,4 +1,8 @@
-use tonic::Request;
+use std::{path::PathBuf, time::Duration};
+
+use clap::{Args, ValueHint};
+use enginelib::{Identifier, config::ConfigTomlServer};
+use tonic::{Request, Status, metadata::MetadataValue};
 
 pub fn get_uid<T>(req: &Request<T>) -> String {
     req.metadata()
@@ -8,6 +12,13 @@ pub fn get_uid<T>(req: &Request<T>) -> String {
         .unwrap_or_default()
 }
 
+/// The IP address the request came from, used to rate limit calls that fail authentication.
+pub fn get_peer<T>(req: &Request<T>) -> String {
+    req.remote_addr()
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_default()
+}
+
 pub fn get_auth<T>(req: &Request<T>) -> String {
     req.metadata()
         .get("authorization")
@@ -15,3 +26,48 @@ pub fn get_auth<T>(req: &Request<T>) -> String {
         .map(|s| s.to_string())
         .unwrap_or_default()
 }
+
+/// Splits a `namespace:task` id into an [`Identifier`], leaving the task empty if there is no colon.
+pub fn get_target(task_id: &str) -> Identifier {
+    let mut parts = task_id.splitn(2, ':');
+    (
+        parts.next().unwrap_or_default().to_string(),
+        parts.next().unwrap_or_default().to_string(),
+    )
+}
+
+/// Builds a `RESOURCE_EXHAUSTED` status, adding `retry-after` metadata (in whole seconds) when known.
+pub fn resource_exhausted(message: &str, retry_after: Option<Duration>) -> Status {
+    let mut status = Status::resource_exhausted(message);
+    if let Some(retry_after) = retry_after {
+        let secs = retry_after
+            .as_secs()
+            .saturating_add(u64::from(retry_after.subsec_nanos() > 0));
+        status
+            .metadata_mut()
+            .insert("retry-after", MetadataValue::from(secs));
+    }
+    status
+}
+
+/// Module search path overrides shared by every binary, taking precedence over config and env.
+#[derive(Args, Debug, Default, Clone, PartialEq)]
+pub struct ModArgs {
+    /// Directory to scan for *.rustforge.tar modules, replaces `mods_dirs` (repeatable)
+    #[arg(long = "mods-dir", global = true, value_hint = ValueHint::DirPath)]
+    pub mods_dirs: Vec<PathBuf>,
+    /// Module archive or library to load, replaces `mod_paths` (repeatable)
+    #[arg(long = "mod", global = true, value_hint = ValueHint::FilePath)]
+    pub mod_paths: Vec<PathBuf>,
+}
+
+impl ModArgs {
+    pub fn apply(&self, cfg: &mut ConfigTomlServer) {
+        if !self.mods_dirs.is_empty() {
+            cfg.mods_dirs = self.mods_dirs.clone();
+        }
+        if !self.mod_paths.is_empty() {
+            cfg.mod_paths = self.mod_paths.clone();
+        }
+    }
+}
@@ -1,10 +1,13 @@
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{DeriveInput, parse_macro_input};
+use syn::{DeriveInput, ItemFn, Path, parse_macro_input};
 #[proc_macro_attribute]
 pub fn metadata(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let item = proc_macro2::TokenStream::from(item);
     quote! {
+        #[unsafe(no_mangle)]
+        pub static ENGINE_MOD_HANDSHAKE: enginelib::abi::ModHandshake =
+            enginelib::abi::ModHandshake::current();
         #[unsafe(export_name="metadata")]
         #item
     }
@@ -19,6 +22,46 @@ pub fn module(_attr: TokenStream, item: TokenStream) -> TokenStream {
     }
     .into()
 }
+/// Lets a `Task + Default` type be registered through the C ABI with `Host::register_task`.
+#[proc_macro_attribute]
+pub fn c_task(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    quote! {
+        #input
+        impl #impl_generics enginelib::cabi::CAbiTask for #name #ty_generics #where_clause {}
+    }
+    .into()
+}
+/// Exports `fn(&mut Host)` as the entry point of a C ABI mod.
+///
+/// Takes the path of the mod's `fn() -> LibraryMetadata`, `metadata` if omitted.
+#[proc_macro_attribute]
+pub fn c_module(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let metadata: Path = if attr.is_empty() {
+        syn::parse_quote!(metadata)
+    } else {
+        parse_macro_input!(attr as Path)
+    };
+    let run = parse_macro_input!(item as ItemFn);
+    let run_name = &run.sig.ident;
+    quote! {
+        #run
+        #[unsafe(no_mangle)]
+        pub static ENGINE_CABI_PLUGIN: enginelib::cabi::CPluginDescriptor =
+            enginelib::cabi::CPluginDescriptor::new(__engine_cabi_metadata, __engine_cabi_init);
+        unsafe extern "C" fn __engine_cabi_metadata() -> enginelib::cabi::FfiBuf {
+            enginelib::cabi::encode_metadata(#metadata())
+        }
+        unsafe extern "C" fn __engine_cabi_init(
+            host: *const enginelib::cabi::HostVTable,
+        ) -> bool {
+            unsafe { enginelib::cabi::init_plugin(host, #run_name) }
+        }
+    }
+    .into()
+}
 #[proc_macro_derive(Verifiable)]
 pub fn derive_verifiable(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
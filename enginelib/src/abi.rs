@@ -0,0 +1,111 @@
+use std::fmt::Display;
+
+/// Version of the interface between the engine and mods, bumped independently of the crate version.
+///
+/// Bump the minor version for additive changes and the major version for anything that breaks
+/// already built mods. Patch releases never require mods to be rebuilt.
+pub const ABI_VERSION: AbiVersion = AbiVersion {
+    major: 1,
+    minor: 0,
+    patch: 0,
+};
+
+/// Symbol every mod exports its [`ModHandshake`] under.
+pub const HANDSHAKE_SYMBOL: &[u8] = b"ENGINE_MOD_HANDSHAKE";
+pub const HANDSHAKE_MAGIC: [u8; 8] = *b"GEMODABI";
+const RUSTC_VERSION_LEN: usize = 64;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AbiVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl AbiVersion {
+    /// Whether a mod built against `self` can be loaded by an engine implementing `engine`.
+    ///
+    /// Follows semver: the major versions must match (and the minor ones while the major is 0),
+    /// and the mod must not rely on a newer minor version than the engine provides.
+    pub fn is_compatible_with(&self, engine: &AbiVersion) -> bool {
+        if self.major != engine.major {
+            return false;
+        }
+        if self.major == 0 {
+            return self.minor == engine.minor;
+        }
+        self.minor <= engine.minor
+    }
+}
+
+impl Display for AbiVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Layout-stable description of what a mod was built against.
+///
+/// Read by the loader before any Rust ABI symbol of the mod is touched.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ModHandshake {
+    pub magic: [u8; 8],
+    pub abi_version: AbiVersion,
+    pub rustc_version: [u8; RUSTC_VERSION_LEN], // NUL padded.
+}
+
+impl ModHandshake {
+    /// Handshake of the enginelib version being compiled against.
+    pub const fn current() -> Self {
+        Self {
+            magic: HANDSHAKE_MAGIC,
+            abi_version: ABI_VERSION,
+            rustc_version: pad(crate::RUSTC_VERSION),
+        }
+    }
+
+    pub fn rustc_version(&self) -> &str {
+        let len = self
+            .rustc_version
+            .iter()
+            .position(|b| *b == 0)
+            .unwrap_or(RUSTC_VERSION_LEN);
+        std::str::from_utf8(&self.rustc_version[..len]).unwrap_or("<invalid>")
+    }
+
+    /// Checks a mod's handshake against this engine.
+    ///
+    /// The rustc version still has to match exactly, as `run` and `metadata` use the Rust ABI.
+    pub fn check(&self) -> Result<(), String> {
+        if self.magic != HANDSHAKE_MAGIC {
+            return Err("Invalid mod handshake".into());
+        }
+        if !self.abi_version.is_compatible_with(&ABI_VERSION) {
+            return Err(format!(
+                "ABI version mismatch - Module ABI: {}, Engine ABI: {}",
+                self.abi_version, ABI_VERSION
+            ));
+        }
+        if self.rustc_version() != crate::RUSTC_VERSION {
+            return Err(format!(
+                "Rustc version mismatch - Module Rustc: {}, Engine Rustc: {}",
+                self.rustc_version(),
+                crate::RUSTC_VERSION
+            ));
+        }
+        Ok(())
+    }
+}
+
+const fn pad(version: &str) -> [u8; RUSTC_VERSION_LEN] {
+    let bytes = version.as_bytes();
+    let mut padded = [0; RUSTC_VERSION_LEN];
+    let mut i = 0;
+    while i < bytes.len() && i < RUSTC_VERSION_LEN {
+        padded[i] = bytes[i];
+        i += 1;
+    }
+    padded
+}
@@ -9,75 +9,165 @@ use tracing::{Level, debug, error, info, instrument};
 
 use crate::{
     Identifier, Registry,
-    config::Config,
-    event::{EngineEventHandlerRegistry, EngineEventRegistry, EventBus},
-    events::Events,
-    plugin::LibraryManager,
-    task::{ExecutingTaskQueue, SolvedTasks, StoredTask, Task, TaskQueue},
+    audit::{AuditEntry, AuditLog, AuditOutcome},
+    cgrpc::CgrpcRegistry,
+    config::{Config, ModConfig},
+    event::{EngineEventHandlerRegistry, EngineEventRegistry, EventBus, RegisteredHandler},
+    events::{Events, task_event::TaskLeaseExpiredEvent},
+    limits::RateLimiter,
+    plugin::{LibraryManager, ModWatcher},
+    replay::EventRecorder,
+    scheduler::{Scheduler, share_of},
+    service::ServiceRegistry,
+    task::{
+        ExecutingTaskQueue, STORE_VERSION, STORE_VERSION_KEY, SolvedTasks, StoredTask, Task,
+        TaskQueue,
+    },
+    tenant::Tenants,
 };
 pub use postcard;
 pub use postcard::from_bytes;
 pub use postcard::to_allocvec;
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{collections::HashMap, path::PathBuf, sync::Arc, time::Duration};
+
+/// Opens the audit log, disabling auditing with an error when its tree can't be opened.
+fn open_audit_log(db: &Db) -> AuditLog {
+    AuditLog::new(db).unwrap_or_else(|e| {
+        error!("{}, audit entries are dropped", e);
+        AuditLog::disabled(db)
+    })
+}
+
+/// Reads the queue stored under `key`, `None` if nothing is stored yet.
+fn load_stored<T: Default>(
+    db: &Db,
+    key: &str,
+    version: u32,
+    decode: fn(&[u8], u32) -> postcard::Result<T>,
+) -> Option<T> {
+    let store = db.get(key).ok().flatten()?;
+    match decode(&store, version) {
+        Ok(tasks) => Some(tasks),
+        Err(e) => {
+            let aside = format!("{}_unreadable", key);
+            error!(
+                "Failed to decode stored {} (version {}): {}, keeping it as {}",
+                key, version, e, aside
+            );
+            if let Err(e) = db.insert(aside, store) {
+                error!("Failed to keep unreadable {}: {:?}", key, e);
+            }
+            Some(T::default())
+        }
+    }
+}
+
+/// Library `init_dev` loads when no `mod_paths` are configured.
+pub const DEV_LIBRARY: &str = "./target/release/libengine_core.so";
 pub struct EngineAPI {
     pub cfg: Config,
     pub task_queue: TaskQueue,
     pub executing_tasks: ExecutingTaskQueue,
     pub solved_tasks: SolvedTasks,
     pub task_registry: EngineTaskRegistry,
+    pub cgrpc_registry: CgrpcRegistry,
+    pub service_registry: ServiceRegistry,
     pub event_bus: EventBus,
     pub db: sled::Db,
+    pub audit_log: AuditLog,
+    pub rate_limiter: RateLimiter,
+    pub tenants: Tenants,
+    pub scheduler: Scheduler,
     pub lib_manager: LibraryManager,
+    /// Errors mods hit reading their config during `run`, failing their load.
+    pub mod_config_errors: HashMap<String, Vec<String>>,
+    /// Set once shutdown started, no new leases are handed out.
+    pub shutting_down: bool,
 }
 
 impl Default for EngineAPI {
     fn default() -> Self {
+        let db = sled::open("engine_db").unwrap();
         Self {
             cfg: Config::default(),
             task_queue: TaskQueue::default(),
-            db: sled::open("engine_db").unwrap(),
+            audit_log: open_audit_log(&db),
+            rate_limiter: RateLimiter::default(),
+            tenants: Tenants::new(&db),
+            scheduler: Scheduler::default(),
+            db,
             lib_manager: LibraryManager::default(),
             task_registry: EngineTaskRegistry::default(),
+            cgrpc_registry: CgrpcRegistry::default(),
+            service_registry: ServiceRegistry::default(),
             event_bus: EventBus {
                 event_registry: EngineEventRegistry {
                     events: HashMap::new(),
+                    owners: HashMap::new(),
                 },
                 event_handler_registry: EngineEventHandlerRegistry {
                     event_handlers: HashMap::new(),
                 },
+                recorder: None,
             },
             solved_tasks: SolvedTasks::default(),
             executing_tasks: ExecutingTaskQueue::default(),
+            mod_config_errors: HashMap::new(),
+            shutting_down: false,
         }
     }
 }
 impl EngineAPI {
     pub fn test_default() -> Self {
+        let db = sled::Config::new()
+            .temporary(true)
+            .flush_every_ms(None)
+            .open()
+            .unwrap();
         Self {
             cfg: Config::new(),
             task_queue: TaskQueue::default(),
-            db: sled::Config::new()
-                .temporary(true)
-                .flush_every_ms(None)
-                .open()
-                .unwrap(),
+            audit_log: open_audit_log(&db),
+            rate_limiter: RateLimiter::default(),
+            tenants: Tenants::new(&db),
+            scheduler: Scheduler::default(),
+            db,
             lib_manager: LibraryManager::default(),
             task_registry: EngineTaskRegistry::default(),
+            cgrpc_registry: CgrpcRegistry::default(),
+            service_registry: ServiceRegistry::default(),
             event_bus: EventBus {
                 event_registry: EngineEventRegistry {
                     events: HashMap::new(),
+                    owners: HashMap::new(),
                 },
                 event_handler_registry: EngineEventHandlerRegistry {
                     event_handlers: HashMap::new(),
                 },
+                recorder: None,
             },
             solved_tasks: SolvedTasks::default(),
             executing_tasks: ExecutingTaskQueue::default(),
+            mod_config_errors: HashMap::new(),
+            shutting_down: false,
         }
     }
     pub fn init(api: &mut Self) {
+        Self::init_with_config(api, Config::new());
+    }
+    /// Like [`EngineAPI::init`], with a config the caller already loaded and overrode.
+    pub fn init_with_config(api: &mut Self, cfg: Config) {
         Self::setup_logger();
-        api.cfg = Config::new();
+        api.cfg = cfg;
+        if let Some(path) = &api.cfg.config_toml.record_events {
+            match EventRecorder::create(path) {
+                Ok(recorder) => {
+                    info!("Recording events to {}", path.display());
+                    api.event_bus.recorder = Some(recorder);
+                }
+                Err(e) => error!("{}", e),
+            }
+        }
         Self::init_db(api);
         let mut new_lib_manager = LibraryManager::default();
         new_lib_manager.load_modules(api);
@@ -85,7 +175,11 @@ impl EngineAPI {
         Events::init(api);
     }
     pub fn init_packer(api: &mut Self) {
+        Self::init_packer_with_config(api, Config::new());
+    }
+    pub fn init_packer_with_config(api: &mut Self, cfg: Config) {
         Self::setup_logger();
+        api.cfg = cfg;
         let mut newLibManager = LibraryManager::default();
         newLibManager.load_modules(api);
     }
@@ -93,6 +187,61 @@ impl EngineAPI {
         let t = api.try_read().unwrap().cfg.config_toml.clean_tasks;
         spawn(clear_sled_periodically(api, t));
     }
+    /// Starts hot reloading mods if `mods_watch_seconds` is set.
+    pub fn init_mod_watcher(api: Arc<RwLock<Self>>) {
+        let t = api.try_read().unwrap().cfg.config_toml.mods_watch_seconds;
+        if t > 0 {
+            spawn(watch_mods_periodically(api, t));
+        }
+    }
+    /// Shuts the engine down after the server stopped: optionally requeues leased tasks, syncs
+    /// and flushes the database, fires `core:shutdown_event` and unloads every mod.
+    pub async fn shutdown(api: &RwLock<Self>) {
+        let requeued = {
+            let mut api = api.write().await;
+            api.shutting_down = true;
+            let requeued = if api.cfg.config_toml.requeue_on_shutdown {
+                let task_types: Vec<Identifier> =
+                    api.executing_tasks.tasks.keys().cloned().collect();
+                api.park_executing(&task_types, "Shutdown")
+            } else {
+                0
+            };
+            Self::sync_db(&mut api);
+            if let Err(e) = api.db.flush() {
+                error!("Failed to flush the database on shutdown: {:?}", e);
+            }
+            requeued
+        };
+        info!(
+            "Shutting down, moved {} leased task(s) back to the queue",
+            requeued
+        );
+        Events::ShutdownEvent(api, requeued).await;
+        api.write()
+            .await
+            .with_lib_manager(|lib_manager, api| lib_manager.unload_all(api));
+        info!("Shutdown complete");
+    }
+    /// Handlers of `id` like [`EventBus::handlers`], each holding the library of its mod so that
+    /// they can be awaited after the lock is released without the mod being closed under them.
+    pub fn event_handlers(&self, id: &Identifier) -> Vec<RegisteredHandler> {
+        let mut handlers = self.event_bus.handlers(id);
+        for registered in &mut handlers {
+            registered.library = self.lib_manager.library(&registered.owner);
+        }
+        handlers
+    }
+    /// Runs `f` with the library manager taken out of the API, so both can be borrowed mutably.
+    pub fn with_lib_manager<R>(
+        &mut self,
+        f: impl FnOnce(&mut LibraryManager, &mut EngineAPI) -> R,
+    ) -> R {
+        let mut lib_manager = std::mem::take(&mut self.lib_manager);
+        let result = f(&mut lib_manager, self);
+        self.lib_manager = lib_manager;
+        result
+    }
     pub fn sync_db(api: &mut EngineAPI) {
         // IF THIS FN CAUSES PANIC SOMETHING IS VERY BROKEN
 
@@ -108,44 +257,111 @@ impl EngineAPI {
         api.db.insert("solved_tasks", solved_tasks_db).unwrap();
         debug!("Synced In memory db to File db");
     }
-    fn init_db(api: &mut EngineAPI) {
-        let tasks = api.db.get("tasks");
-        let exec_tasks = api.db.get("executing_tasks");
-        let solved_tasks = api.db.get("solved_tasks");
-        if tasks.is_err() || tasks.unwrap().is_none() {
-            let store = postcard::to_allocvec(&api.task_queue.clone()).unwrap();
-            api.db.insert("tasks", store).unwrap();
-        } else {
-            let store = api.db.get("tasks").unwrap().unwrap();
-            let res: TaskQueue = postcard::from_bytes(&store).unwrap();
-            api.task_queue = res;
-        }
-        if exec_tasks.is_err() || exec_tasks.unwrap().is_none() {
-            let store = postcard::to_allocvec(&api.executing_tasks.clone()).unwrap();
-            api.db.insert("executing_tasks", store).unwrap();
-        } else {
-            let store = api.db.get("executing_tasks").unwrap().unwrap();
-            let res: ExecutingTaskQueue = postcard::from_bytes(&store).unwrap();
-            api.executing_tasks = res;
-        };
-        if solved_tasks.is_err() || solved_tasks.unwrap().is_none() {
-            let store = postcard::to_allocvec(&api.solved_tasks.clone()).unwrap();
-            api.db.insert("solved_tasks", store).unwrap();
-        } else {
-            let store = api.db.get("solved_tasks").unwrap().unwrap();
-            let res: SolvedTasks = postcard::from_bytes(&store).unwrap();
-            api.solved_tasks = res;
+    /// Loads the stored task queues, converting queues written in an older layout, see
+    /// [`STORE_VERSION`]. A queue that can't be decoded is kept under `<key>_unreadable` and
+    /// replaced by an empty one.
+    pub fn init_db(api: &mut EngineAPI) {
+        let version = match api.db.get(STORE_VERSION_KEY) {
+            Ok(Some(v)) => v
+                .as_ref()
+                .try_into()
+                .map(u32::from_le_bytes)
+                .unwrap_or(STORE_VERSION),
+            // Queues stored before the version was recorded
+            _ => 1,
         };
+        if let Some(tasks) = load_stored(&api.db, "tasks", version, TaskQueue::decode) {
+            api.task_queue = tasks;
+        }
+        if let Some(tasks) = load_stored(
+            &api.db,
+            "executing_tasks",
+            version,
+            ExecutingTaskQueue::decode,
+        ) {
+            api.executing_tasks = tasks;
+        }
+        if let Some(tasks) = load_stored(&api.db, "solved_tasks", version, SolvedTasks::decode) {
+            api.solved_tasks = tasks;
+        }
+        Self::sync_db(api);
+        api.db
+            .insert(STORE_VERSION_KEY, &STORE_VERSION.to_le_bytes())
+            .unwrap();
     }
     pub fn init_dev(api: &mut Self) {
         Self::setup_logger();
         Events::init(api);
+        let mods = if api.cfg.config_toml.mod_paths.is_empty() {
+            vec![PathBuf::from(DEV_LIBRARY)]
+        } else {
+            api.cfg.config_toml.mod_paths.clone()
+        };
         let mut newLibManager = LibraryManager::default();
-        newLibManager
-            .load_library("./target/release/libengine_core.so", api)
-            .unwrap();
+        for path in mods {
+            newLibManager.load_path(&path, api).unwrap();
+        }
         api.lib_manager = newLibManager;
     }
+    /// Reads the `[mods.<mod_id>]` section from a mod's `run`, see [`ModConfig`].
+    ///
+    /// An invalid section is logged and fails the mod's load once `run` returns, `T::default()`
+    /// is returned in the meantime.
+    pub fn mod_config<T: ModConfig>(&mut self, mod_id: &str) -> T {
+        self.cfg.config_toml.mod_config(mod_id).unwrap_or_else(|e| {
+            error!("{}", e);
+            self.mod_config_errors
+                .entry(mod_id.to_string())
+                .or_default()
+                .push(e);
+            T::default()
+        })
+    }
+    /// Lease duration granted to `uid` when it acquires a task, honoring its tenant's override.
+    pub fn lease_seconds_for(&self, uid: &str) -> u64 {
+        self.tenants
+            .of_uid(uid)
+            .and_then(|t| t.lease_seconds)
+            .unwrap_or(self.cfg.config_toml.lease_seconds)
+    }
+    /// Moves leased tasks of the given types back to the queue, returning how many were moved.
+    /// Each move is audited under `action`.
+    pub fn park_executing(&mut self, task_types: &[Identifier], action: &str) -> usize {
+        let mut parked = 0;
+        for key in task_types {
+            let Some(leased) = self.executing_tasks.tasks.remove(key) else {
+                continue;
+            };
+            parked += leased.len();
+            let queue = self.task_queue.tasks.entry(key.clone()).or_default();
+            for info in leased {
+                self.audit_log.record(AuditEntry::new(
+                    info.user_id.clone(),
+                    action,
+                    key.clone(),
+                    info.id.clone(),
+                    AuditOutcome::Success,
+                ));
+                queue.push(StoredTask {
+                    id: info.id,
+                    bytes: info.bytes,
+                    created_by: info.created_by,
+                });
+            }
+        }
+        parked
+    }
+    /// Removes the next task of the given type from the queue, as picked by the scheduling policy.
+    pub fn take_next_task(&mut self, key: &Identifier) -> Option<StoredTask> {
+        let queue = self.task_queue.tasks.get_mut(key)?;
+        let tenants = &self.tenants;
+        let index =
+            self.scheduler
+                .pick(self.cfg.config_toml.scheduling_policy, key, queue, |uid| {
+                    share_of(tenants, uid)
+                })?;
+        Some(queue.remove(index))
+    }
     pub fn setup_logger() {
         #[cfg(debug_assertions)]
         tracing_subscriber::FmtSubscriber::builder()
@@ -166,6 +382,7 @@ impl EngineAPI {
 #[derive(Default, Clone, Debug)]
 pub struct EngineTaskRegistry {
     pub tasks: HashMap<Identifier, Arc<dyn Task>>,
+    pub owners: HashMap<Identifier, String>, // mod_id that registered each task.
 }
 impl Registry<dyn Task> for EngineTaskRegistry {
     #[instrument]
@@ -190,29 +407,45 @@ pub async fn clear_sled_periodically(api: Arc<RwLock<EngineAPI>>, n_minutes: u64
     loop {
         interval.tick().await; // Wait for the interval
         info!("Purging Unsolved Tasks");
-        let now = Utc::now().timestamp(); // Current timestamp in seconds
+        let now = Utc::now();
         let mut moved_tasks: Vec<(String, String, StoredTask)> = Vec::new();
+        let mut expired_events: Vec<TaskLeaseExpiredEvent> = Vec::new();
         let mut rw_api = api.write().await;
         let db = rw_api.db.clone();
+        let audit_log = rw_api.audit_log.clone();
+        let default_lease = rw_api.cfg.config_toml.lease_seconds;
         // Load "executing_tasks"
         if let Ok(Some(tsks)) = db.get("executing_tasks") {
             if let Ok(mut s) = postcard::from_bytes::<ExecutingTaskQueue>(&tsks) {
                 for ((key1, key2), task_list) in s.tasks.iter_mut() {
                     task_list.retain(|info| {
-                        let age = now - info.given_at.timestamp();
-                        if age > 3600 {
-                            info!("Task {:?} is older than an hour! Moving...", info);
+                        if info.lease_expired(now, default_lease) {
+                            info!("Task {:?} lease has expired! Moving...", info);
+                            audit_log.record(AuditEntry::new(
+                                info.user_id.clone(),
+                                "LeaseExpired",
+                                (key1.clone(), key2.clone()),
+                                info.id.clone(),
+                                AuditOutcome::Success,
+                            ));
+                            expired_events.push(TaskLeaseExpiredEvent::new(
+                                (key1.clone(), key2.clone()),
+                                info.id.clone(),
+                                info.user_id.clone(),
+                                info.bytes.clone(),
+                            ));
                             moved_tasks.push((
                                 key1.clone(),
                                 key2.clone(),
                                 StoredTask {
                                     id: info.id.clone(),
                                     bytes: info.bytes.clone(),
+                                    created_by: info.created_by.clone(),
                                 },
                             ));
                             false // Remove old tasks
                         } else {
-                            true // Keep tasks that are less than an hour old
+                            true // Keep tasks whose lease is still valid
                         }
                     });
                 }
@@ -255,5 +488,33 @@ pub async fn clear_sled_periodically(api: Arc<RwLock<EngineAPI>>, n_minutes: u64
             }
         }
         EngineAPI::init_db(&mut rw_api);
+        audit_log.purge(rw_api.cfg.config_toml.audit_retention_days);
+        drop(rw_api);
+        for mut event in expired_events {
+            Events::dispatch(&api, &mut event).await;
+        }
+    }
+}
+
+pub async fn watch_mods_periodically(api: Arc<RwLock<EngineAPI>>, n_seconds: u64) {
+    info!("Mod Watcher Started");
+    let mut watcher = ModWatcher::new(&api.read().await.cfg.config_toml);
+    let mut interval = interval(Duration::from_secs(n_seconds));
+    loop {
+        interval.tick().await;
+        let cfg = api.read().await.cfg.config_toml.clone();
+        let changes = watcher.poll(&cfg);
+        if changes.is_empty() {
+            continue;
+        }
+        let mut rw_api = api.write().await;
+        for change in changes {
+            info!("Mod watcher: {:?}", change);
+            if let Err(e) =
+                rw_api.with_lib_manager(|lib_manager, api| lib_manager.apply_change(&change, api))
+            {
+                error!("Mod watcher: {}", e);
+            }
+        }
     }
 }
@@ -0,0 +1,165 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sled::{Db, Tree};
+use tracing::{debug, error};
+
+use crate::Identifier;
+
+/// Name of the sled tree the audit log is stored in.
+pub const AUDIT_TREE: &str = "audit_log";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AuditOutcome {
+    Success,
+    Failure(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: DateTime<Utc>,
+    pub uid: String,
+    pub rpc: String,
+    pub target: Identifier,
+    pub task_id: String,
+    pub subject: String, // Tenant id, mod id or mod path an admin action applies to.
+    pub outcome: AuditOutcome,
+}
+
+impl AuditEntry {
+    pub fn new(
+        uid: impl Into<String>,
+        rpc: impl Into<String>,
+        target: Identifier,
+        task_id: impl Into<String>,
+        outcome: AuditOutcome,
+    ) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            uid: uid.into(),
+            rpc: rpc.into(),
+            target,
+            task_id: task_id.into(),
+            subject: String::new(),
+            outcome,
+        }
+    }
+
+    pub fn with_subject(mut self, subject: impl Into<String>) -> Self {
+        self.subject = subject.into();
+        self
+    }
+}
+
+/// Filter used by [`AuditLog::query`]. Unset fields match every entry.
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub uid: Option<String>,
+    pub target: Option<Identifier>,
+    pub task_id: Option<String>,
+    pub subject: Option<String>,
+    pub limit: Option<usize>,
+}
+
+impl AuditFilter {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        self.uid.as_ref().is_none_or(|uid| &entry.uid == uid)
+            && self.target.as_ref().is_none_or(|t| &entry.target == t)
+            && self.task_id.as_ref().is_none_or(|id| &entry.task_id == id)
+            && self.subject.as_ref().is_none_or(|s| &entry.subject == s)
+    }
+}
+
+/// Append-only log of task lifecycle and administrative actions.
+///
+/// Entries are keyed by their timestamp (big endian microseconds) followed by a
+/// sled generated id, so iterating the tree yields them in chronological order
+/// and time range queries are plain range scans.
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    db: Db,
+    tree: Option<Tree>, // `None` when the tree could not be opened, entries are dropped.
+}
+
+impl AuditLog {
+    pub fn new(db: &Db) -> Result<Self, String> {
+        let tree = db
+            .open_tree(AUDIT_TREE)
+            .map_err(|e| format!("Failed to open the audit log: {}", e))?;
+        Ok(Self {
+            db: db.clone(),
+            tree: Some(tree),
+        })
+    }
+
+    /// An audit log that drops every entry, for when [`new`](Self::new) failed.
+    pub fn disabled(db: &Db) -> Self {
+        Self {
+            db: db.clone(),
+            tree: None,
+        }
+    }
+
+    fn time_key(time: DateTime<Utc>) -> [u8; 8] {
+        (time.timestamp_micros().max(0) as u64).to_be_bytes()
+    }
+
+    pub fn record(&self, entry: AuditEntry) {
+        let Some(tree) = &self.tree else {
+            return;
+        };
+        let id = match self.db.generate_id() {
+            Ok(id) => id,
+            Err(e) => {
+                error!("AuditLog: Failed to generate entry id: {}", e);
+                return;
+            }
+        };
+        let mut key = Self::time_key(entry.timestamp).to_vec();
+        key.extend_from_slice(&id.to_be_bytes());
+        match postcard::to_allocvec(&entry) {
+            Ok(bytes) => {
+                if let Err(e) = tree.insert(key, bytes) {
+                    error!("AuditLog: Failed to store entry: {}", e);
+                }
+            }
+            Err(e) => error!("AuditLog: Failed to serialize entry: {}", e),
+        }
+    }
+
+    pub fn query(&self, filter: &AuditFilter) -> Vec<AuditEntry> {
+        let Some(tree) = &self.tree else {
+            return Vec::new();
+        };
+        let start = filter.since.map(Self::time_key).unwrap_or([0; 8]);
+        let iter = match filter.until {
+            Some(until) => tree.range(start..Self::time_key(until + Duration::microseconds(1))),
+            None => tree.range(start..),
+        };
+        iter.filter_map(|res| res.ok())
+            .filter_map(|(_, v)| postcard::from_bytes::<AuditEntry>(&v).ok())
+            .filter(|entry| filter.matches(entry))
+            .take(filter.limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+
+    /// Removes every entry older than `retention_days`. A retention of zero keeps everything.
+    pub fn purge(&self, retention_days: u64) -> usize {
+        let Some(tree) = &self.tree else {
+            return 0;
+        };
+        if retention_days == 0 {
+            return 0;
+        }
+        let cutoff = Self::time_key(Utc::now() - Duration::days(retention_days as i64));
+        let mut removed = 0;
+        for (key, _) in tree.range(..cutoff).filter_map(|res| res.ok()) {
+            if tree.remove(key).is_ok() {
+                removed += 1;
+            }
+        }
+        debug!("AuditLog: Purged {} entries", removed);
+        removed
+    }
+}
@@ -0,0 +1,538 @@
+//! Compiler independent plugin interface.
+//!
+//! Mods built with a different rustc than the engine export a [`CPluginDescriptor`] under
+//! [`CABI_SYMBOL`] instead of the Rust ABI `run` and `metadata` symbols. Everything crossing
+//! the boundary is `#[repr(C)]`, tasks travel as their serialized bytes and metadata as postcard.
+//!
+//! Event payloads are Rust types and are not visible across this interface, C ABI handlers are
+//! told which event fired and may cancel it.
+use std::{
+    ffi::c_void,
+    fmt::Debug,
+    panic::{AssertUnwindSafe, catch_unwind},
+    ptr::null_mut,
+    sync::Arc,
+};
+
+use tracing::{debug, error};
+
+use crate::{
+    Identifier, Registry,
+    abi::{ABI_VERSION, AbiVersion, HANDSHAKE_MAGIC},
+    api::EngineAPI,
+    event::{Event, EventHandler},
+    plugin::{LibraryHandle, LibraryMetadata},
+    task::{Runner, Task, Verifiable},
+};
+
+/// Symbol C ABI mods export their [`CPluginDescriptor`] under.
+pub const CABI_SYMBOL: &[u8] = b"ENGINE_CABI_PLUGIN";
+pub const RUNNER_CPU: u32 = 0;
+pub const RUNNER_HIP: u32 = 1;
+
+/// Borrowed bytes, only valid for the duration of the call they are passed to.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct FfiSlice {
+    pub ptr: *const u8,
+    pub len: usize,
+}
+
+impl FfiSlice {
+    pub fn new(bytes: &[u8]) -> Self {
+        Self {
+            ptr: bytes.as_ptr(),
+            len: bytes.len(),
+        }
+    }
+    /// # Safety
+    /// `ptr` must point to `len` readable bytes that outlive `'a`.
+    pub unsafe fn as_bytes<'a>(&self) -> &'a [u8] {
+        if self.ptr.is_null() {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+    /// # Safety
+    /// Same as [`FfiSlice::as_bytes`].
+    pub unsafe fn to_string_lossy(&self) -> String {
+        String::from_utf8_lossy(unsafe { self.as_bytes() }).into_owned()
+    }
+}
+
+/// Bytes allocated on one side of the boundary, handed back to that side's `free_buf` once copied.
+#[repr(C)]
+#[derive(Debug)]
+pub struct FfiBuf {
+    pub ptr: *mut u8,
+    pub len: usize,
+    pub cap: usize,
+}
+
+impl FfiBuf {
+    pub fn from_vec(bytes: Vec<u8>) -> Self {
+        let mut bytes = std::mem::ManuallyDrop::new(bytes);
+        Self {
+            ptr: bytes.as_mut_ptr(),
+            len: bytes.len(),
+            cap: bytes.capacity(),
+        }
+    }
+    /// Copies the bytes out and frees the buffer.
+    ///
+    /// # Safety
+    /// `free_buf` must belong to the side of the boundary that allocated the buffer.
+    pub unsafe fn take(self, free_buf: unsafe extern "C" fn(FfiBuf)) -> Vec<u8> {
+        let slice = FfiSlice {
+            ptr: self.ptr,
+            len: self.len,
+        };
+        let bytes = unsafe { slice.as_bytes() }.to_vec();
+        unsafe { free_buf(self) };
+        bytes
+    }
+}
+
+unsafe extern "C" fn free_buf(buf: FfiBuf) {
+    if !buf.ptr.is_null() {
+        drop(unsafe { Vec::from_raw_parts(buf.ptr, buf.len, buf.cap) });
+    }
+}
+
+/// A task type provided by a C ABI mod.
+///
+/// Task instances are opaque pointers owned by the mod, `prototype` is the registered instance.
+#[repr(C)]
+pub struct TaskVTable {
+    pub namespace: FfiSlice,
+    pub name: FfiSlice,
+    pub prototype: *mut c_void,
+    /// Returns null if the bytes are not a valid task.
+    pub deserialize: unsafe extern "C" fn(prototype: *const c_void, bytes: FfiSlice) -> *mut c_void,
+    pub serialize: unsafe extern "C" fn(task: *const c_void) -> FfiBuf,
+    pub verify: unsafe extern "C" fn(prototype: *const c_void, bytes: FfiSlice) -> bool,
+    pub run: unsafe extern "C" fn(task: *mut c_void, runner: u32),
+    /// Returns null if the toml is not a valid task.
+    pub from_toml: unsafe extern "C" fn(prototype: *const c_void, toml: FfiSlice) -> *mut c_void,
+    pub to_toml: unsafe extern "C" fn(task: *const c_void) -> FfiBuf,
+    pub clone: unsafe extern "C" fn(task: *const c_void) -> *mut c_void,
+    pub drop: unsafe extern "C" fn(task: *mut c_void),
+    pub free_buf: unsafe extern "C" fn(buf: FfiBuf),
+}
+
+// The vtable only holds function pointers and the prototype, shared like any registered task.
+unsafe impl Send for TaskVTable {}
+unsafe impl Sync for TaskVTable {}
+
+/// An event handler provided by a C ABI mod.
+#[repr(C)]
+pub struct EventHandlerVTable {
+    pub ctx: *mut c_void,
+    /// Sets `cancelled` to cancel the event.
+    pub handle: unsafe extern "C" fn(
+        ctx: *const c_void,
+        namespace: FfiSlice,
+        name: FfiSlice,
+        cancelled: *mut bool,
+    ),
+    pub drop: unsafe extern "C" fn(ctx: *mut c_void),
+}
+
+/// Functions the engine hands to a C ABI mod's `init`.
+#[repr(C)]
+pub struct HostVTable {
+    pub host: *mut c_void,
+    pub abi_version: AbiVersion,
+    pub register_task: unsafe extern "C" fn(host: *mut c_void, task: TaskVTable),
+    pub register_event_handler: unsafe extern "C" fn(
+        host: *mut c_void,
+        namespace: FfiSlice,
+        name: FfiSlice,
+        handler: EventHandlerVTable,
+    ),
+}
+
+/// Exported by C ABI mods under [`CABI_SYMBOL`], usually generated by `#[c_module]`.
+#[repr(C)]
+pub struct CPluginDescriptor {
+    pub magic: [u8; 8],
+    pub abi_version: AbiVersion,
+    /// Postcard encoded [`LibraryMetadata`].
+    pub metadata: unsafe extern "C" fn() -> FfiBuf,
+    pub free_buf: unsafe extern "C" fn(buf: FfiBuf),
+    /// Registers the mod's tasks and handlers, returns false on failure.
+    pub init: unsafe extern "C" fn(host: *const HostVTable) -> bool,
+}
+
+impl CPluginDescriptor {
+    pub const fn new(
+        metadata: unsafe extern "C" fn() -> FfiBuf,
+        init: unsafe extern "C" fn(host: *const HostVTable) -> bool,
+    ) -> Self {
+        Self {
+            magic: HANDSHAKE_MAGIC,
+            abi_version: ABI_VERSION,
+            metadata,
+            free_buf,
+            init,
+        }
+    }
+
+    /// Checks the descriptor and reads the mod's metadata.
+    pub fn read_metadata(&self) -> Result<LibraryMetadata, String> {
+        if self.magic != HANDSHAKE_MAGIC {
+            return Err("Invalid C ABI plugin descriptor".into());
+        }
+        if !self.abi_version.is_compatible_with(&ABI_VERSION) {
+            return Err(format!(
+                "ABI version mismatch - Module ABI: {}, Engine ABI: {}",
+                self.abi_version, ABI_VERSION
+            ));
+        }
+        let bytes = unsafe { (self.metadata)().take(self.free_buf) };
+        postcard::from_bytes(&bytes).map_err(|e| format!("Invalid mod metadata: {}", e))
+    }
+
+    /// Runs the mod's `init`, registering into `api`. Tasks keep `library`, the mod's own
+    /// library, loaded for as long as any instance of them is alive.
+    pub fn init(&self, api: &mut EngineAPI, library: Option<LibraryHandle>) -> Result<(), String> {
+        let mut context = HostContext { api, library };
+        let host = host_vtable(&mut context);
+        if unsafe { (self.init)(&host) } {
+            Ok(())
+        } else {
+            Err("C ABI plugin init failed".into())
+        }
+    }
+}
+
+// Engine side.
+
+/// What a [`HostVTable`] registers into.
+struct HostContext<'a> {
+    api: &'a mut EngineAPI,
+    library: Option<LibraryHandle>,
+}
+
+/// [`HostVTable`] registering into `context`, valid as long as `context` is.
+fn host_vtable(context: &mut HostContext) -> HostVTable {
+    HostVTable {
+        host: context as *mut HostContext as *mut c_void,
+        abi_version: ABI_VERSION,
+        register_task: host_register_task,
+        register_event_handler: host_register_event_handler,
+    }
+}
+
+unsafe extern "C" fn host_register_task(host: *mut c_void, task: TaskVTable) {
+    let context = unsafe { &mut *(host as *mut HostContext) };
+    let id: Identifier = unsafe {
+        (
+            task.namespace.to_string_lossy(),
+            task.name.to_string_lossy(),
+        )
+    };
+    debug!("C ABI: Registering task {}.{}", id.0, id.1);
+    let instance = task.prototype;
+    let task = CTask {
+        vtable: Arc::new(task),
+        instance,
+        id: id.clone(),
+        library: context.library.clone(),
+    };
+    context.api.task_registry.register(Arc::new(task), id);
+}
+
+unsafe extern "C" fn host_register_event_handler(
+    host: *mut c_void,
+    namespace: FfiSlice,
+    name: FfiSlice,
+    handler: EventHandlerVTable,
+) {
+    let context = unsafe { &mut *(host as *mut HostContext) };
+    let id: Identifier = unsafe { (namespace.to_string_lossy(), name.to_string_lossy()) };
+    context
+        .api
+        .event_bus
+        .event_handler_registry
+        .register_handler(CEventHandler { vtable: handler }, id);
+}
+
+/// A task instance living in a C ABI mod.
+pub struct CTask {
+    vtable: Arc<TaskVTable>,
+    instance: *mut c_void,
+    id: Identifier,
+    /// Library of the mod, dropped after the instance so an unloaded mod stays mapped until
+    /// its last task is gone.
+    library: Option<LibraryHandle>,
+}
+
+// The mod's tasks are `Send + Sync` Rust tasks behind the vtable.
+unsafe impl Send for CTask {}
+unsafe impl Sync for CTask {}
+
+impl CTask {
+    fn wrap(&self, instance: *mut c_void, what: &str) -> Box<dyn Task> {
+        if instance.is_null() {
+            panic!("C ABI task {}.{}: {}", self.id.0, self.id.1, what);
+        }
+        Box::new(CTask {
+            vtable: self.vtable.clone(),
+            instance,
+            id: self.id.clone(),
+            library: self.library.clone(),
+        })
+    }
+}
+
+impl Debug for CTask {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CTask").field("id", &self.id).finish()
+    }
+}
+
+impl Drop for CTask {
+    fn drop(&mut self) {
+        unsafe { (self.vtable.drop)(self.instance) };
+    }
+}
+
+impl Verifiable for CTask {
+    fn verify(&self, b: Vec<u8>) -> bool {
+        unsafe { (self.vtable.verify)(self.instance, FfiSlice::new(&b)) }
+    }
+}
+
+impl Task for CTask {
+    fn get_id(&self) -> Identifier {
+        self.id.clone()
+    }
+    fn clone_box(&self) -> Box<dyn Task> {
+        self.wrap(
+            unsafe { (self.vtable.clone)(self.instance) },
+            "failed to clone",
+        )
+    }
+    fn run_hip(&mut self) {
+        unsafe { (self.vtable.run)(self.instance, RUNNER_HIP) };
+    }
+    fn run_cpu(&mut self) {
+        unsafe { (self.vtable.run)(self.instance, RUNNER_CPU) };
+    }
+    fn to_bytes(&self) -> Vec<u8> {
+        unsafe { (self.vtable.serialize)(self.instance).take(self.vtable.free_buf) }
+    }
+    fn from_bytes(&self, bytes: &[u8]) -> Box<dyn Task> {
+        self.wrap(
+            unsafe { (self.vtable.deserialize)(self.instance, FfiSlice::new(bytes)) },
+            "invalid task bytes",
+        )
+    }
+    fn from_toml(&self, d: String) -> Box<dyn Task> {
+        self.wrap(
+            unsafe { (self.vtable.from_toml)(self.instance, FfiSlice::new(d.as_bytes())) },
+            "invalid task toml",
+        )
+    }
+    fn to_toml(&self) -> String {
+        let bytes = unsafe { (self.vtable.to_toml)(self.instance).take(self.vtable.free_buf) };
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+}
+
+struct CEventHandler {
+    vtable: EventHandlerVTable,
+}
+
+// The mod's handlers are `Send + Sync` Rust closures behind the vtable.
+unsafe impl Send for CEventHandler {}
+unsafe impl Sync for CEventHandler {}
+
+impl EventHandler for CEventHandler {
+    fn handle(&self, event: &mut dyn Event) {
+        let (namespace, name) = event.get_id();
+        let mut cancelled = false;
+        unsafe {
+            (self.vtable.handle)(
+                self.vtable.ctx,
+                FfiSlice::new(namespace.as_bytes()),
+                FfiSlice::new(name.as_bytes()),
+                &mut cancelled,
+            )
+        };
+        if cancelled {
+            event.cancel();
+        }
+    }
+}
+
+impl Drop for CEventHandler {
+    fn drop(&mut self) {
+        unsafe { (self.vtable.drop)(self.vtable.ctx) };
+    }
+}
+
+// Mod side.
+
+/// Tasks that can be registered through the C ABI, implemented by `#[c_task]`.
+pub trait CAbiTask: Task + Default + 'static {}
+
+type HandlerFn = Box<dyn Fn(&Identifier) -> bool + Send + Sync>;
+
+/// What a C ABI mod's `run` registers its tasks and handlers with.
+pub struct Host<'a> {
+    vtable: &'a HostVTable,
+}
+
+impl Host<'_> {
+    pub fn register_task<T: CAbiTask>(&mut self) {
+        self.register_task_instance(Box::new(T::default()));
+    }
+
+    pub fn register_task_instance(&mut self, task: Box<dyn Task>) {
+        let (namespace, name) = task.get_id();
+        let vtable = TaskVTable {
+            namespace: FfiSlice::new(namespace.as_bytes()),
+            name: FfiSlice::new(name.as_bytes()),
+            prototype: into_opaque(task),
+            deserialize: task_deserialize,
+            serialize: task_serialize,
+            verify: task_verify,
+            run: task_run,
+            from_toml: task_from_toml,
+            to_toml: task_to_toml,
+            clone: task_clone,
+            drop: task_drop,
+            free_buf,
+        };
+        unsafe { (self.vtable.register_task)(self.vtable.host, vtable) };
+    }
+
+    /// Registers `handler` for the event `id`, it returns whether to cancel the event.
+    pub fn register_event_handler<F>(&mut self, id: Identifier, handler: F)
+    where
+        F: Fn(&Identifier) -> bool + Send + Sync + 'static,
+    {
+        let handler: HandlerFn = Box::new(handler);
+        let vtable = EventHandlerVTable {
+            ctx: Box::into_raw(Box::new(handler)) as *mut c_void,
+            handle: handler_handle,
+            drop: handler_drop,
+        };
+        unsafe {
+            (self.vtable.register_event_handler)(
+                self.vtable.host,
+                FfiSlice::new(id.0.as_bytes()),
+                FfiSlice::new(id.1.as_bytes()),
+                vtable,
+            )
+        };
+    }
+}
+
+/// Body of the `init` generated by `#[c_module]`.
+///
+/// # Safety
+/// `host` must be null or point to a valid [`HostVTable`].
+pub unsafe fn init_plugin(host: *const HostVTable, run: fn(&mut Host)) -> bool {
+    let Some(vtable) = (unsafe { host.as_ref() }) else {
+        return false;
+    };
+    if !ABI_VERSION.is_compatible_with(&vtable.abi_version) {
+        error!(
+            "C ABI: Mod built for ABI {} cannot run on engine ABI {}",
+            ABI_VERSION, vtable.abi_version
+        );
+        return false;
+    }
+    catch_unwind(AssertUnwindSafe(|| run(&mut Host { vtable }))).is_ok()
+}
+
+/// Body of the `metadata` generated by `#[c_module]`.
+pub fn encode_metadata(metadata: LibraryMetadata) -> FfiBuf {
+    FfiBuf::from_vec(postcard::to_allocvec(&metadata).unwrap_or_default())
+}
+
+fn into_opaque(task: Box<dyn Task>) -> *mut c_void {
+    Box::into_raw(Box::new(task)) as *mut c_void
+}
+
+unsafe fn task_ref<'a>(task: *const c_void) -> &'a dyn Task {
+    unsafe { &**(task as *const Box<dyn Task>) }
+}
+
+// Panics must not unwind into the engine, they turn into null or empty results instead.
+
+unsafe extern "C" fn task_deserialize(prototype: *const c_void, bytes: FfiSlice) -> *mut c_void {
+    let (prototype, bytes) = unsafe { (task_ref(prototype), bytes.as_bytes()) };
+    catch_unwind(AssertUnwindSafe(|| prototype.from_bytes(bytes)))
+        .map(into_opaque)
+        .unwrap_or(null_mut())
+}
+
+unsafe extern "C" fn task_serialize(task: *const c_void) -> FfiBuf {
+    let task = unsafe { task_ref(task) };
+    FfiBuf::from_vec(catch_unwind(AssertUnwindSafe(|| task.to_bytes())).unwrap_or_default())
+}
+
+unsafe extern "C" fn task_verify(prototype: *const c_void, bytes: FfiSlice) -> bool {
+    let (prototype, bytes) = unsafe { (task_ref(prototype), bytes.as_bytes()) };
+    catch_unwind(AssertUnwindSafe(|| prototype.verify(bytes.to_vec()))).unwrap_or(false)
+}
+
+unsafe extern "C" fn task_run(task: *mut c_void, runner: u32) {
+    let task = unsafe { &mut **(task as *mut Box<dyn Task>) };
+    let runner = match runner {
+        RUNNER_HIP => Runner::HIP,
+        _ => Runner::CPU,
+    };
+    if catch_unwind(AssertUnwindSafe(|| task.run(Some(runner)))).is_err() {
+        error!(
+            "C ABI: Task {}.{} panicked",
+            task.get_id().0,
+            task.get_id().1
+        );
+    }
+}
+
+unsafe extern "C" fn task_from_toml(prototype: *const c_void, toml: FfiSlice) -> *mut c_void {
+    let (prototype, toml) = unsafe { (task_ref(prototype), toml.to_string_lossy()) };
+    catch_unwind(AssertUnwindSafe(|| prototype.from_toml(toml)))
+        .map(into_opaque)
+        .unwrap_or(null_mut())
+}
+
+unsafe extern "C" fn task_to_toml(task: *const c_void) -> FfiBuf {
+    let task = unsafe { task_ref(task) };
+    let toml = catch_unwind(AssertUnwindSafe(|| task.to_toml())).unwrap_or_default();
+    FfiBuf::from_vec(toml.into_bytes())
+}
+
+unsafe extern "C" fn task_clone(task: *const c_void) -> *mut c_void {
+    let task = unsafe { task_ref(task) };
+    catch_unwind(AssertUnwindSafe(|| task.clone_box()))
+        .map(into_opaque)
+        .unwrap_or(null_mut())
+}
+
+unsafe extern "C" fn task_drop(task: *mut c_void) {
+    drop(unsafe { Box::from_raw(task as *mut Box<dyn Task>) });
+}
+
+unsafe extern "C" fn handler_handle(
+    ctx: *const c_void,
+    namespace: FfiSlice,
+    name: FfiSlice,
+    cancelled: *mut bool,
+) {
+    let handler = unsafe { &*(ctx as *const HandlerFn) };
+    let id = unsafe { (namespace.to_string_lossy(), name.to_string_lossy()) };
+    if let Ok(true) = catch_unwind(AssertUnwindSafe(|| handler(&id))) {
+        unsafe { *cancelled = true };
+    }
+}
+
+unsafe extern "C" fn handler_drop(ctx: *mut c_void) {
+    drop(unsafe { Box::from_raw(ctx as *mut HandlerFn) });
+}
@@ -0,0 +1,385 @@
+use std::{collections::HashMap, future::Future, marker::PhantomData, pin::Pin, sync::Arc};
+
+use serde::{Serialize, de::DeserializeOwned};
+use tokio::sync::mpsc;
+use tracing::debug;
+
+use crate::Identifier;
+
+/// Status a cgrpc handler answers with, the discriminants are the gRPC status codes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CgrpcCode {
+    #[default]
+    Ok = 0,
+    Cancelled = 1,
+    Unknown = 2,
+    InvalidArgument = 3,
+    DeadlineExceeded = 4,
+    NotFound = 5,
+    AlreadyExists = 6,
+    PermissionDenied = 7,
+    ResourceExhausted = 8,
+    FailedPrecondition = 9,
+    Aborted = 10,
+    OutOfRange = 11,
+    Unimplemented = 12,
+    Internal = 13,
+    Unavailable = 14,
+    DataLoss = 15,
+    Unauthenticated = 16,
+}
+
+/// Encoding of cgrpc payloads, picked by the `content_type` of the request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ContentType {
+    #[default]
+    Postcard,
+    Json,
+}
+
+impl ContentType {
+    /// Parses a `content_type`, an empty one is postcard.
+    pub fn parse(content_type: &str) -> Result<Self, String> {
+        match content_type {
+            "" | "application/postcard" => Ok(Self::Postcard),
+            "application/json" => Ok(Self::Json),
+            other => Err(format!("Unsupported content type {}", other)),
+        }
+    }
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Postcard => "application/postcard",
+            Self::Json => "application/json",
+        }
+    }
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, String> {
+        match self {
+            Self::Postcard => postcard::from_bytes(bytes).map_err(|e| e.to_string()),
+            Self::Json => serde_json::from_slice(bytes).map_err(|e| e.to_string()),
+        }
+    }
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, String> {
+        match self {
+            Self::Postcard => postcard::to_allocvec(value).map_err(|e| e.to_string()),
+            Self::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CgrpcRequest {
+    pub handler: Identifier,
+    pub uid: String,
+    pub content_type: ContentType,
+    pub payload: Vec<u8>,
+    pub metadata: HashMap<String, String>, // ASCII request metadata, without `authorization`.
+}
+
+/// What a cgrpc handler answers. The payload is only sent back with [`CgrpcCode::Ok`], the
+/// message only with an error, the metadata with both.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CgrpcResponse {
+    pub code: CgrpcCode,
+    pub message: String,
+    pub payload: Vec<u8>,
+    pub metadata: HashMap<String, String>,
+}
+
+impl CgrpcResponse {
+    pub fn ok(payload: Vec<u8>) -> Self {
+        Self {
+            payload,
+            ..Default::default()
+        }
+    }
+    pub fn error(code: CgrpcCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            ..Default::default()
+        }
+    }
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+}
+
+/// Describes a request or response type of a typed handler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CgrpcSchema {
+    pub type_name: String,
+    pub example: String, // The type's default value as JSON.
+}
+
+impl CgrpcSchema {
+    pub fn of<T: Serialize + Default>() -> Self {
+        Self {
+            type_name: std::any::type_name::<T>().to_string(),
+            example: serde_json::to_string(&T::default()).unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CgrpcSchemas {
+    pub request: CgrpcSchema,
+    pub response: CgrpcSchema,
+}
+
+/// A `CgrpcStream` call. Frames the client sends arrive on `incoming`, frames pushed to
+/// `outgoing` are sent back; the call ends once the handler's future completes.
+pub struct CgrpcStream {
+    pub handler: Identifier,
+    pub uid: String,
+    pub metadata: HashMap<String, String>, // ASCII request metadata, without `authorization`.
+    pub incoming: mpsc::Receiver<Vec<u8>>,
+    pub outgoing: mpsc::Sender<Vec<u8>>,
+}
+
+/// Serves a stream, an error ends the call with its status after the frames sent so far.
+pub type StreamFuture = Pin<Box<dyn Future<Output = Result<(), CgrpcResponse>> + Send>>;
+
+pub trait CgrpcHandler: Send + Sync {
+    fn handle(&self, request: CgrpcRequest) -> CgrpcResponse;
+    /// The request and response types, `None` for handlers working on raw bytes.
+    fn schemas(&self) -> Option<CgrpcSchemas> {
+        None
+    }
+    /// Whether the handler serves `CgrpcStream` calls through
+    /// [`handle_stream`](Self::handle_stream).
+    fn streaming(&self) -> bool {
+        false
+    }
+    fn handle_stream(&self, stream: CgrpcStream) -> StreamFuture {
+        drop(stream);
+        Box::pin(async {
+            Err(CgrpcResponse::error(
+                CgrpcCode::Unimplemented,
+                "Handler does not serve streams",
+            ))
+        })
+    }
+}
+
+impl<F> CgrpcHandler for F
+where
+    F: Fn(CgrpcRequest) -> CgrpcResponse + Send + Sync,
+{
+    fn handle(&self, request: CgrpcRequest) -> CgrpcResponse {
+        self(request)
+    }
+}
+
+/// Decodes the payload as `Req` in the request's content type, runs `f` and encodes the `Res`
+/// it returns the same way. Payloads that fail to decode are answered with `InvalidArgument`.
+pub fn handle_typed<Req, Res>(
+    request: CgrpcRequest,
+    f: impl FnOnce(Req, &CgrpcRequest) -> Result<Res, CgrpcResponse>,
+) -> CgrpcResponse
+where
+    Req: DeserializeOwned,
+    Res: Serialize,
+{
+    let content_type = request.content_type;
+    let decoded = match content_type.decode(&request.payload) {
+        Ok(decoded) => decoded,
+        Err(e) => {
+            return CgrpcResponse::error(
+                CgrpcCode::InvalidArgument,
+                format!("Failed to decode request: {}", e),
+            );
+        }
+    };
+    match f(decoded, &request) {
+        Ok(response) => match content_type.encode(&response) {
+            Ok(payload) => CgrpcResponse::ok(payload),
+            Err(e) => CgrpcResponse::error(
+                CgrpcCode::Internal,
+                format!("Failed to encode response: {}", e),
+            ),
+        },
+        Err(response) => response,
+    }
+}
+
+/// A handler taking and returning serde types, see [`handle_typed`]. Both types need a
+/// `Default` to describe them in [`CgrpcSchemas`].
+pub struct TypedCgrpcHandler<Req, Res, F> {
+    f: F,
+    types: PhantomData<fn(Req) -> Res>,
+}
+
+impl<Req, Res, F> TypedCgrpcHandler<Req, Res, F> {
+    pub fn new(f: F) -> Self {
+        Self {
+            f,
+            types: PhantomData,
+        }
+    }
+}
+
+impl<Req, Res, F> CgrpcHandler for TypedCgrpcHandler<Req, Res, F>
+where
+    Req: DeserializeOwned + Serialize + Default,
+    Res: Serialize + Default,
+    F: Fn(Req, &CgrpcRequest) -> Result<Res, CgrpcResponse> + Send + Sync,
+{
+    fn handle(&self, request: CgrpcRequest) -> CgrpcResponse {
+        handle_typed(request, &self.f)
+    }
+    fn schemas(&self) -> Option<CgrpcSchemas> {
+        Some(CgrpcSchemas {
+            request: CgrpcSchema::of::<Req>(),
+            response: CgrpcSchema::of::<Res>(),
+        })
+    }
+}
+
+/// A handler serving only `CgrpcStream` calls by running `f`.
+pub struct CgrpcStreamHandler<F> {
+    f: F,
+}
+
+impl<F> CgrpcStreamHandler<F> {
+    pub fn new(f: F) -> Self {
+        Self { f }
+    }
+}
+
+impl<F, Fut> CgrpcHandler for CgrpcStreamHandler<F>
+where
+    F: Fn(CgrpcStream) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<(), CgrpcResponse>> + Send + 'static,
+{
+    fn handle(&self, _request: CgrpcRequest) -> CgrpcResponse {
+        CgrpcResponse::error(CgrpcCode::Unimplemented, "Handler only serves streams")
+    }
+    fn streaming(&self) -> bool {
+        true
+    }
+    fn handle_stream(&self, stream: CgrpcStream) -> StreamFuture {
+        Box::pin((self.f)(stream))
+    }
+}
+
+/// Handlers of the `cgrpc` and `CgrpcStream` RPCs, keyed by the `(handler_mod_id, handler_id)`
+/// requests address.
+#[derive(Default, Clone)]
+pub struct CgrpcRegistry {
+    pub handlers: HashMap<Identifier, Arc<dyn CgrpcHandler>>,
+    pub owners: HashMap<Identifier, String>, // mod_id that registered each handler.
+}
+
+impl CgrpcRegistry {
+    pub fn register(&mut self, handler: Arc<dyn CgrpcHandler>, identifier: Identifier) {
+        debug!(
+            "CgrpcRegistry: Registering handler {}.{}",
+            identifier.0, identifier.1
+        );
+        self.handlers.insert(identifier, handler);
+    }
+
+    /// Registers a [`CgrpcStreamHandler`] running `f`.
+    pub fn register_stream<F, Fut>(&mut self, f: F, identifier: Identifier)
+    where
+        F: Fn(CgrpcStream) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), CgrpcResponse>> + Send + 'static,
+    {
+        self.register(Arc::new(CgrpcStreamHandler::new(f)), identifier);
+    }
+
+    /// Registers a [`TypedCgrpcHandler`] running `f`.
+    pub fn register_typed<Req, Res, F>(&mut self, f: F, identifier: Identifier)
+    where
+        Req: DeserializeOwned + Serialize + Default + 'static,
+        Res: Serialize + Default + 'static,
+        F: Fn(Req, &CgrpcRequest) -> Result<Res, CgrpcResponse> + Send + Sync + 'static,
+    {
+        self.register(Arc::new(TypedCgrpcHandler::new(f)), identifier);
+    }
+
+    pub fn get(&self, identifier: &Identifier) -> Option<Arc<dyn CgrpcHandler>> {
+        self.handlers.get(identifier).cloned()
+    }
+}
+
+#[macro_export]
+macro_rules! RegisterCgrpcHandler {
+    ($handler:ident,$handler_mod_id:ident,$handler_id:ident,$req:ty => $res:ty,$handler_fn:expr) => {
+        pub struct $handler;
+        impl $handler {
+            /// The `(handler_mod_id, handler_id)` requests address this handler by.
+            pub fn id() -> $crate::Identifier {
+                (
+                    stringify!($handler_mod_id).to_string(),
+                    stringify!($handler_id).to_string(),
+                )
+            }
+        }
+        impl $crate::cgrpc::CgrpcHandler for $handler {
+            fn handle(&self, request: $crate::cgrpc::CgrpcRequest) -> $crate::cgrpc::CgrpcResponse {
+                $crate::cgrpc::handle_typed::<$req, $res>(request, $handler_fn)
+            }
+            fn schemas(&self) -> Option<$crate::cgrpc::CgrpcSchemas> {
+                Some($crate::cgrpc::CgrpcSchemas {
+                    request: $crate::cgrpc::CgrpcSchema::of::<$req>(),
+                    response: $crate::cgrpc::CgrpcSchema::of::<$res>(),
+                })
+            }
+        }
+    };
+    ($handler:ident,$handler_mod_id:ident,$handler_id:ident,$handler_fn:expr) => {
+        pub struct $handler;
+        impl $handler {
+            /// The `(handler_mod_id, handler_id)` requests address this handler by.
+            pub fn id() -> $crate::Identifier {
+                (
+                    stringify!($handler_mod_id).to_string(),
+                    stringify!($handler_id).to_string(),
+                )
+            }
+        }
+        impl $crate::cgrpc::CgrpcHandler for $handler {
+            fn handle(&self, request: $crate::cgrpc::CgrpcRequest) -> $crate::cgrpc::CgrpcResponse {
+                $handler_fn(request)
+            }
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! RegisterCgrpcStreamHandler {
+    ($handler:ident,$handler_mod_id:ident,$handler_id:ident,$handler_fn:expr) => {
+        pub struct $handler;
+        impl $handler {
+            /// The `(handler_mod_id, handler_id)` requests address this handler by.
+            pub fn id() -> $crate::Identifier {
+                (
+                    stringify!($handler_mod_id).to_string(),
+                    stringify!($handler_id).to_string(),
+                )
+            }
+        }
+        impl $crate::cgrpc::CgrpcHandler for $handler {
+            fn handle(
+                &self,
+                _request: $crate::cgrpc::CgrpcRequest,
+            ) -> $crate::cgrpc::CgrpcResponse {
+                $crate::cgrpc::CgrpcResponse::error(
+                    $crate::cgrpc::CgrpcCode::Unimplemented,
+                    "Handler only serves streams",
+                )
+            }
+            fn streaming(&self) -> bool {
+                true
+            }
+            fn handle_stream(
+                &self,
+                stream: $crate::cgrpc::CgrpcStream,
+            ) -> $crate::cgrpc::StreamFuture {
+                Box::pin($handler_fn(stream))
+            }
+        }
+    };
+}
@@ -1,7 +1,11 @@
-use std::{fs, io::Error, u32};
+use std::{collections::HashMap, env, fs, io::Error, path::PathBuf, u32};
 
-use serde::{Deserialize, Serialize};
-use tracing::{error, instrument};
+use serde::{Deserialize, Deserializer, Serialize, de::DeserializeOwned, de::Error as _};
+use tracing::{error, instrument, warn};
+
+use crate::{
+    limits::LimitsConfig, plugin::ConflictPolicy, scheduler::SchedulingPolicy, wasm::WasmLimits,
+};
 
 fn default_host() -> String {
     "[::1]:50051".into()
@@ -15,6 +19,59 @@ fn default_pagination_limit() -> u32 {
     u32::MAX
 }
 
+fn default_audit_retention_days() -> u64 {
+    30
+}
+
+fn default_lease_seconds() -> u64 {
+    3600
+}
+
+fn default_wasm_fuel() -> u64 {
+    1_000_000_000
+}
+
+fn default_wasm_max_memory() -> usize {
+    64 * 1024 * 1024
+}
+
+fn default_wasm_max_output() -> usize {
+    16 * 1024 * 1024
+}
+
+fn default_mods_dirs() -> Vec<PathBuf> {
+    vec!["./mods".into()]
+}
+
+fn deserialize_mod_sections<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<String, toml::Table>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Sections {
+        Tables(HashMap<String, toml::Table>),
+        Paths(Vec<PathBuf>),
+    }
+    match Sections::deserialize(deserializer)? {
+        Sections::Tables(sections) => Ok(sections),
+        Sections::Paths(paths) => Err(D::Error::custom(format!(
+            "`mods` holds [mods.<mod_id>] sections, list the {} module path(s) in `mod_paths`",
+            paths.len()
+        ))),
+    }
+}
+
+/// Configuration a mod reads from its `[mods.<mod_id>]` section.
+///
+/// `Default` is used when the section is missing, add `#[serde(default)]` to also fill in
+/// missing fields from it.
+pub trait ModConfig: DeserializeOwned + Default {
+    /// Checks the values beyond what deserializing does, an error fails the mod's load.
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ConfigTomlServer {
     #[serde(default)]
@@ -25,6 +82,107 @@ pub struct ConfigTomlServer {
     pub clean_tasks: u64,
     #[serde(default = "default_pagination_limit")]
     pub pagination_limit: u32,
+    #[serde(default = "default_audit_retention_days")]
+    pub audit_retention_days: u64, // Audit log entries older than this are purged, 0 keeps them forever.
+    #[serde(default = "default_lease_seconds")]
+    pub lease_seconds: u64, // Executing tasks older than this are moved back to the queue.
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    #[serde(default)]
+    pub uid_limits: HashMap<String, LimitsConfig>, // Per uid overrides of `limits`.
+    #[serde(default)]
+    pub scheduling_policy: SchedulingPolicy, // "fifo", "weighted_round_robin" or "deficit_round_robin".
+    #[serde(default = "default_mods_dirs")]
+    pub mods_dirs: Vec<PathBuf>, // Directories scanned for *.rustforge.tar modules, in order.
+    #[serde(default)]
+    pub mod_paths: Vec<PathBuf>, // Module archives or libraries loaded after the scanned ones.
+    #[serde(default, deserialize_with = "deserialize_mod_sections")]
+    pub mods: HashMap<String, toml::Table>, // [mods.<mod_id>] sections, read by each mod through `EngineAPI::mod_config`.
+    #[serde(default)]
+    pub mods_watch_seconds: u64, // Seconds between checks of the modules for changes, 0 disables hot reloading.
+    #[serde(default)]
+    pub registration_conflicts: ConflictPolicy, // "error", "warn" or "priority", when mods register the same task or event.
+    #[serde(default)]
+    pub mod_priorities: HashMap<String, i64>, // mod_id -> priority under the "priority" policy, 0 by default.
+    #[serde(default)]
+    pub trusted_keys: Vec<String>, // Hex Ed25519 public keys accepted on mod package signatures.
+    #[serde(default)]
+    pub allow_unsigned_mods: bool, // Loads packages without a trusted signature, bare libraries and mod directories, for development only.
+    #[serde(default = "default_wasm_fuel")]
+    pub wasm_fuel: u64, // Fuel (roughly instructions) a single call into a WASM task may use.
+    #[serde(default = "default_wasm_max_memory")]
+    pub wasm_max_memory: usize, // Bytes of linear memory a WASM task may grow to.
+    #[serde(default = "default_wasm_max_output")]
+    pub wasm_max_output: usize, // Bytes a single call into a WASM task may return.
+    #[serde(default)]
+    pub requeue_on_shutdown: bool, // Moves leased tasks back to the queue on shutdown instead of keeping their leases.
+    #[serde(default)]
+    pub record_events: Option<PathBuf>, // Appends every dispatched event to this file, see `enginelib::replay`.
+}
+impl ConfigTomlServer {
+    /// Parses a config file, moving a deprecated `mods = [...]` list of module paths to
+    /// `mod_paths` so configs written before `[mods.<mod_id>]` sections keep loading. Rate limits
+    /// are validated, see [`LimitsConfig::validate`].
+    pub fn parse(content: &str) -> Result<Self, toml::de::Error> {
+        let mut table: toml::Table = toml::from_str(content)?;
+        if let Some(toml::Value::Array(paths)) = table.get("mods").cloned() {
+            warn!(
+                "`mods = [...]` is deprecated, list the {} module path(s) in `mod_paths`",
+                paths.len()
+            );
+            table.remove("mods");
+            match table
+                .entry("mod_paths")
+                .or_insert_with(|| toml::Value::Array(Vec::new()))
+            {
+                toml::Value::Array(mod_paths) => mod_paths.extend(paths),
+                _ => return Err(toml::de::Error::custom("`mod_paths` must be a list")),
+            }
+        }
+        let config: Self = table.try_into()?;
+        config
+            .limits
+            .validate()
+            .map_err(|e| toml::de::Error::custom(format!("[limits] {}", e)))?;
+        for (uid, limits) in &config.uid_limits {
+            limits
+                .validate()
+                .map_err(|e| toml::de::Error::custom(format!("[uid_limits.{}] {}", uid, e)))?;
+        }
+        Ok(config)
+    }
+    pub fn wasm_limits(&self) -> WasmLimits {
+        WasmLimits {
+            fuel: self.wasm_fuel,
+            max_memory: self.wasm_max_memory,
+            max_output: self.wasm_max_output,
+        }
+    }
+    /// Applies `ENGINE_MODS_DIRS` and `ENGINE_MODS`, both lists in the platform's `PATH` format.
+    pub fn apply_env(&mut self) {
+        if let Some(dirs) = env::var_os("ENGINE_MODS_DIRS") {
+            self.mods_dirs = env::split_paths(&dirs).collect();
+        }
+        if let Some(mods) = env::var_os("ENGINE_MODS") {
+            self.mod_paths = env::split_paths(&mods).collect();
+        }
+    }
+    /// Reads and validates the `[mods.<mod_id>]` section, using `T::default()` when it is missing.
+    pub fn mod_config<T: ModConfig>(&self, mod_id: &str) -> Result<T, String> {
+        let config = match self.mods.get(mod_id) {
+            Some(section) => section.clone().try_into().map_err(|e| e.to_string()),
+            None => Ok(T::default()),
+        };
+        config
+            .and_then(|config| config.validate().map(|()| config))
+            .map_err(|e| format!("Invalid [mods.{}]: {}", mod_id, e))
+    }
+    pub fn limits_for(&self, uid: &str) -> LimitsConfig {
+        match self.uid_limits.get(uid) {
+            Some(overrides) => self.limits.merged(overrides),
+            None => self.limits.clone(),
+        }
+    }
 }
 impl Default for ConfigTomlServer {
     fn default() -> Self {
@@ -33,6 +191,24 @@ impl Default for ConfigTomlServer {
             cgrpc_token: None,
             clean_tasks: 60,
             pagination_limit: u32::MAX,
+            audit_retention_days: 30,
+            lease_seconds: 3600,
+            limits: LimitsConfig::default(),
+            uid_limits: HashMap::new(),
+            scheduling_policy: SchedulingPolicy::default(),
+            mods_dirs: default_mods_dirs(),
+            mod_paths: Vec::new(),
+            mods: HashMap::new(),
+            mods_watch_seconds: 0,
+            registration_conflicts: ConflictPolicy::default(),
+            mod_priorities: HashMap::new(),
+            trusted_keys: Vec::new(),
+            allow_unsigned_mods: false,
+            wasm_fuel: default_wasm_fuel(),
+            wasm_max_memory: default_wasm_max_memory(),
+            wasm_max_output: default_wasm_max_output(),
+            requeue_on_shutdown: false,
+            record_events: None,
         }
     }
 }
@@ -50,11 +226,12 @@ impl Config {
         if result.is_ok() {
             content = result.unwrap();
         };
-        let config_toml: ConfigTomlServer = toml::from_str(&content).unwrap_or_else(|err| {
+        let mut config_toml = ConfigTomlServer::parse(&content).unwrap_or_else(|err| {
             error!("Failed to parse config file.");
             error!("{:#?}", err);
             ConfigTomlServer::default()
         });
+        config_toml.apply_env();
         Self { config_toml }
     }
 }
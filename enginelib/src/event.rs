@@ -1,25 +1,47 @@
 use crate::Identifier;
 use crate::Registry;
-use std::any::Any;
+use crate::plugin::LibraryHandle;
+use crate::replay::EventRecorder;
+use std::any::{Any, TypeId};
 use std::collections::HashMap;
 use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::pin::Pin;
 use std::sync::Arc;
 use tracing::instrument;
 pub use tracing::{debug, error, event, info, warn};
 // The Actual Fuck
 // this fucking piece of god given code saves so much time and wastes soo much time
 
+/// Downcasts an event to the type a handler expects, logging an error on a mismatch.
+pub fn downcast_event<E: Event>(event: &mut dyn Event) -> Option<&mut E> {
+    let (namespace, id) = event.get_id();
+    let found = event.type_name();
+    let downcast = event.as_any_mut().downcast_mut::<E>();
+    if downcast.is_none() {
+        error!(
+            "EventBus: Handler for {} received event {}.{} of type {}",
+            std::any::type_name::<E>(),
+            namespace,
+            id,
+            found
+        );
+    }
+    downcast
+}
+
 pub trait EventCTX<C: Event>: EventHandler {
-    fn get_event<T: Event + Sized>(event: &mut dyn Event) -> &mut T {
+    fn get_event<T: Event + Sized>(event: &mut dyn Event) -> Option<&mut T> {
         debug!("Aquiring Event");
-        unsafe { &mut *(event as *mut dyn Event as *mut T) }
+        downcast_event(event)
     }
     fn handle(&self, event: &mut dyn Event) {
         let namespace = event.get_id().0;
         let id = event.get_id().1;
         debug!("EventBus: Handling event {}.{}", namespace, id);
-        let event: &mut C = unsafe { &mut *(event as *mut dyn Event as *mut C) };
-        self.handleCTX(event);
+        if let Some(event) = downcast_event::<C>(event) {
+            self.handleCTX(event);
+        }
     }
     #[allow(non_snake_case)]
     fn handleCTX(&self, event: &mut C);
@@ -28,6 +50,7 @@ pub trait EventCTX<C: Event>: EventHandler {
 pub struct EventBus {
     pub event_registry: EngineEventRegistry,
     pub event_handler_registry: EngineEventHandlerRegistry,
+    pub recorder: Option<EventRecorder>,
 }
 impl Debug for EventBus {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -41,34 +64,197 @@ pub trait Event: Any + Send + Sync + Debug {
     fn get_id(&self) -> Identifier;
     fn as_any(&self) -> &dyn Any;
     fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+    /// The event as JSON for the [`EventRecorder`], `None` if it can't be replayed.
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        None
+    }
 }
 
 pub trait EventHandler: Any + Send + Sync {
     fn handle(&self, event: &mut dyn Event);
+    /// Type of event the handler accepts, checked against the registered event when known.
+    fn event_type(&self) -> Option<TypeId> {
+        None
+    }
+    /// The async handler to await instead of `handle`, see [`EventBus::handle_async`].
+    fn as_async(&self) -> Option<&dyn AsyncEventHandler> {
+        None
+    }
+}
+
+pub type HandlerFuture<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+/// Handler that may await, e.g. for I/O in an auth hook. [`EventBus::handle_async`] awaits it,
+/// [`EventBus::handle`] blocks on it.
+pub trait AsyncEventHandler: Any + Send + Sync {
+    fn handle<'a>(&'a self, event: &'a mut dyn Event) -> HandlerFuture<'a>;
+    fn event_type(&self) -> Option<TypeId> {
+        None
+    }
+}
+
+/// Stores an async handler next to the sync ones, sharing their priorities and provenance.
+struct AsyncAdapter<H>(H);
+
+impl<H: AsyncEventHandler> EventHandler for AsyncAdapter<H> {
+    fn handle(&self, event: &mut dyn Event) {
+        block_on(self.0.handle(event));
+    }
+    fn event_type(&self) -> Option<TypeId> {
+        self.0.event_type()
+    }
+    fn as_async(&self) -> Option<&dyn AsyncEventHandler> {
+        Some(&self.0)
+    }
+}
+
+/// Drives an async handler to completion from the sync path, on the caller's runtime when it
+/// may block and on a runtime of its own otherwise.
+fn block_on(future: HandlerFuture<'_>) {
+    use tokio::runtime::{Builder, Handle, RuntimeFlavor};
+    let run = |future: HandlerFuture<'_>| match Builder::new_current_thread().enable_all().build() {
+        Ok(runtime) => runtime.block_on(future),
+        Err(e) => error!(
+            "EventBus: Failed to start a runtime for an async handler: {}",
+            e
+        ),
+    };
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(|| handle.block_on(future))
+        }
+        // A current thread runtime cannot block its only thread, nor start a nested runtime
+        Ok(_) => std::thread::scope(|scope| {
+            if let Err(panic) = scope.spawn(|| run(future)).join() {
+                std::panic::resume_unwind(panic);
+            }
+        }),
+        Err(_) => run(future),
+    }
+}
+
+/// Handler registered through [`EventBus::subscribe`].
+struct TypedHandler<E, F> {
+    handler: F,
+    event: PhantomData<fn(&mut E)>,
+}
+
+impl<E: Event, F: Fn(&mut E) + Send + Sync + 'static> EventHandler for TypedHandler<E, F> {
+    fn handle(&self, event: &mut dyn Event) {
+        if let Some(event) = downcast_event::<E>(event) {
+            (self.handler)(event);
+        }
+    }
+    fn event_type(&self) -> Option<TypeId> {
+        Some(TypeId::of::<E>())
+    }
 }
 
 #[derive(Default, Clone)]
 pub struct EngineEventRegistry {
     pub events: HashMap<Identifier, Arc<dyn Event>>,
+    pub owners: HashMap<Identifier, String>, // mod_id that registered each event.
+}
+
+/// When a handler runs relative to the others of an event, from first to last.
+///
+/// Later handlers get the final say, `Monitor` is meant for observing the outcome and should not
+/// change the event.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EventPriority {
+    Lowest,
+    Low,
+    #[default]
+    Normal,
+    High,
+    Highest,
+    Monitor,
+}
+
+/// How a handler is registered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HandlerOptions {
+    pub priority: EventPriority,
+    pub receive_cancelled: bool, // Still runs after an earlier handler cancelled the event.
+}
+
+/// A handler together with the mod that registered it, empty for the engine itself.
+#[derive(Clone)]
+pub struct RegisteredHandler {
+    pub handler: Arc<dyn EventHandler>,
+    pub owner: String,
+    pub options: HandlerOptions,
+    /// Library of the owner, set by [`EngineAPI::event_handlers`](crate::api::EngineAPI::event_handlers).
+    /// Declared after `handler` so the handler is dropped first.
+    pub library: Option<LibraryHandle>,
 }
 
 #[derive(Clone, Default)]
 pub struct EngineEventHandlerRegistry {
-    pub event_handlers: HashMap<Identifier, Vec<Arc<dyn EventHandler>>>,
+    pub event_handlers: HashMap<Identifier, Vec<RegisteredHandler>>, // Sorted by priority.
 }
 
 impl EngineEventHandlerRegistry {
+    /// Registers without checking the event type, see [`EventBus::register_handler`].
     pub fn register_handler<H: EventHandler + Send + Sync + 'static>(
         &mut self,
         handler: H,
         identifier: Identifier,
     ) {
-        let handler = Arc::new(handler);
+        self.register_arc(Arc::new(handler), identifier, HandlerOptions::default());
+    }
+
+    pub fn register_handler_with<H: EventHandler + Send + Sync + 'static>(
+        &mut self,
+        handler: H,
+        identifier: Identifier,
+        options: HandlerOptions,
+    ) {
+        self.register_arc(Arc::new(handler), identifier, options);
+    }
+
+    pub fn register_async_handler<H: AsyncEventHandler>(
+        &mut self,
+        handler: H,
+        identifier: Identifier,
+    ) {
+        self.register_async_handler_with(handler, identifier, HandlerOptions::default());
+    }
+
+    pub fn register_async_handler_with<H: AsyncEventHandler>(
+        &mut self,
+        handler: H,
+        identifier: Identifier,
+        options: HandlerOptions,
+    ) {
+        self.register_arc(Arc::new(AsyncAdapter(handler)), identifier, options);
+    }
+
+    /// Inserts after every handler of the same or a lower priority, keeping registration order
+    /// within a priority.
+    fn register_arc(
+        &mut self,
+        handler: Arc<dyn EventHandler>,
+        identifier: Identifier,
+        options: HandlerOptions,
+    ) {
         let handlers = self.event_handlers.entry(identifier.clone()).or_default();
-        handlers.push(handler);
+        let index = handlers.partition_point(|h| h.options.priority <= options.priority);
+        handlers.insert(
+            index,
+            RegisteredHandler {
+                handler,
+                owner: String::new(),
+                options,
+                library: None,
+            },
+        );
         debug!(
-            "EventBus: Registered handler for event {}.{}",
-            identifier.0, identifier.1
+            "EventBus: Registered handler for event {}.{} at {:?}",
+            identifier.0, identifier.1, options.priority
         );
     }
 }
@@ -92,15 +278,121 @@ impl Registry<dyn Event> for EngineEventRegistry {
 }
 
 impl EventBus {
+    /// Registers `handler` for every event whose registered prototype is an `E`.
+    ///
+    /// Fails if no such event is registered yet, returns the identifiers subscribed to.
+    pub fn subscribe<E: Event, F: Fn(&mut E) + Send + Sync + 'static>(
+        &mut self,
+        handler: F,
+    ) -> Result<Vec<Identifier>, String> {
+        self.subscribe_with(HandlerOptions::default(), handler)
+    }
+
+    pub fn subscribe_with<E: Event, F: Fn(&mut E) + Send + Sync + 'static>(
+        &mut self,
+        options: HandlerOptions,
+        handler: F,
+    ) -> Result<Vec<Identifier>, String> {
+        let mut ids: Vec<Identifier> = self
+            .event_registry
+            .events
+            .iter()
+            .filter(|(_, event)| (***event).as_any().is::<E>())
+            .map(|(id, _)| id.clone())
+            .collect();
+        if ids.is_empty() {
+            return Err(format!(
+                "No event of type {} is registered",
+                std::any::type_name::<E>()
+            ));
+        }
+        ids.sort();
+        let handler = Arc::new(TypedHandler {
+            handler,
+            event: PhantomData,
+        });
+        for id in &ids {
+            self.event_handler_registry
+                .register_arc(handler.clone(), id.clone(), options);
+        }
+        Ok(ids)
+    }
+
+    /// Registers `handler` under `identifier`, rejecting it if it expects another type of event
+    /// than the one registered there.
+    pub fn register_handler<H: EventHandler>(
+        &mut self,
+        handler: H,
+        identifier: Identifier,
+    ) -> Result<(), String> {
+        self.register_handler_with(handler, identifier, HandlerOptions::default())
+    }
+
+    pub fn register_handler_with<H: EventHandler>(
+        &mut self,
+        handler: H,
+        identifier: Identifier,
+        options: HandlerOptions,
+    ) -> Result<(), String> {
+        if let (Some(expected), Some(event)) = (
+            handler.event_type(),
+            self.event_registry.events.get(&identifier),
+        ) && (**event).as_any().type_id() != expected
+        {
+            return Err(format!(
+                "Handler does not accept event {}.{} of type {}",
+                identifier.0,
+                identifier.1,
+                event.type_name()
+            ));
+        }
+        self.event_handler_registry
+            .register_handler_with(handler, identifier, options);
+        Ok(())
+    }
+
+    /// Handlers of `id` in the order they run, to await them with [`dispatch_async`] once the
+    /// bus is no longer borrowed.
+    pub fn handlers(&self, id: &Identifier) -> Vec<RegisteredHandler> {
+        self.event_handler_registry
+            .event_handlers
+            .get(id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Writes the event to the [`EventRecorder`], if one is set.
+    pub fn record(&self, id: &Identifier, event: &dyn Event) {
+        if let Some(recorder) = &self.recorder {
+            recorder.record(id, event);
+        }
+    }
+
+    /// Like [`handle`](Self::handle), but also awaits async handlers in their place.
+    pub async fn handle_async<T: Event>(&self, id: Identifier, event: &mut T) -> bool {
+        debug!("EventBus: Processing event {}.{} async", id.0, id.1);
+        self.record(&id, event);
+        dispatch_async(&self.handlers(&id), event).await
+    }
+
+    /// Runs the handlers of `id` by priority, returning whether the event ended up cancelled.
+    ///
+    /// Once the event is cancelled only handlers registered with `receive_cancelled` still run.
+    /// Async handlers are blocked on, prefer [`handle_async`](Self::handle_async) for events they
+    /// hook.
     #[instrument]
-    pub fn handle<T: Event>(&self, id: Identifier, event: &mut T) {
+    pub fn handle<T: Event>(&self, id: Identifier, event: &mut T) -> bool {
         debug!("EventBus: Processing event {}.{}", id.0, id.1);
-        let handlers: Option<&Vec<Arc<dyn EventHandler>>> =
+        self.record(&id, event);
+        let handlers: Option<&Vec<RegisteredHandler>> =
             self.event_handler_registry.event_handlers.get(&id);
 
         if let Some(handlers) = handlers {
-            for handler in handlers {
-                handler.handle(event)
+            for registered in handlers {
+                if event.is_cancelled() && !registered.options.receive_cancelled {
+                    continue;
+                }
+                registered.handler.handle(event)
             }
         } else {
             debug!(
@@ -108,5 +400,21 @@ impl EventBus {
                 id.0, id.1
             );
         }
+        event.is_cancelled()
+    }
+}
+
+/// Runs handlers taken from [`EventBus::handlers`] as [`EventBus::handle_async`] does, so callers
+/// can release the `EngineAPI` lock while handlers await.
+pub async fn dispatch_async(handlers: &[RegisteredHandler], event: &mut dyn Event) -> bool {
+    for registered in handlers {
+        if event.is_cancelled() && !registered.options.receive_cancelled {
+            continue;
+        }
+        match registered.handler.as_async() {
+            Some(handler) => handler.handle(event).await,
+            None => registered.handler.handle(event),
+        }
     }
+    event.is_cancelled()
 }
@@ -5,7 +5,11 @@ use std::{
 
 use sled::Db;
 
-use crate::{Identifier, api::EngineAPI, event::Event};
+use crate::{
+    Identifier,
+    api::EngineAPI,
+    event::{Event, dispatch_async},
+};
 
 use super::{Events, ID};
 
@@ -36,9 +40,10 @@ macro_rules! RegisterAdminAuthEventHandler {
         }
         impl EventHandler for $handler {
             fn handle(&self, event: &mut dyn Event) {
-                let event: &mut AdminAuthEvent =
-                    <Self as EventCTX<AdminAuthEvent>>::get_event::<AdminAuthEvent>(event);
-                self.handleCTX(event);
+                <Self as EventCTX<AdminAuthEvent>>::handle(self, event);
+            }
+            fn event_type(&self) -> Option<std::any::TypeId> {
+                Some(std::any::TypeId::of::<AdminAuthEvent>())
             }
         }
         impl EventCTX<AdminAuthEvent> for $handler {
@@ -56,9 +61,10 @@ macro_rules! RegisterAdminAuthEventHandler {
         pub struct $handler;
         impl EventHandler for $handler {
             fn handle(&self, event: &mut dyn Event) {
-                let event: &mut AdminAuthEvent =
-                    <Self as EventCTX<AdminAuthEvent>>::get_event::<AdminAuthEvent>(event);
-                self.handleCTX(event);
+                <Self as EventCTX<AdminAuthEvent>>::handle(self, event);
+            }
+            fn event_type(&self) -> Option<std::any::TypeId> {
+                Some(std::any::TypeId::of::<AdminAuthEvent>())
             }
         }
         impl EventCTX<AdminAuthEvent> for $handler {
@@ -76,8 +82,33 @@ impl Events {
         db: Db,
     ) -> bool {
         let output = Arc::new(RwLock::new(false));
-        Self::AdminAuthEvent(api, payload, target, db, output.clone());
-        return *output.read().unwrap();
+        let cancelled = Self::AdminAuthEvent(api, payload, target, db, output.clone());
+        !cancelled && *output.read().unwrap()
+    }
+    /// Like [`CheckAdminAuth`](Self::CheckAdminAuth), holding the lock only to collect the
+    /// handlers so async ones can await without blocking the engine.
+    #[allow(non_snake_case)]
+    pub async fn CheckAdminAuthAsync(
+        api: &tokio::sync::RwLock<EngineAPI>,
+        payload: String,
+        target: Identifier,
+    ) -> bool {
+        let output = Arc::new(RwLock::new(false));
+        let (handlers, mut event) = {
+            let api = api.read().await;
+            let event = AdminAuthEvent {
+                db: api.db.clone(),
+                cancelled: false,
+                id: ID("core", "admin_auth_event"),
+                payload,
+                output: output.clone(),
+                target,
+            };
+            api.event_bus.record(&event.id, &event);
+            (api.event_handlers(&event.id), event)
+        };
+        let cancelled = dispatch_async(&handlers, &mut event).await;
+        !cancelled && *output.read().unwrap()
     }
     pub fn AdminAuthEvent(
         api: &mut EngineAPI,
@@ -85,7 +116,7 @@ impl Events {
         target: Identifier,
         db: Db,
         output: Arc<RwLock<bool>>,
-    ) {
+    ) -> bool {
         api.event_bus.handle(
             ID("core", "admin_auth_event"),
             &mut AdminAuthEvent {
@@ -96,7 +127,7 @@ impl Events {
                 output,
                 target,
             },
-        );
+        )
     }
 }
 
@@ -5,7 +5,11 @@ use std::{
 
 use sled::Db;
 
-use crate::{Identifier, api::EngineAPI, event::Event};
+use crate::{
+    Identifier,
+    api::EngineAPI,
+    event::{Event, dispatch_async},
+};
 
 use super::{Events, ID};
 
@@ -28,9 +32,10 @@ macro_rules! RegisterAuthEventHandler {
         pub struct $handler;
         impl EventHandler for $handler {
             fn handle(&self, event: &mut dyn Event) {
-                let event: &mut AuthEvent =
-                    <Self as EventCTX<AuthEvent>>::get_event::<AuthEvent>(event);
-                self.handleCTX(event);
+                <Self as EventCTX<AuthEvent>>::handle(self, event);
+            }
+            fn event_type(&self) -> Option<std::any::TypeId> {
+                Some(std::any::TypeId::of::<AuthEvent>())
             }
         }
         impl EventCTX<AuthEvent> for $handler {
@@ -43,8 +48,33 @@ macro_rules! RegisterAuthEventHandler {
 impl Events {
     pub fn CheckAuth(api: &mut EngineAPI, uid: String, challenge: String, db: Db) -> bool {
         let output = Arc::new(RwLock::new(false));
-        Self::AuthEvent(api, uid, challenge, db, output.clone());
-        return *output.read().unwrap();
+        let cancelled = Self::AuthEvent(api, uid, challenge, db, output.clone());
+        !cancelled && *output.read().unwrap()
+    }
+    /// Like [`CheckAuth`](Self::CheckAuth), holding the lock only to collect the handlers so
+    /// async ones can await without blocking the engine.
+    #[allow(non_snake_case)]
+    pub async fn CheckAuthAsync(
+        api: &tokio::sync::RwLock<EngineAPI>,
+        uid: String,
+        challenge: String,
+    ) -> bool {
+        let output = Arc::new(RwLock::new(false));
+        let (handlers, mut event) = {
+            let api = api.read().await;
+            let event = AuthEvent {
+                db: api.db.clone(),
+                cancelled: false,
+                id: ID("core", "auth_event"),
+                uid,
+                challenge,
+                output: output.clone(),
+            };
+            api.event_bus.record(&event.id, &event);
+            (api.event_handlers(&event.id), event)
+        };
+        let cancelled = dispatch_async(&handlers, &mut event).await;
+        !cancelled && *output.read().unwrap()
     }
     pub fn AuthEvent(
         api: &mut EngineAPI,
@@ -52,7 +82,7 @@ impl Events {
         challenge: String,
         db: Db,
         output: Arc<RwLock<bool>>,
-    ) {
+    ) -> bool {
         api.event_bus.handle(
             ID("core", "auth_event"),
             &mut AuthEvent {
@@ -63,7 +93,7 @@ impl Events {
                 challenge,
                 output,
             },
-        );
+        )
     }
 }
 
@@ -1,12 +1,14 @@
 use auth_event::AuthEvent;
 
 use crate::api::{self, EngineAPI};
+use crate::event::{Event, dispatch_async};
 use crate::{Identifier, RegisterAdminAuthEventHandler, RegisterAuthEventHandler};
-use std::sync::{Arc, Mutex, RwLock};
+use std::sync::{Arc, Mutex};
 pub mod admin_auth_event;
 pub mod auth_event;
-pub mod cgrpc_event;
+pub mod shutdown_event;
 pub mod start_event;
+pub mod task_event;
 pub struct Events {}
 pub fn ID(namespace: &str, id: &str) -> Identifier {
     (namespace.to_string(), id.to_string())
@@ -48,6 +50,17 @@ impl Events {
                 .register_handler(AdminAuthHandler, ID("core", "admin_auth_event"));
         }
     }
+    /// Fires `event` through its handlers, holding the lock only to collect them so async
+    /// handlers can await without blocking the engine. Returns whether it was cancelled.
+    pub async fn dispatch<E: Event>(api: &tokio::sync::RwLock<EngineAPI>, event: &mut E) -> bool {
+        let handlers = {
+            let api = api.read().await;
+            let id = event.get_id();
+            api.event_bus.record(&id, event);
+            api.event_handlers(&id)
+        };
+        dispatch_async(&handlers, event).await
+    }
     pub fn init(api: &mut EngineAPI) {
         for (id, tsk) in api.task_registry.tasks.iter() {
             api.task_queue.tasks.entry(id.clone()).or_default();
@@ -55,28 +68,27 @@ impl Events {
             api.solved_tasks.tasks.entry(id.clone()).or_default();
         }
 
+        //Register Events to the Default impl for less boilerplate
         crate::register_event!(
             api,
             core,
-            cgrpc_event,
-            crate::events::cgrpc_event::CgrpcEvent {
+            start_event,
+            crate::events::start_event::StartEvent {
+                modules: vec![],
                 cancelled: false,
-                handler_id: ("".to_string(), "".to_string()),
-                id: ("core".to_string(), "cgrpc_event".to_string()),
-                payload: Vec::new(),
-                output: Arc::new(RwLock::new(Vec::new()))
+                id: ("core".to_string(), "start_event".to_string())
             }
         );
-        //Register Events to the Default impl for less boilerplate
         crate::register_event!(
             api,
             core,
-            start_event,
-            crate::events::start_event::StartEvent {
-                modules: vec![],
+            shutdown_event,
+            crate::events::shutdown_event::ShutdownEvent {
                 cancelled: false,
-                id: ("core".to_string(), "start_event".to_string())
+                id: ("core".to_string(), "shutdown_event".to_string()),
+                requeued: 0
             }
         );
+        task_event::register_events(api);
     }
 }
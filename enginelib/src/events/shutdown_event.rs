@@ -0,0 +1,59 @@
+use std::any::Any;
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{Identifier, api::EngineAPI, event::Event};
+
+use super::{Events, ID};
+
+/// Fired once the server stopped serving and the database is synced, before the mods are
+/// unloaded. Handlers should release whatever they hold, the shutdown can't be cancelled.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShutdownEvent {
+    pub cancelled: bool,
+    pub id: Identifier,
+    /// Leased tasks moved back to the queue, see `requeue_on_shutdown`.
+    pub requeued: usize,
+}
+
+impl Events {
+    #[allow(non_snake_case)]
+    pub async fn ShutdownEvent(api: &tokio::sync::RwLock<EngineAPI>, requeued: usize) {
+        Self::dispatch(
+            api,
+            &mut ShutdownEvent {
+                cancelled: false,
+                id: ID("core", "shutdown_event"),
+                requeued,
+            },
+        )
+        .await;
+    }
+}
+
+impl Event for ShutdownEvent {
+    fn clone_box(&self) -> Box<dyn Event> {
+        Box::new(self.clone())
+    }
+
+    fn cancel(&mut self) {
+        warn!("ShutdownEvent: cancelling is ignored, the engine is shutting down");
+    }
+    fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+    fn get_id(&self) -> Identifier {
+        self.id.clone()
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        serde_json::to_value(self).ok()
+    }
+}
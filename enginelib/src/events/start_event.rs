@@ -1,4 +1,4 @@
-use std::{any::Any, process, sync::Arc};
+use std::{any::Any, sync::Arc};
 
 use tracing::info;
 
@@ -19,7 +19,8 @@ pub struct StartEvent {
 }
 
 impl Events {
-    pub fn StartEvent(api: &mut EngineAPI) {
+    /// Returns whether a handler cancelled the start, in which case the engine should stop.
+    pub fn StartEvent(api: &mut EngineAPI) -> bool {
         let lib_manager = api.lib_manager.clone();
         info!("Started on {}", api.cfg.config_toml.host);
         api.event_bus.handle(
@@ -34,7 +35,7 @@ impl Events {
                     .map(|lib| lib.metadata)
                     .collect(),
             },
-        );
+        )
     }
 }
 
@@ -45,7 +46,6 @@ impl Event for StartEvent {
 
     fn cancel(&mut self) {
         self.cancelled = true;
-        process::exit(0)
     }
     fn is_cancelled(&self) -> bool {
         self.cancelled
@@ -0,0 +1,212 @@
+use std::{any::Any, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{Identifier, api::EngineAPI, event::Event};
+
+use super::ID;
+
+/// Declares a task lifecycle event. Pre-events (`cancellable`) are fired before the engine
+/// commits the change and may cancel it or rewrite the payload, post-events are notifications
+/// fired after the change is persisted.
+macro_rules! task_event {
+    ($(#[$meta:meta])* $name:ident, $event_id:ident, $cancellable:literal) => {
+        $(#[$meta])*
+        #[derive(Clone, Debug, Serialize, Deserialize)]
+        pub struct $name {
+            pub cancelled: bool,
+            pub id: Identifier,
+            /// The task type, `(namespace, task)`.
+            pub task: Identifier,
+            pub task_id: String,
+            pub uid: String,
+            pub payload: Vec<u8>,
+        }
+
+        impl $name {
+            pub fn new(task: Identifier, task_id: String, uid: String, payload: Vec<u8>) -> Self {
+                Self {
+                    cancelled: false,
+                    id: ID("core", stringify!($event_id)),
+                    task,
+                    task_id,
+                    uid,
+                    payload,
+                }
+            }
+        }
+
+        impl Event for $name {
+            fn clone_box(&self) -> Box<dyn Event> {
+                Box::new(self.clone())
+            }
+
+            fn cancel(&mut self) {
+                if $cancellable {
+                    self.cancelled = true;
+                } else {
+                    warn!(
+                        "{}: cancelling is ignored, the change is already committed",
+                        stringify!($name)
+                    );
+                }
+            }
+            fn is_cancelled(&self) -> bool {
+                self.cancelled
+            }
+            fn get_id(&self) -> Identifier {
+                self.id.clone()
+            }
+            fn as_any(&self) -> &dyn Any {
+                self
+            }
+
+            fn as_any_mut(&mut self) -> &mut dyn Any {
+                self
+            }
+            fn snapshot(&self) -> Option<serde_json::Value> {
+                serde_json::to_value(self).ok()
+            }
+        }
+    };
+}
+
+task_event!(
+    /// Fired before a task is queued. Cancelling rejects the request, the payload may be
+    /// rewritten before it is verified and stored.
+    TaskCreateEvent,
+    task_create,
+    true
+);
+task_event!(
+    /// Fired after a task is queued.
+    TaskCreatedEvent,
+    task_created,
+    false
+);
+task_event!(
+    /// Fired when a worker leases a task. Cancelling puts the task back on the queue. The
+    /// payload may be rewritten, which only changes what the worker receives: the lease keeps
+    /// the stored payload, so a task requeued after its lease expires is rewritten afresh.
+    TaskAcquireEvent,
+    task_acquire,
+    true
+);
+task_event!(
+    /// Fired after a worker received a leased task.
+    TaskAcquiredEvent,
+    task_acquired,
+    false
+);
+task_event!(
+    /// Fired before a result is published. Cancelling rejects the request, the result may be
+    /// rewritten before it is verified and stored.
+    TaskPublishEvent,
+    task_publish,
+    true
+);
+task_event!(
+    /// Fired after a result is stored as solved.
+    TaskPublishedEvent,
+    task_published,
+    false
+);
+task_event!(
+    /// Fired before a task is deleted. Cancelling rejects the request.
+    TaskDeleteEvent,
+    task_delete,
+    true
+);
+task_event!(
+    /// Fired after a task is deleted.
+    TaskDeletedEvent,
+    task_deleted,
+    false
+);
+task_event!(
+    /// Fired after an expired lease is dropped from the executing tasks.
+    TaskLeaseExpiredEvent,
+    task_lease_expired,
+    false
+);
+
+/// Registers the task event prototypes so handlers can [`subscribe`](crate::event::EventBus::subscribe) to them.
+pub(crate) fn register_events(api: &mut EngineAPI) {
+    macro_rules! register {
+        ($($event:ident => $name:ident),*) => {$(
+            crate::register_event!(
+                api,
+                core,
+                $name,
+                $event::new(ID("", ""), String::new(), String::new(), Vec::new())
+            );
+        )*};
+    }
+    register!(
+        TaskCreateEvent => task_create,
+        TaskCreatedEvent => task_created,
+        TaskAcquireEvent => task_acquire,
+        TaskAcquiredEvent => task_acquired,
+        TaskPublishEvent => task_publish,
+        TaskPublishedEvent => task_published,
+        TaskDeleteEvent => task_delete,
+        TaskDeletedEvent => task_deleted,
+        TaskLeaseExpiredEvent => task_lease_expired
+    );
+}
+
+#[macro_export]
+macro_rules! RegisterTaskCreateEventHandler {
+    ($handler:ident, $($args:tt)*) => {
+        $crate::RegisterEventHandler!($handler, $crate::events::task_event::TaskCreateEvent, $($args)*);
+    };
+}
+#[macro_export]
+macro_rules! RegisterTaskCreatedEventHandler {
+    ($handler:ident, $($args:tt)*) => {
+        $crate::RegisterEventHandler!($handler, $crate::events::task_event::TaskCreatedEvent, $($args)*);
+    };
+}
+#[macro_export]
+macro_rules! RegisterTaskAcquireEventHandler {
+    ($handler:ident, $($args:tt)*) => {
+        $crate::RegisterEventHandler!($handler, $crate::events::task_event::TaskAcquireEvent, $($args)*);
+    };
+}
+#[macro_export]
+macro_rules! RegisterTaskAcquiredEventHandler {
+    ($handler:ident, $($args:tt)*) => {
+        $crate::RegisterEventHandler!($handler, $crate::events::task_event::TaskAcquiredEvent, $($args)*);
+    };
+}
+#[macro_export]
+macro_rules! RegisterTaskPublishEventHandler {
+    ($handler:ident, $($args:tt)*) => {
+        $crate::RegisterEventHandler!($handler, $crate::events::task_event::TaskPublishEvent, $($args)*);
+    };
+}
+#[macro_export]
+macro_rules! RegisterTaskPublishedEventHandler {
+    ($handler:ident, $($args:tt)*) => {
+        $crate::RegisterEventHandler!($handler, $crate::events::task_event::TaskPublishedEvent, $($args)*);
+    };
+}
+#[macro_export]
+macro_rules! RegisterTaskDeleteEventHandler {
+    ($handler:ident, $($args:tt)*) => {
+        $crate::RegisterEventHandler!($handler, $crate::events::task_event::TaskDeleteEvent, $($args)*);
+    };
+}
+#[macro_export]
+macro_rules! RegisterTaskDeletedEventHandler {
+    ($handler:ident, $($args:tt)*) => {
+        $crate::RegisterEventHandler!($handler, $crate::events::task_event::TaskDeletedEvent, $($args)*);
+    };
+}
+#[macro_export]
+macro_rules! RegisterTaskLeaseExpiredEventHandler {
+    ($handler:ident, $($args:tt)*) => {
+        $crate::RegisterEventHandler!($handler, $crate::events::task_event::TaskLeaseExpiredEvent, $($args)*);
+    };
+}
@@ -1,13 +1,24 @@
 use std::sync::Arc;
+pub mod abi;
 pub mod api;
+pub mod audit;
+pub mod cabi;
+pub mod cgrpc;
 pub mod config;
 pub mod event;
 pub mod events;
+pub mod limits;
+pub mod package;
 #[macro_use]
 pub mod macros;
 pub mod plugin;
 pub mod prelude;
+pub mod replay;
+pub mod scheduler;
+pub mod service;
 pub mod task;
+pub mod tenant;
+pub mod wasm;
 pub type Identifier = (String, String);
 pub type RawIdentier = String;
 pub const GIT_VERSION: &str = env!("CARGO_PKG_VERSION"); //get commit hash
@@ -17,3 +28,4 @@ pub trait Registry<T: ?Sized>: Default + Clone {
     fn get(&self, identifier: &Identifier) -> Option<Box<T>>;
 }
 pub use chrono;
+pub use semver;
@@ -0,0 +1,116 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::task::{ExecutingTaskQueue, TaskQueue};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub per_second: f64,
+    pub burst: u32,
+}
+
+/// Rate limits and quotas, either global (`[limits]`) or for a single uid (`[uid_limits.<uid>]`).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LimitsConfig {
+    #[serde(default)]
+    pub rate_limits: HashMap<String, RateLimit>, // Keyed by RPC name, e.g. "CreateTask".
+    #[serde(default)]
+    pub max_queued: Option<usize>, // Max tasks a uid may have waiting in the queue.
+    #[serde(default)]
+    pub max_leased: Option<usize>, // Max tasks a uid may be executing at once.
+}
+
+impl LimitsConfig {
+    /// Rejects rate limits that could never refill or would never let a call through.
+    pub fn validate(&self) -> Result<(), String> {
+        for (rpc, limit) in &self.rate_limits {
+            if !(limit.per_second > 0.0 && limit.per_second.is_finite()) {
+                return Err(format!(
+                    "rate limit of {}: per_second must be a positive number",
+                    rpc
+                ));
+            }
+            if limit.burst == 0 {
+                return Err(format!("rate limit of {}: burst must be at least 1", rpc));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns these limits with every value set in `overrides` taking precedence.
+    pub fn merged(&self, overrides: &LimitsConfig) -> LimitsConfig {
+        let mut limits = self.clone();
+        limits.rate_limits.extend(overrides.rate_limits.clone());
+        limits.max_queued = overrides.max_queued.or(limits.max_queued);
+        limits.max_leased = overrides.max_leased.or(limits.max_leased);
+        limits
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+/// Token bucket rate limiter keyed by caller and RPC.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    buckets: Mutex<HashMap<(String, String), Bucket>>,
+}
+
+impl RateLimiter {
+    /// Takes a token for `key`, a uid or peer address, calling `rpc`, returning how long to wait
+    /// if none are left.
+    pub fn check(&self, key: &str, rpc: &str, limit: Option<&RateLimit>) -> Result<(), Duration> {
+        let Some(limit) = limit else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets
+            .entry((key.to_string(), rpc.to_string()))
+            .or_insert(Bucket {
+                tokens: limit.burst as f64,
+                last: now,
+            });
+        let elapsed = now.duration_since(bucket.last).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * limit.per_second).min(limit.burst as f64);
+        bucket.last = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        debug!("RateLimiter: {} exceeded the rate limit for {}", key, rpc);
+        Err(
+            Duration::try_from_secs_f64((1.0 - bucket.tokens) / limit.per_second)
+                .unwrap_or(Duration::MAX),
+        )
+    }
+}
+
+/// Number of queued tasks created by `uid` across every task type.
+pub fn queued_by(queue: &TaskQueue, uid: &str) -> usize {
+    queue
+        .tasks
+        .values()
+        .flatten()
+        .filter(|t| t.created_by == uid)
+        .count()
+}
+
+/// Number of tasks currently leased by `uid` across every task type.
+pub fn leased_by(queue: &ExecutingTaskQueue, uid: &str) -> usize {
+    queue
+        .tasks
+        .values()
+        .flatten()
+        .filter(|t| t.user_id == uid)
+        .count()
+}
@@ -23,8 +23,10 @@ macro_rules! RegisterEventHandler {
         }
         impl EventHandler for $handler {
             fn handle(&self, event: &mut dyn Event) {
-                let event: &mut $event = <Self as EventCTX<$event>>::get_event::<$event>(event);
-                self.handleCTX(event);
+                <Self as EventCTX<$event>>::handle(self, event);
+            }
+            fn event_type(&self) -> Option<std::any::TypeId> {
+                Some(std::any::TypeId::of::<$event>())
             }
         }
         impl EventCTX<$event> for $handler {
@@ -38,8 +40,10 @@ macro_rules! RegisterEventHandler {
         pub struct $handler;
         impl EventHandler for $handler {
             fn handle(&self, event: &mut dyn Event) {
-                let event: &mut $event = <Self as EventCTX<$event>>::get_event::<$event>(event);
-                self.handleCTX(event);
+                <Self as EventCTX<$event>>::handle(self, event);
+            }
+            fn event_type(&self) -> Option<std::any::TypeId> {
+                Some(std::any::TypeId::of::<$event>())
             }
         }
         impl EventCTX<$event> for $handler {
@@ -49,3 +53,52 @@ macro_rules! RegisterEventHandler {
         }
     };
 }
+
+#[macro_export]
+macro_rules! RegisterAsyncEventHandler {
+    ($handler:ident,$event:ty,$mod_ctx:ty, |$arg:ident, $ctx:ident| $body:block) => {
+        use std::sync::Arc;
+        pub struct $handler {
+            mod_ctx: Arc<$mod_ctx>,
+        };
+        impl $handler {
+            pub fn new(mod_ctx: Arc<$mod_ctx>) -> Self {
+                Self { mod_ctx }
+            }
+        }
+        impl $crate::event::AsyncEventHandler for $handler {
+            fn handle<'a>(
+                &'a self,
+                event: &'a mut dyn $crate::event::Event,
+            ) -> $crate::event::HandlerFuture<'a> {
+                Box::pin(async move {
+                    let $ctx: &Arc<$mod_ctx> = &self.mod_ctx;
+                    if let Some($arg) = $crate::event::downcast_event::<$event>(event) {
+                        $body
+                    }
+                })
+            }
+            fn event_type(&self) -> Option<std::any::TypeId> {
+                Some(std::any::TypeId::of::<$event>())
+            }
+        }
+    };
+    ($handler:ident,$event:ty, |$arg:ident| $body:block) => {
+        pub struct $handler;
+        impl $crate::event::AsyncEventHandler for $handler {
+            fn handle<'a>(
+                &'a self,
+                event: &'a mut dyn $crate::event::Event,
+            ) -> $crate::event::HandlerFuture<'a> {
+                Box::pin(async move {
+                    if let Some($arg) = $crate::event::downcast_event::<$event>(event) {
+                        $body
+                    }
+                })
+            }
+            fn event_type(&self) -> Option<std::any::TypeId> {
+                Some(std::any::TypeId::of::<$event>())
+            }
+        }
+    };
+}
@@ -0,0 +1,259 @@
+//! Signed `*.rustforge.tar` mod packages.
+//!
+//! A package holds a [`PACKAGE_MANIFEST`] listing the mod's metadata and the SHA-256 of every
+//! other file, and a [`PACKAGE_SIGNATURE`] with an Ed25519 signature over the manifest's bytes.
+//! The loader checks both against `trusted_keys` before a package is extracted.
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::Read,
+    path::{Component, Path},
+};
+
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::{config::ConfigTomlServer, plugin::LibraryMetadata};
+
+pub const PACKAGE_MANIFEST: &str = "manifest.toml";
+pub const PACKAGE_SIGNATURE: &str = "manifest.sig";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageManifest {
+    pub metadata: LibraryMetadata,
+    pub files: BTreeMap<String, String>, // Path inside the package -> hex SHA-256.
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageSignature {
+    pub public_key: String, // Hex Ed25519 public key.
+    pub signature: String,  // Hex Ed25519 signature over the manifest file.
+}
+
+impl PackageManifest {
+    /// Checks the files extracted to `dir` still match the verified hashes.
+    pub fn check_files(&self, dir: &Path) -> Result<(), String> {
+        for (name, hash) in &self.files {
+            let bytes = fs::read(dir.join(name))
+                .map_err(|e| format!("Failed to read extracted {}: {}", name, e))?;
+            if sha256_hex(&bytes) != *hash {
+                return Err(format!("{} changed after verification", name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the manifest describes the mod that was actually found in the package.
+    pub fn check_metadata(&self, metadata: &LibraryMetadata) -> Result<(), String> {
+        if self.metadata.mod_id != metadata.mod_id
+            || self.metadata.mod_version != metadata.mod_version
+        {
+            return Err(format!(
+                "Manifest describes {} {} but the package contains {} {}",
+                self.metadata.mod_id,
+                self.metadata.mod_version,
+                metadata.mod_id,
+                metadata.mod_version
+            ));
+        }
+        Ok(())
+    }
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+/// Builds a package from in-memory files, signing it if a key is given.
+pub struct PackageBuilder {
+    metadata: LibraryMetadata,
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+impl PackageBuilder {
+    pub fn new(metadata: LibraryMetadata) -> Self {
+        Self {
+            metadata,
+            files: BTreeMap::new(),
+        }
+    }
+
+    pub fn file(mut self, name: impl Into<String>, bytes: Vec<u8>) -> Self {
+        self.files.insert(name.into(), bytes);
+        self
+    }
+
+    pub fn build(self, key: Option<&SigningKey>) -> Result<Vec<u8>, String> {
+        let manifest = PackageManifest {
+            metadata: self.metadata,
+            files: self
+                .files
+                .iter()
+                .map(|(name, bytes)| (name.clone(), sha256_hex(bytes)))
+                .collect(),
+        };
+        let manifest = toml::to_string(&manifest).map_err(|e| e.to_string())?;
+        let mut entries = vec![(PACKAGE_MANIFEST.to_string(), manifest.clone().into_bytes())];
+        if let Some(key) = key {
+            let signature = PackageSignature {
+                public_key: hex::encode(key.verifying_key().as_bytes()),
+                signature: hex::encode(key.sign(manifest.as_bytes()).to_bytes()),
+            };
+            let signature = toml::to_string(&signature).map_err(|e| e.to_string())?;
+            entries.push((PACKAGE_SIGNATURE.to_string(), signature.into_bytes()));
+        }
+        entries.extend(self.files);
+
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, bytes) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(bytes.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, &name, bytes.as_slice())
+                .map_err(|e| format!("Failed to add {}: {}", name, e))?;
+        }
+        builder.into_inner().map_err(|e| e.to_string())
+    }
+}
+
+/// Verifies a package's manifest, file hashes and signature before anything is extracted.
+///
+/// Returns `None` for packages without a manifest, which are only accepted with
+/// `allow_unsigned_mods`. A manifest that does not match the files or a broken signature is
+/// always rejected, an unsigned or untrusted one unless `allow_unsigned_mods` is set.
+pub fn verify_package(
+    bytes: &[u8],
+    cfg: &ConfigTomlServer,
+) -> Result<Option<PackageManifest>, String> {
+    let mut manifest = None;
+    let mut signature = None;
+    let mut hashes = BTreeMap::new();
+    let mut archive = tar::Archive::new(bytes);
+    for entry in archive.entries().map_err(|e| e.to_string())? {
+        let mut entry = entry.map_err(|e| e.to_string())?;
+        let path = entry.path().map_err(|e| e.to_string())?.into_owned();
+        let kind = entry.header().entry_type();
+        if kind.is_dir() {
+            continue;
+        }
+        let mut parts = Vec::new();
+        for component in path.components() {
+            match component {
+                Component::Normal(part) => parts.push(part.to_string_lossy().into_owned()),
+                Component::CurDir => {}
+                _ => return Err(format!("Entry {} escapes the package", path.display())),
+            }
+        }
+        let name = parts.join("/");
+        if !kind.is_file() {
+            return Err(format!("Entry {} is not a regular file", name));
+        }
+        let mut data = Vec::new();
+        entry
+            .read_to_end(&mut data)
+            .map_err(|e| format!("Failed to read {}: {}", name, e))?;
+        match name.as_str() {
+            PACKAGE_MANIFEST => manifest = Some(data),
+            PACKAGE_SIGNATURE => signature = Some(data),
+            _ => {
+                hashes.insert(name, sha256_hex(&data));
+            }
+        }
+    }
+
+    let Some(manifest_bytes) = manifest else {
+        if cfg.allow_unsigned_mods {
+            warn!("Loading package without a manifest");
+            return Ok(None);
+        }
+        return Err("Package has no manifest".into());
+    };
+    let parsed: PackageManifest = std::str::from_utf8(&manifest_bytes)
+        .map_err(|e| e.to_string())
+        .and_then(|manifest| toml::from_str(manifest).map_err(|e| e.to_string()))
+        .map_err(|e| format!("Invalid {}: {}", PACKAGE_MANIFEST, e))?;
+    for (name, hash) in &parsed.files {
+        match hashes.get(name) {
+            Some(actual) if actual == hash => {}
+            Some(_) => return Err(format!("Hash of {} does not match the manifest", name)),
+            None => return Err(format!("{} is listed in the manifest but missing", name)),
+        }
+    }
+    if let Some(name) = hashes.keys().find(|name| !parsed.files.contains_key(*name)) {
+        return Err(format!("{} is not listed in the manifest", name));
+    }
+
+    let Some(signature) = signature else {
+        if cfg.allow_unsigned_mods {
+            warn!("Loading unsigned package {}", parsed.metadata.mod_id);
+            return Ok(Some(parsed));
+        }
+        return Err("Package is not signed".into());
+    };
+    let signature: PackageSignature = std::str::from_utf8(&signature)
+        .map_err(|e| e.to_string())
+        .and_then(|signature| toml::from_str(signature).map_err(|e| e.to_string()))
+        .map_err(|e| format!("Invalid {}: {}", PACKAGE_SIGNATURE, e))?;
+    let key = parse_public_key(&signature.public_key)?;
+    let sig: [u8; 64] = hex::decode(&signature.signature)
+        .ok()
+        .and_then(|sig| sig.try_into().ok())
+        .ok_or("Invalid signature encoding")?;
+    key.verify_strict(&manifest_bytes, &Signature::from_bytes(&sig))
+        .map_err(|_| "Invalid package signature".to_string())?;
+    let trusted = cfg
+        .trusted_keys
+        .iter()
+        .any(|trusted| trusted.eq_ignore_ascii_case(&signature.public_key));
+    if !trusted {
+        if cfg.allow_unsigned_mods {
+            warn!(
+                "Loading package {} signed by untrusted key {}",
+                parsed.metadata.mod_id, signature.public_key
+            );
+            return Ok(Some(parsed));
+        }
+        return Err(format!(
+            "Package is signed by untrusted key {}",
+            signature.public_key
+        ));
+    }
+    Ok(Some(parsed))
+}
+
+pub fn parse_public_key(key: &str) -> Result<VerifyingKey, String> {
+    let bytes: [u8; 32] = hex::decode(key)
+        .ok()
+        .and_then(|key| key.try_into().ok())
+        .ok_or_else(|| format!("Invalid public key {}", key))?;
+    VerifyingKey::from_bytes(&bytes).map_err(|e| format!("Invalid public key {}: {}", key, e))
+}
+
+pub fn public_key_hex(key: &SigningKey) -> String {
+    hex::encode(key.verifying_key().as_bytes())
+}
+
+pub fn generate_signing_key() -> Result<SigningKey, String> {
+    let mut seed = [0; 32];
+    getrandom::getrandom(&mut seed).map_err(|e| e.to_string())?;
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+/// Reads a signing key stored as hex, as written by `packer keygen`.
+pub fn read_signing_key(path: &Path) -> Result<SigningKey, String> {
+    let key = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read key {}: {}", path.display(), e))?;
+    let bytes: [u8; 32] = hex::decode(key.trim())
+        .ok()
+        .and_then(|key| key.try_into().ok())
+        .ok_or_else(|| format!("Invalid signing key in {}", path.display()))?;
+    Ok(SigningKey::from_bytes(&bytes))
+}
+
+pub fn signing_key_hex(key: &SigningKey) -> String {
+    hex::encode(key.to_bytes())
+}
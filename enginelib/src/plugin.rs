@@ -1,17 +1,46 @@
-use crate::api::EngineAPI;
+use crate::{
+    Identifier,
+    abi::{AbiVersion, HANDSHAKE_SYMBOL, ModHandshake},
+    api::EngineAPI,
+    cabi::{CABI_SYMBOL, CPluginDescriptor},
+    cgrpc::CgrpcHandler,
+    config::ConfigTomlServer,
+    event::{Event, EventHandler, RegisteredHandler},
+    package::verify_package,
+    service::RegisteredService,
+    task::Task,
+    wasm::{WASM_MANIFEST, WasmManager, WasmPackage},
+};
 use libloading::{Library, Symbol};
 use oxifs::OxiFS;
+use semver::{Version, VersionReq};
 use serde::{Deserialize, Serialize};
 use std::mem::ManuallyDrop;
 use std::sync::Arc;
-use std::{collections::HashMap, fs};
-use tracing::{debug, error, info};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use tracing::{debug, error, info, warn};
+
+/// File name of the native library inside a `*.rustforge.tar` package.
+#[cfg(unix)]
+pub const MOD_LIBRARY: &str = "mod.so";
+#[cfg(windows)]
+pub const MOD_LIBRARY: &str = "mod.dll";
+
+/// A loaded native library, closed by [`LibraryManager`] once no handle to it is left.
+pub type LibraryHandle = Arc<ManuallyDrop<Library>>;
+
 #[derive(Clone, Debug)]
 pub struct LibraryInstance {
-    dynamic_library: Arc<ManuallyDrop<Library>>,
+    dynamic_library: LibraryHandle,
     pub metadata: Arc<LibraryMetadata>,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct LibraryMetadata {
     pub mod_id: String,
     pub mod_author: String,
@@ -27,10 +56,12 @@ pub struct LibraryMetadata {
     pub mod_issue_tracker: String,
 }
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct LibraryDependency {
     pub mod_git_repo: String,
     pub mod_git_commit: String,
     pub mod_id: String,
+    pub version_req: String, // Semver requirement on the dependency's mod_version, empty accepts any.
 }
 impl Default for LibraryMetadata {
     fn default() -> Self {
@@ -53,6 +84,355 @@ impl Default for LibraryMetadata {
 #[derive(Default, Clone)]
 pub struct LibraryManager {
     pub libraries: HashMap<String, LibraryInstance>,
+    pub wasm: WasmManager,
+    /// What every loaded mod registered, removed again when it is unloaded.
+    pub registrations: HashMap<String, ModRegistrations>,
+    /// Where every loaded mod was loaded from, used to reload it.
+    pub sources: HashMap<String, PathBuf>,
+    /// Task types that are not handed out while their mod is being unloaded.
+    pub draining: HashSet<Identifier>,
+}
+
+/// Registry entries a mod added while it ran.
+#[derive(Clone, Default)]
+pub struct ModRegistrations {
+    pub tasks: Vec<(Identifier, Arc<dyn Task>)>,
+    pub events: Vec<(Identifier, Arc<dyn Event>)>,
+    pub handlers: Vec<(Identifier, Arc<dyn EventHandler>)>,
+    pub cgrpc_handlers: Vec<(Identifier, Arc<dyn CgrpcHandler>)>,
+    pub services: Vec<(Identifier, RegisteredService)>,
+}
+
+/// Registry contents captured before a mod runs, see [`ModRegistrations::since`].
+pub struct RegistrySnapshot {
+    tasks: HashMap<Identifier, Arc<dyn Task>>,
+    events: HashMap<Identifier, Arc<dyn Event>>,
+    handlers: HashMap<Identifier, Vec<RegisteredHandler>>,
+    cgrpc_handlers: HashMap<Identifier, Arc<dyn CgrpcHandler>>,
+    services: HashMap<Identifier, RegisteredService>,
+}
+
+impl RegistrySnapshot {
+    pub fn capture(api: &EngineAPI) -> Self {
+        Self {
+            tasks: api.task_registry.tasks.clone(),
+            events: api.event_bus.event_registry.events.clone(),
+            handlers: api.event_bus.event_handler_registry.event_handlers.clone(),
+            cgrpc_handlers: api.cgrpc_registry.handlers.clone(),
+            services: api.service_registry.services.clone(),
+        }
+    }
+}
+
+impl ModRegistrations {
+    /// Entries added or replaced since `before` was captured.
+    pub fn since(before: &RegistrySnapshot, api: &EngineAPI) -> Self {
+        let tasks = api
+            .task_registry
+            .tasks
+            .iter()
+            .filter(|(id, task)| {
+                !before
+                    .tasks
+                    .get(*id)
+                    .is_some_and(|old| Arc::ptr_eq(old, task))
+            })
+            .map(|(id, task)| (id.clone(), task.clone()))
+            .collect();
+        let events = api
+            .event_bus
+            .event_registry
+            .events
+            .iter()
+            .filter(|(id, event)| {
+                !before
+                    .events
+                    .get(*id)
+                    .is_some_and(|old| Arc::ptr_eq(old, event))
+            })
+            .map(|(id, event)| (id.clone(), event.clone()))
+            .collect();
+        let mut handlers = Vec::new();
+        for (id, list) in &api.event_bus.event_handler_registry.event_handlers {
+            let old = before.handlers.get(id);
+            for registered in list {
+                if !old.is_some_and(|old| {
+                    old.iter()
+                        .any(|old| Arc::ptr_eq(&old.handler, &registered.handler))
+                }) {
+                    handlers.push((id.clone(), registered.handler.clone()));
+                }
+            }
+        }
+        let cgrpc_handlers = api
+            .cgrpc_registry
+            .handlers
+            .iter()
+            .filter(|(id, handler)| {
+                !before
+                    .cgrpc_handlers
+                    .get(*id)
+                    .is_some_and(|old| Arc::ptr_eq(old, handler))
+            })
+            .map(|(id, handler)| (id.clone(), handler.clone()))
+            .collect();
+        let services = api
+            .service_registry
+            .services
+            .iter()
+            .filter(|(id, registered)| {
+                !before
+                    .services
+                    .get(*id)
+                    .is_some_and(|old| Arc::ptr_eq(&old.service, &registered.service))
+            })
+            .map(|(id, registered)| (id.clone(), registered.clone()))
+            .collect();
+        Self {
+            tasks,
+            events,
+            handlers,
+            cgrpc_handlers,
+            services,
+        }
+    }
+
+    pub fn task_types(&self) -> Vec<Identifier> {
+        self.tasks.iter().map(|(id, _)| id.clone()).collect()
+    }
+
+    /// Removes the entries from the registries, unless another mod replaced them since.
+    pub fn remove(&self, api: &mut EngineAPI) {
+        for (id, task) in &self.tasks {
+            if api
+                .task_registry
+                .tasks
+                .get(id)
+                .is_some_and(|current| Arc::ptr_eq(current, task))
+            {
+                debug!("TaskRegistry: Deregistering task {}.{}", id.0, id.1);
+                api.task_registry.tasks.remove(id);
+                api.task_registry.owners.remove(id);
+            }
+        }
+        let registry = &mut api.event_bus.event_registry;
+        for (id, event) in &self.events {
+            if registry
+                .events
+                .get(id)
+                .is_some_and(|current| Arc::ptr_eq(current, event))
+            {
+                debug!("EventBus: Deregistering event {}.{}", id.0, id.1);
+                registry.events.remove(id);
+                registry.owners.remove(id);
+            }
+        }
+        let event_handlers = &mut api.event_bus.event_handler_registry.event_handlers;
+        for (id, handler) in &self.handlers {
+            if let Some(list) = event_handlers.get_mut(id) {
+                list.retain(|current| !Arc::ptr_eq(&current.handler, handler));
+                if list.is_empty() {
+                    event_handlers.remove(id);
+                }
+                debug!("EventBus: Deregistered handler for event {}.{}", id.0, id.1);
+            }
+        }
+        let registry = &mut api.cgrpc_registry;
+        for (id, handler) in &self.cgrpc_handlers {
+            if registry
+                .handlers
+                .get(id)
+                .is_some_and(|current| Arc::ptr_eq(current, handler))
+            {
+                debug!("CgrpcRegistry: Deregistering handler {}.{}", id.0, id.1);
+                registry.handlers.remove(id);
+                registry.owners.remove(id);
+            }
+        }
+        let registry = &mut api.service_registry;
+        for (id, registered) in &self.services {
+            if registry
+                .services
+                .get(id)
+                .is_some_and(|current| Arc::ptr_eq(&current.service, &registered.service))
+            {
+                debug!("ServiceRegistry: Deregistering service {}.{}", id.0, id.1);
+                registry.services.remove(id);
+                registry.owners.remove(id);
+            }
+        }
+    }
+
+    /// Calls [`Service::started`](crate::service::Service::started) of the services.
+    pub fn start_services(&self, api: &EngineAPI) {
+        for (_, registered) in &self.services {
+            registered.hooks.started(api);
+        }
+    }
+
+    /// Calls [`Service::stopping`](crate::service::Service::stopping) of the services that are
+    /// still registered.
+    pub fn stop_services(&self, api: &EngineAPI) {
+        for (id, registered) in &self.services {
+            if api
+                .service_registry
+                .services
+                .get(id)
+                .is_some_and(|current| Arc::ptr_eq(&current.service, &registered.service))
+            {
+                registered.hooks.stopping(api);
+            }
+        }
+    }
+}
+
+/// What happens when a mod registers a task or event another mod already registered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Refuse to load the second mod.
+    Error,
+    /// Let the second mod replace the entry, logging a warning.
+    #[default]
+    Warn,
+    /// Keep the entry of the mod with the higher `mod_priorities` value, the first one on ties.
+    Priority,
+}
+
+/// Whether `new_owner` replaces the entry `old_owner` registered under `id`.
+fn resolve_conflict(
+    cfg: &ConfigTomlServer,
+    kind: &str,
+    id: &Identifier,
+    old_owner: &str,
+    new_owner: &str,
+) -> Result<bool, String> {
+    let old_owner = if old_owner.is_empty() {
+        "the engine"
+    } else {
+        old_owner
+    };
+    match cfg.registration_conflicts {
+        ConflictPolicy::Error => Err(format!(
+            "{} {}.{} registered by {} is already registered by {}",
+            kind, id.0, id.1, new_owner, old_owner
+        )),
+        ConflictPolicy::Warn => {
+            warn!(
+                "{} {}.{} registered by {} replaces the one of {}",
+                kind, id.0, id.1, new_owner, old_owner
+            );
+            Ok(true)
+        }
+        ConflictPolicy::Priority => {
+            let priority = |owner: &str| cfg.mod_priorities.get(owner).copied().unwrap_or(0);
+            let replace = priority(new_owner) > priority(old_owner);
+            let winner = if replace { new_owner } else { old_owner };
+            warn!(
+                "{} {}.{} is registered by both {} and {}, keeping the one of {}",
+                kind, id.0, id.1, old_owner, new_owner, winner
+            );
+            Ok(replace)
+        }
+    }
+}
+
+/// Registry entries a mod currently owns.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModContributions {
+    pub tasks: Vec<Identifier>,
+    pub events: Vec<Identifier>,
+    pub handlers: Vec<Identifier>, // One entry per handler, the event it handles.
+    pub cgrpc_handlers: Vec<Identifier>,
+    pub services: Vec<Identifier>,
+}
+
+impl ModContributions {
+    pub fn of(api: &EngineAPI, mod_id: &str) -> Self {
+        let owned = |owners: &HashMap<Identifier, String>| -> Vec<Identifier> {
+            let mut ids: Vec<Identifier> = owners
+                .iter()
+                .filter(|(_, owner)| *owner == mod_id)
+                .map(|(id, _)| id.clone())
+                .collect();
+            ids.sort();
+            ids
+        };
+        let mut handlers: Vec<Identifier> = api
+            .event_bus
+            .event_handler_registry
+            .event_handlers
+            .iter()
+            .flat_map(|(id, list)| {
+                list.iter()
+                    .filter(|registered| registered.owner == mod_id)
+                    .map(move |_| id.clone())
+            })
+            .collect();
+        handlers.sort();
+        Self {
+            tasks: owned(&api.task_registry.owners),
+            events: owned(&api.event_bus.event_registry.owners),
+            handlers,
+            cgrpc_handlers: owned(&api.cgrpc_registry.owners),
+            services: owned(&api.service_registry.owners),
+        }
+    }
+}
+
+/// A change to the module files, as seen by [`ModWatcher`].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ModChange {
+    Added(PathBuf),
+    Modified(PathBuf),
+    Removed(PathBuf),
+}
+
+/// Polls the paths returned by [`module_paths`] for added, modified and removed modules.
+#[derive(Debug, Default)]
+pub struct ModWatcher {
+    seen: HashMap<PathBuf, SystemTime>,
+}
+
+impl ModWatcher {
+    /// Creates a watcher that treats the current modules as already loaded.
+    pub fn new(cfg: &ConfigTomlServer) -> Self {
+        let mut watcher = Self::default();
+        watcher.poll(cfg);
+        watcher
+    }
+
+    pub fn poll(&mut self, cfg: &ConfigTomlServer) -> Vec<ModChange> {
+        let current: HashMap<PathBuf, SystemTime> = module_paths(cfg)
+            .into_iter()
+            .filter_map(|path| {
+                let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+                Some((path, modified))
+            })
+            .collect();
+        let mut changes = Vec::new();
+        for (path, modified) in &current {
+            match self.seen.get(path) {
+                None => changes.push(ModChange::Added(path.clone())),
+                Some(seen) if seen != modified => changes.push(ModChange::Modified(path.clone())),
+                Some(_) => {}
+            }
+        }
+        for path in self.seen.keys() {
+            if !current.contains_key(path) {
+                changes.push(ModChange::Removed(path.clone()));
+            }
+        }
+        changes.sort();
+        self.seen = current;
+        changes
+    }
+}
+
+/// A mod whose metadata has been read but which has not run yet.
+enum OpenedMod {
+    Native(Library),
+    Wasm(Box<WasmPackage>),
 }
 
 impl LibraryManager {
@@ -62,118 +442,612 @@ impl LibraryManager {
         drop(self);
     }
 
+    /// Loads every module found by [`module_paths`], running them in dependency order.
+    ///
+    /// All metadata is read before any `run` is called, mods with missing or mismatched
+    /// dependencies or dependency cycles are reported and skipped.
     pub fn load_modules(&mut self, api: &mut EngineAPI) {
-        let dir_path = "./mods";
-        let mut files: Vec<String> = Vec::new();
-
-        info!("Scanning for modules in directory: {}", dir_path);
-
-        match fs::read_dir(dir_path) {
-            Ok(entries) => {
-                for entry in entries.filter_map(Result::ok) {
-                    let path = entry.path();
-                    if path.is_file() {
-                        if let Some(extension) = path.extension() {
-                            if extension == "tar" {
-                                if let Some(stem) = path.file_stem() {
-                                    if stem.to_string_lossy().ends_with(".rustforge") {
-                                        debug!("Found valid module file: {}", path.display());
-                                        files.push(path.display().to_string());
-                                    }
-                                }
-                            }
-                        }
-                    }
+        let paths = module_paths(&api.cfg.config_toml);
+        info!("Found {} module(s) to load", paths.len());
+        for err in self.load_paths(paths, api) {
+            error!("{}", err);
+        }
+    }
+
+    /// Loads the given modules in dependency order, returning one message per module that failed.
+    pub fn load_paths(&mut self, paths: Vec<PathBuf>, api: &mut EngineAPI) -> Vec<String> {
+        let mut errors = Vec::new();
+        let mut pending: Vec<Option<(PathBuf, OpenedMod, LibraryMetadata)>> = Vec::new();
+        for path in paths {
+            match open_path(&path, &api.cfg.config_toml) {
+                Ok((opened, metadata)) => pending.push(Some((path, opened, metadata))),
+                Err(e) => errors.push(format!("Failed to load module {}: {}", path.display(), e)),
+            }
+        }
+        let metadata: Vec<LibraryMetadata> = pending
+            .iter()
+            .flatten()
+            .map(|(_, _, metadata)| metadata.clone())
+            .collect();
+        let (order, resolve_errors) = self.resolve_load_order(&metadata);
+        errors.extend(resolve_errors);
+        for index in order {
+            let Some((path, opened, metadata)) = pending[index].take() else {
+                continue;
+            };
+            let mod_id = metadata.mod_id.clone();
+            if let Err(e) = self.run_opened(opened, metadata, Some(&path), api) {
+                errors.push(format!("Failed to load module {}: {}", mod_id, e));
+            }
+        }
+        errors
+    }
+
+    /// Loads a `*.rustforge.tar` module or a bare library, depending on the file name.
+    pub fn load_path(&mut self, path: &Path, api: &mut EngineAPI) -> Result<(), String> {
+        let (opened, metadata) = open_path(path, &api.cfg.config_toml)?;
+        self.run_opened(opened, metadata, Some(path), api)
+    }
+
+    pub fn load_module(&mut self, path: &str, api: &mut EngineAPI) {
+        if let Err(e) = self.load_path(Path::new(path), api) {
+            error!("Failed to load module {}: {}", path, e);
+        }
+    }
+
+    pub fn load_library(&mut self, path: &str, api: &mut EngineAPI) -> Result<(), String> {
+        check_unpackaged(Path::new(path), &api.cfg.config_toml)?;
+        let (lib, metadata) = open_library(path)?;
+        self.run_opened(OpenedMod::Native(lib), metadata, Some(Path::new(path)), api)
+    }
+
+    /// Registers the tasks of an extracted WASM package directory.
+    pub fn load_wasm_package(&mut self, dir: &Path, api: &mut EngineAPI) -> Result<(), String> {
+        self.load_path(dir, api)
+    }
+
+    /// Metadata of a loaded native or WASM mod.
+    pub fn loaded(&self, mod_id: &str) -> Option<&LibraryMetadata> {
+        self.libraries
+            .get(mod_id)
+            .map(|instance| &*instance.metadata)
+            .or_else(|| self.wasm.packages.get(mod_id).map(|metadata| &**metadata))
+    }
+
+    /// Handle to the native library of `mod_id`, keeping its code mapped even if the mod is
+    /// unloaded while the handle is held.
+    pub fn library(&self, mod_id: &str) -> Option<LibraryHandle> {
+        self.libraries
+            .get(mod_id)
+            .map(|instance| instance.dynamic_library.clone())
+    }
+
+    pub fn loaded_mods(&self) -> impl Iterator<Item = &LibraryMetadata> {
+        self.libraries
+            .values()
+            .map(|instance| &*instance.metadata)
+            .chain(self.wasm.packages.values().map(|metadata| &**metadata))
+    }
+
+    /// Loaded mods that depend on `mod_id`, directly or through other mods.
+    pub fn dependents(&self, mod_id: &str) -> Vec<String> {
+        let mut found: Vec<String> = Vec::new();
+        let mut stack = vec![mod_id.to_string()];
+        while let Some(current) = stack.pop() {
+            for metadata in self.loaded_mods() {
+                if metadata
+                    .mod_dependencies
+                    .iter()
+                    .any(|dep| dep.mod_id == current)
+                    && !found.contains(&metadata.mod_id)
+                {
+                    found.push(metadata.mod_id.clone());
+                    stack.push(metadata.mod_id.clone());
                 }
             }
-            Err(e) => {
-                error!("Failed to read modules directory {}: {}", dir_path, e);
-                return;
+        }
+        found
+    }
+
+    /// Task types registered by the given mods.
+    pub fn task_types(&self, mod_ids: &[String]) -> Vec<Identifier> {
+        mod_ids
+            .iter()
+            .filter_map(|mod_id| self.registrations.get(mod_id))
+            .flat_map(ModRegistrations::task_types)
+            .collect()
+    }
+
+    /// Unloads a mod, removing everything it registered and moving its leased tasks back to the
+    /// queue before the library is closed. Fails while other loaded mods depend on it.
+    pub fn unload(&mut self, mod_id: &str, api: &mut EngineAPI) -> Result<(), String> {
+        if self.loaded(mod_id).is_none() {
+            return Err(format!("Mod {} is not loaded", mod_id));
+        }
+        if let Some(dependent) = self.dependents(mod_id).first() {
+            return Err(format!("Mod {} is required by {}", mod_id, dependent));
+        }
+        let registrations = self.registrations.remove(mod_id).unwrap_or_default();
+        registrations.stop_services(api);
+        registrations.remove(api);
+        let parked = api.park_executing(&registrations.task_types(), "ModUnloaded");
+        if parked > 0 {
+            EngineAPI::sync_db(api);
+        }
+        // Drop everything pointing into the library before it is closed.
+        drop(registrations);
+        self.sources.remove(mod_id);
+        self.close(mod_id);
+        info!(
+            "Unloaded module {}, moved {} leased task(s) back to the queue",
+            mod_id, parked
+        );
+        Ok(())
+    }
+
+    /// Unloads every mod on shutdown, stopping their services and removing everything they
+    /// registered before the libraries are closed. Leased tasks are left as they are.
+    pub fn unload_all(&mut self, api: &mut EngineAPI) {
+        // Dependents first, a mod has fewer dependents than each of its dependencies.
+        let mut registrations: Vec<(String, ModRegistrations)> =
+            std::mem::take(&mut self.registrations)
+                .into_iter()
+                .collect();
+        registrations.sort_by_cached_key(|(mod_id, _)| self.dependents(mod_id).len());
+        for (_, registrations) in &registrations {
+            registrations.stop_services(api);
+        }
+        for (_, registrations) in registrations {
+            registrations.remove(api);
+        }
+        let mut mod_ids: Vec<String> = self.libraries.keys().cloned().collect();
+        mod_ids.extend(self.wasm.packages.keys().cloned());
+        mod_ids.sort();
+        mod_ids.dedup();
+        for mod_id in &mod_ids {
+            self.close(mod_id);
+        }
+        self.sources.clear();
+        info!("Unloaded {} module(s)", mod_ids.len());
+    }
+
+    /// Forgets a loaded mod and closes its library, once nothing it registered is left. A
+    /// library whose [`LibraryHandle`] is still held by running code stays mapped instead.
+    fn close(&mut self, mod_id: &str) {
+        self.wasm.packages.remove(mod_id);
+        if let Some(instance) = self.libraries.remove(mod_id) {
+            match Arc::try_unwrap(instance.dynamic_library) {
+                Ok(lib) => {
+                    if let Err(e) = ManuallyDrop::into_inner(lib).close() {
+                        error!("Failed to close library of mod {}: {}", mod_id, e);
+                    }
+                }
+                Err(_) => warn!(
+                    "Library of mod {} is still referenced and stays mapped",
+                    mod_id
+                ),
             }
         }
+    }
 
-        info!("Found {} module(s) to load", files.len());
-        for file in files {
-            self.load_module(&file, api);
+    /// Drops everything registered since `before`, putting back the entries that were replaced.
+    fn rollback(before: &RegistrySnapshot, api: &mut EngineAPI) {
+        let registrations = ModRegistrations::since(before, api);
+        registrations.remove(api);
+        for (id, _) in &registrations.tasks {
+            if let Some(old) = before.tasks.get(id) {
+                api.task_registry.tasks.insert(id.clone(), old.clone());
+            }
+        }
+        for (id, _) in &registrations.events {
+            if let Some(old) = before.events.get(id) {
+                api.event_bus
+                    .event_registry
+                    .events
+                    .insert(id.clone(), old.clone());
+            }
+        }
+        for (id, _) in &registrations.cgrpc_handlers {
+            if let Some(old) = before.cgrpc_handlers.get(id) {
+                api.cgrpc_registry.handlers.insert(id.clone(), old.clone());
+            }
+        }
+        for (id, _) in &registrations.services {
+            if let Some(old) = before.services.get(id) {
+                api.service_registry
+                    .services
+                    .insert(id.clone(), old.clone());
+            }
         }
     }
 
-    pub fn load_module(&mut self, path: &str, api: &mut EngineAPI) {
-        info!("Loading module from path: {}", path);
-        let fs = OxiFS::new(path);
+    /// Records `mod_id` as the owner of what it registered since `before`, resolving conflicts
+    /// with entries of other mods by the configured [`ConflictPolicy`].
+    pub fn claim(
+        mod_id: &str,
+        before: &RegistrySnapshot,
+        api: &mut EngineAPI,
+    ) -> Result<ModRegistrations, String> {
+        let cfg = &api.cfg.config_toml;
+        let mut registrations = ModRegistrations::since(before, api);
+        let mut error = None;
+        let mut restored_tasks = Vec::new();
+        registrations.tasks.retain(|(id, _)| {
+            let Some(old) = before.tasks.get(id) else {
+                return true;
+            };
+            let old_owner = api.task_registry.owners.get(id).map_or("", String::as_str);
+            match resolve_conflict(cfg, "Task", id, old_owner, mod_id) {
+                Ok(true) => return true,
+                Ok(false) => {}
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
+            restored_tasks.push((id.clone(), old.clone()));
+            false
+        });
+        let mut restored_events = Vec::new();
+        registrations.events.retain(|(id, _)| {
+            let Some(old) = before.events.get(id) else {
+                return true;
+            };
+            let old_owner = api
+                .event_bus
+                .event_registry
+                .owners
+                .get(id)
+                .map_or("", String::as_str);
+            match resolve_conflict(cfg, "Event", id, old_owner, mod_id) {
+                Ok(true) => return true,
+                Ok(false) => {}
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
+            restored_events.push((id.clone(), old.clone()));
+            false
+        });
+        let mut restored_cgrpc = Vec::new();
+        registrations.cgrpc_handlers.retain(|(id, _)| {
+            let Some(old) = before.cgrpc_handlers.get(id) else {
+                return true;
+            };
+            let old_owner = api.cgrpc_registry.owners.get(id).map_or("", String::as_str);
+            match resolve_conflict(cfg, "Cgrpc handler", id, old_owner, mod_id) {
+                Ok(true) => return true,
+                Ok(false) => {}
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
+            restored_cgrpc.push((id.clone(), old.clone()));
+            false
+        });
+        let mut restored_services = Vec::new();
+        registrations.services.retain(|(id, _)| {
+            let Some(old) = before.services.get(id) else {
+                return true;
+            };
+            let old_owner = api
+                .service_registry
+                .owners
+                .get(id)
+                .map_or("", String::as_str);
+            match resolve_conflict(cfg, "Service", id, old_owner, mod_id) {
+                Ok(true) => return true,
+                Ok(false) => {}
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
+            restored_services.push((id.clone(), old.clone()));
+            false
+        });
+        if error.is_some() {
+            registrations.remove(api);
+        }
+        api.task_registry.tasks.extend(restored_tasks);
+        api.event_bus.event_registry.events.extend(restored_events);
+        api.cgrpc_registry.handlers.extend(restored_cgrpc);
+        api.service_registry.services.extend(restored_services);
+        if let Some(e) = error {
+            return Err(e);
+        }
 
-        let tmp_path = fs.tempdir.path();
-        #[cfg(unix)]
-        let library_path = tmp_path.join("mod.so");
-        #[cfg(windows)]
-        let library_path = tmp_path.join("mod.dll");
+        for (id, _) in &registrations.tasks {
+            api.task_registry
+                .owners
+                .insert(id.clone(), mod_id.to_string());
+        }
+        for (id, _) in &registrations.events {
+            api.event_bus
+                .event_registry
+                .owners
+                .insert(id.clone(), mod_id.to_string());
+        }
+        for (id, _) in &registrations.cgrpc_handlers {
+            api.cgrpc_registry
+                .owners
+                .insert(id.clone(), mod_id.to_string());
+        }
+        for (id, _) in &registrations.services {
+            api.service_registry
+                .owners
+                .insert(id.clone(), mod_id.to_string());
+        }
+        let event_handlers = &mut api.event_bus.event_handler_registry.event_handlers;
+        for (id, handler) in &registrations.handlers {
+            for registered in event_handlers.get_mut(id).into_iter().flatten() {
+                if Arc::ptr_eq(&registered.handler, handler) {
+                    registered.owner = mod_id.to_string();
+                }
+            }
+        }
+        Ok(registrations)
+    }
 
-        if let Some(lib_path_str) = library_path.to_str() {
-            debug!("Extracted library path: {}", lib_path_str);
-            if let Err(e) = self.load_library(lib_path_str, api) {
-                error!("Failed to load library {}: {}", lib_path_str, e);
+    /// Unloads a mod and every mod depending on it, then loads them again from their files.
+    pub fn reload(&mut self, mod_id: &str, api: &mut EngineAPI) -> Result<(), String> {
+        if self.loaded(mod_id).is_none() {
+            return Err(format!("Mod {} is not loaded", mod_id));
+        }
+        let mut remaining = self.dependents(mod_id);
+        remaining.push(mod_id.to_string());
+        let mut paths = Vec::new();
+        for id in &remaining {
+            match self.sources.get(id) {
+                Some(path) => paths.push(path.clone()),
+                None => return Err(format!("Mod {} was not loaded from a file", id)),
             }
-        } else {
-            error!("Invalid library path for module: {}", path);
         }
-        //std::mem::forget(fs);
+        while let Some(index) = remaining
+            .iter()
+            .position(|id| self.dependents(id).is_empty())
+        {
+            let id = remaining.remove(index);
+            self.unload(&id, api)?;
+        }
+        let errors = self.load_paths(paths, api);
+        if !errors.is_empty() {
+            return Err(errors.join("; "));
+        }
+        info!("Reloaded module {}", mod_id);
+        Ok(())
     }
 
-    pub fn load_library(&mut self, path: &str, api: &mut EngineAPI) -> Result<(), String> {
-        debug!("Attempting to load library: {}", path);
-
-        let (lib, metadata): (Library, LibraryMetadata) = unsafe {
-            match Library::new(path)
-                .map_err(|e| format!("Failed to load library: {}", e))
-                .and_then(|library| {
-                    let metadata_fn: Symbol<unsafe extern "Rust" fn() -> LibraryMetadata> = library
-                        .get(b"metadata")
-                        .map_err(|e| format!("Failed to load metadata: {}", e))?;
-                    let metadata: LibraryMetadata = metadata_fn();
-                    Ok((library, metadata))
-                }) {
-                Ok(result) => result,
-                Err(err) => {
-                    error!("Failed to load module at {}: {}", path, err);
-                    return Err(err);
+    /// Loads, reloads or unloads the mod behind a changed module file.
+    pub fn apply_change(&mut self, change: &ModChange, api: &mut EngineAPI) -> Result<(), String> {
+        match change {
+            ModChange::Added(path) => self.load_path(path, api),
+            ModChange::Modified(path) => match self.mod_at(path) {
+                Some(mod_id) => self.reload(&mod_id, api),
+                None => self.load_path(path, api),
+            },
+            ModChange::Removed(path) => match self.mod_at(path) {
+                Some(mod_id) => self.unload(&mod_id, api),
+                None => Ok(()),
+            },
+        }
+    }
+
+    fn mod_at(&self, path: &Path) -> Option<String> {
+        self.sources
+            .iter()
+            .find(|(_, source)| *source == path)
+            .map(|(mod_id, _)| mod_id.clone())
+    }
+
+    /// Orders `mods` so every mod comes after its dependencies, returning indices into `mods`
+    /// and one message per mod that cannot be loaded. Already loaded mods satisfy dependencies.
+    pub fn resolve_load_order(&self, mods: &[LibraryMetadata]) -> (Vec<usize>, Vec<String>) {
+        let mut errors = Vec::new();
+        let mut by_id: HashMap<&str, usize> = HashMap::new();
+        let mut skipped = vec![false; mods.len()];
+        for (index, metadata) in mods.iter().enumerate() {
+            if by_id.contains_key(metadata.mod_id.as_str())
+                || self.loaded(&metadata.mod_id).is_some()
+            {
+                errors.push(format!(
+                    "Mod {} is provided more than once",
+                    metadata.mod_id
+                ));
+                skipped[index] = true;
+            } else {
+                by_id.insert(&metadata.mod_id, index);
+            }
+        }
+        for (index, metadata) in mods.iter().enumerate() {
+            if skipped[index] {
+                continue;
+            }
+            for dep in &metadata.mod_dependencies {
+                let version = match by_id.get(dep.mod_id.as_str()) {
+                    Some(dep_index) => &mods[*dep_index].mod_version,
+                    None => match self.loaded(&dep.mod_id) {
+                        Some(loaded) => &loaded.mod_version,
+                        None => {
+                            errors.push(format!(
+                                "Mod {} depends on {}, which is not available",
+                                metadata.mod_id, dep.mod_id
+                            ));
+                            skipped[index] = true;
+                            continue;
+                        }
+                    },
+                };
+                if let Err(e) = check_version(dep, version) {
+                    errors.push(format!(
+                        "Mod {} depends on {}: {}",
+                        metadata.mod_id, dep.mod_id, e
+                    ));
+                    skipped[index] = true;
                 }
             }
+        }
+        // Kahn's algorithm, always placing the earliest ready mod to keep discovery order.
+        let mut order = Vec::new();
+        let mut placed = vec![false; mods.len()];
+        loop {
+            let ready = (0..mods.len()).find(|index| {
+                !placed[*index]
+                    && !skipped[*index]
+                    && mods[*index].mod_dependencies.iter().all(|dep| {
+                        match by_id.get(dep.mod_id.as_str()) {
+                            Some(dep_index) => placed[*dep_index],
+                            None => self.loaded(&dep.mod_id).is_some(),
+                        }
+                    })
+            });
+            let Some(index) = ready else {
+                break;
+            };
+            placed[index] = true;
+            order.push(index);
+        }
+        let deps_of = |index: usize| -> Vec<usize> {
+            mods[index]
+                .mod_dependencies
+                .iter()
+                .filter_map(|dep| by_id.get(dep.mod_id.as_str()).copied())
+                .collect()
         };
+        let mut reported = vec![false; mods.len()];
+        for start in 0..mods.len() {
+            if placed[start] || skipped[start] || reported[start] {
+                continue;
+            }
+            // Follow unplaced dependencies until a mod repeats or a skipped mod is reached.
+            let mut path = vec![start];
+            let mut current = start;
+            let cycle_start = loop {
+                let Some(next) = deps_of(current).into_iter().find(|dep| !placed[*dep]) else {
+                    break None;
+                };
+                if let Some(position) = path.iter().position(|index| *index == next) {
+                    break Some(position);
+                }
+                if skipped[next] || reported[next] {
+                    break None;
+                }
+                path.push(next);
+                current = next;
+            };
+            if let Some(position) = cycle_start {
+                let cycle = &path[position..];
+                let mut names: Vec<&str> = cycle.iter().map(|i| mods[*i].mod_id.as_str()).collect();
+                names.push(&mods[cycle[0]].mod_id);
+                errors.push(format!("Dependency cycle: {}", names.join(" -> ")));
+                for index in cycle {
+                    reported[*index] = true;
+                }
+            }
+        }
+        for index in 0..mods.len() {
+            if !placed[index] && !skipped[index] && !reported[index] {
+                errors.push(format!(
+                    "Mod {} is skipped because one of its dependencies cannot be loaded",
+                    mods[index].mod_id
+                ));
+            }
+        }
+        (order, errors)
+    }
 
-        // Version compatibility check
-        if metadata.api_version != crate::GIT_VERSION
-            || metadata.rustc_version != crate::RUSTC_VERSION
+    fn run_opened(
+        &mut self,
+        opened: OpenedMod,
+        metadata: LibraryMetadata,
+        source: Option<&Path>,
+        api: &mut EngineAPI,
+    ) -> Result<(), String> {
+        if self.loaded(&metadata.mod_id).is_some() {
+            return Err(format!("Mod {} is already loaded", metadata.mod_id));
+        }
+        if let Some(dep) = metadata
+            .mod_dependencies
+            .iter()
+            .find(|dep| self.loaded(&dep.mod_id).is_none())
         {
-            let err = format!(
-                "Version mismatch - Module API: {}, Engine API: {}, Module Rustc: {}, Engine Rustc: {}",
-                metadata.api_version,
-                crate::GIT_VERSION,
-                metadata.rustc_version,
-                crate::RUSTC_VERSION
-            );
-            error!("{}", err);
-            return Err(err);
+            return Err(format!("Dependency {} is not loaded", dep.mod_id));
         }
-
-        // Execute module's run function
-        if let Err(e) = unsafe {
-            lib.get(b"run")
-                .map_err(|e| format!("Failed to get run symbol: {}", e))
-                .map(|run: Symbol<unsafe extern "Rust" fn(reg: &mut EngineAPI)>| run(api))
-        } {
-            error!("Failed to execute module's run function: {}", e);
-            return Err(e);
+        let mod_id = metadata.mod_id.clone();
+        let before = RegistrySnapshot::capture(api);
+        match opened {
+            OpenedMod::Native(lib) => {
+                if let Err(e) = self.run_library(lib, metadata, api) {
+                    Self::rollback(&before, api);
+                    self.close(&mod_id);
+                    return Err(e);
+                }
+            }
+            OpenedMod::Wasm(package) => {
+                package.register(api);
+                info!(
+                    "Successfully loaded WASM module '{}' (version {}) with {} task(s)",
+                    metadata.mod_name,
+                    metadata.mod_version,
+                    package.tasks.len()
+                );
+                self.wasm
+                    .packages
+                    .insert(metadata.mod_id.clone(), Arc::new(metadata));
+            }
+        }
+        if let Some(errors) = api.mod_config_errors.remove(&mod_id) {
+            Self::rollback(&before, api);
+            self.close(&mod_id);
+            return Err(errors.join(", "));
+        }
+        match Self::claim(&mod_id, &before, api) {
+            Ok(registrations) => {
+                registrations.start_services(api);
+                self.registrations.insert(mod_id.clone(), registrations);
+            }
+            Err(e) => {
+                self.close(&mod_id);
+                return Err(e);
+            }
         }
+        if let Some(source) = source {
+            self.sources.insert(mod_id, source.to_path_buf());
+        }
+        Ok(())
+    }
 
-        // Store the loaded library
+    /// Keeps the library loaded and calls its `run`.
+    ///
+    /// The library is stored first so that, if `run` fails after registering something, the
+    /// caller can roll the registrations back before closing it.
+    fn run_library(
+        &mut self,
+        lib: Library,
+        metadata: LibraryMetadata,
+        api: &mut EngineAPI,
+    ) -> Result<(), String> {
+        let lib = Arc::new(ManuallyDrop::new(lib));
         self.libraries.insert(
             metadata.mod_id.clone(),
             LibraryInstance {
-                dynamic_library: Arc::new(ManuallyDrop::new(lib)),
+                dynamic_library: lib.clone(),
                 metadata: Arc::new(metadata.clone()),
             },
         );
 
+        // Execute module's run function
+        if let Err(e) = unsafe {
+            match lib.get::<*const CPluginDescriptor>(CABI_SYMBOL) {
+                Ok(descriptor) => (**descriptor).init(api, Some(lib.clone())),
+                Err(_) => lib
+                    .get(b"run")
+                    .map_err(|e| format!("Failed to get run symbol: {}", e))
+                    .map(|run: Symbol<unsafe extern "Rust" fn(reg: &mut EngineAPI)>| run(api)),
+            }
+        } {
+            error!("Failed to execute module's run function: {}", e);
+            return Err(e);
+        }
+
         info!(
             "Successfully loaded module '{}' (version {}) by {}",
             metadata.mod_name, metadata.mod_version, metadata.mod_author
@@ -181,3 +1055,217 @@ impl LibraryManager {
         Ok(())
     }
 }
+
+/// Checks `version` against the dependency's `version_req`.
+pub fn check_version(dep: &LibraryDependency, version: &str) -> Result<(), String> {
+    if dep.version_req.is_empty() {
+        return Ok(());
+    }
+    let req = VersionReq::parse(&dep.version_req)
+        .map_err(|e| format!("invalid version requirement {}: {}", dep.version_req, e))?;
+    let version =
+        Version::parse(version).map_err(|e| format!("invalid version {}: {}", version, e))?;
+    if !req.matches(&version) {
+        return Err(format!(
+            "version {} does not satisfy {}",
+            version, dep.version_req
+        ));
+    }
+    Ok(())
+}
+
+/// Opens a `*.rustforge.tar` module or a bare library without running it.
+///
+/// Packages are verified by [`verify_package`] before they are extracted. Packages and
+/// directories holding a [`WASM_MANIFEST`] are compiled as WASM tasks instead.
+/// Bare libraries and mod directories carry no signature, so they are only loaded with
+/// `allow_unsigned_mods`.
+fn check_unpackaged(path: &Path, cfg: &ConfigTomlServer) -> Result<(), String> {
+    if !cfg.allow_unsigned_mods {
+        return Err(format!(
+            "{} is not a signed package, set allow_unsigned_mods to load it",
+            path.display()
+        ));
+    }
+    warn!("Loading unsigned mod {}", path.display());
+    Ok(())
+}
+
+fn open_path(path: &Path, cfg: &ConfigTomlServer) -> Result<(OpenedMod, LibraryMetadata), String> {
+    let limits = cfg.wasm_limits();
+    let Some(path_str) = path.to_str() else {
+        return Err(format!("Invalid module path: {}", path.display()));
+    };
+    if path.is_dir() || !is_module_archive(path) {
+        check_unpackaged(path, cfg)?;
+    }
+    if path.is_dir() {
+        let package = WasmPackage::open(path, limits)?;
+        let metadata = package.metadata.clone();
+        return Ok((OpenedMod::Wasm(Box::new(package)), metadata));
+    }
+    if !is_module_archive(path) {
+        let (lib, metadata) = open_library(path_str)?;
+        return Ok((OpenedMod::Native(lib), metadata));
+    }
+    info!("Loading module from path: {}", path_str);
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read package: {}", e))?;
+    let manifest = verify_package(&bytes, cfg).map_err(|e| format!("Package rejected: {}", e))?;
+    let fs = OxiFS::new(path_str);
+
+    let tmp_path = fs.tempdir.path();
+    if let Some(manifest) = &manifest {
+        manifest.check_files(tmp_path)?;
+    }
+    let (opened, metadata) = if tmp_path.join(WASM_MANIFEST).is_file() {
+        let package = WasmPackage::open(tmp_path, limits)?;
+        let metadata = package.metadata.clone();
+        (OpenedMod::Wasm(Box::new(package)), metadata)
+    } else {
+        let library_path = tmp_path.join(MOD_LIBRARY);
+
+        let Some(lib_path_str) = library_path.to_str() else {
+            return Err(format!("Invalid library path for module: {}", path_str));
+        };
+        debug!("Extracted library path: {}", lib_path_str);
+        let (lib, metadata) = open_library(lib_path_str)?;
+        (OpenedMod::Native(lib), metadata)
+    };
+    if let Some(manifest) = &manifest {
+        manifest.check_metadata(&metadata)?;
+    }
+    Ok((opened, metadata))
+}
+
+/// How a mod library is linked against the engine.
+#[derive(Debug, Clone)]
+pub enum ModInterface {
+    /// Exports a [`CPluginDescriptor`], only its ABI version has to be compatible.
+    C { abi_version: AbiVersion },
+    /// Exports a [`ModHandshake`], `run` and `metadata` also need the engine's rustc.
+    Rust {
+        abi_version: AbiVersion,
+        rustc_version: String,
+    },
+}
+
+/// What a mod library declares about itself, read without calling its `run`.
+#[derive(Debug, Clone)]
+pub struct LibraryInspection {
+    pub interface: ModInterface,
+    /// Why the engine would refuse the mod, as its metadata is only read once it is compatible.
+    pub metadata: Result<LibraryMetadata, String>,
+}
+
+/// Reads a library's handshake and metadata without running it, as done by `packer mod`.
+pub fn inspect_library(path: &Path) -> Result<LibraryInspection, String> {
+    let library =
+        unsafe { Library::new(path) }.map_err(|e| format!("Failed to load library: {}", e))?;
+    inspect(&library)
+}
+
+fn inspect(library: &Library) -> Result<LibraryInspection, String> {
+    unsafe {
+        if let Ok(descriptor) = library.get::<*const CPluginDescriptor>(CABI_SYMBOL) {
+            let descriptor = &**descriptor;
+            return Ok(LibraryInspection {
+                interface: ModInterface::C {
+                    abi_version: descriptor.abi_version,
+                },
+                metadata: descriptor.read_metadata(),
+            });
+        }
+        // Only read through the C layout until the handshake says the Rust ABI matches.
+        let handshake: Symbol<*const ModHandshake> = library
+            .get(HANDSHAKE_SYMBOL)
+            .map_err(|e| format!("Missing mod handshake, rebuild the mod: {}", e))?;
+        let handshake = &**handshake;
+        let interface = ModInterface::Rust {
+            abi_version: handshake.abi_version,
+            rustc_version: handshake.rustc_version().to_string(),
+        };
+        let metadata = handshake.check().and_then(|()| {
+            let metadata_fn: Symbol<unsafe extern "Rust" fn() -> LibraryMetadata> = library
+                .get(b"metadata")
+                .map_err(|e| format!("Failed to load metadata: {}", e))?;
+            Ok(metadata_fn())
+        });
+        Ok(LibraryInspection {
+            interface,
+            metadata,
+        })
+    }
+}
+
+/// Opens a library and reads its metadata, checking it was built for this engine.
+fn open_library(path: &str) -> Result<(Library, LibraryMetadata), String> {
+    debug!("Attempting to load library: {}", path);
+
+    let result = unsafe { Library::new(path) }
+        .map_err(|e| format!("Failed to load library: {}", e))
+        .and_then(|library| {
+            let metadata = inspect(&library)?.metadata?;
+            Ok((library, metadata))
+        });
+    if let Err(err) = &result {
+        error!("Failed to load module at {}: {}", path, err);
+    }
+    result
+}
+
+fn is_module_archive(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().ends_with(".rustforge.tar"))
+}
+
+/// Modules to load, in load order.
+///
+/// Every directory in `mods_dirs` is scanned in order, each one's `*.rustforge.tar` files sorted
+/// by name, followed by the explicit `mod_paths` entries, which may also be WASM mod directories.
+/// A path listed twice is only loaded once.
+pub fn module_paths(cfg: &ConfigTomlServer) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for dir in &cfg.mods_dirs {
+        info!("Scanning for modules in directory: {}", dir.display());
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                warn!("Modules directory {} does not exist", dir.display());
+                continue;
+            }
+            Err(e) => {
+                error!("Failed to read modules directory {}: {}", dir.display(), e);
+                continue;
+            }
+        };
+        let mut found: Vec<PathBuf> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && is_module_archive(path))
+            .collect();
+        found.sort();
+        for path in found {
+            debug!("Found valid module file: {}", path.display());
+            paths.push(path);
+        }
+    }
+    for path in &cfg.mod_paths {
+        // Directories hold WASM mods
+        if !path.exists() {
+            error!("Configured module {} does not exist", path.display());
+            continue;
+        }
+        paths.push(path.clone());
+    }
+    let mut seen = Vec::new();
+    paths.retain(|path| {
+        let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+        if seen.contains(&canonical) {
+            warn!("Module {} is listed more than once", path.display());
+            return false;
+        }
+        seen.push(canonical);
+        true
+    });
+    paths
+}
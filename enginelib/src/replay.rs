@@ -0,0 +1,222 @@
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tracing::{error, warn};
+
+use crate::{
+    Identifier,
+    api::EngineAPI,
+    event::{Event, dispatch_async},
+    events::{
+        Events, ID,
+        shutdown_event::ShutdownEvent,
+        task_event::{
+            TaskAcquireEvent, TaskAcquiredEvent, TaskCreateEvent, TaskCreatedEvent,
+            TaskDeleteEvent, TaskDeletedEvent, TaskLeaseExpiredEvent, TaskPublishEvent,
+            TaskPublishedEvent,
+        },
+    },
+};
+
+/// One line of a recording.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    pub id: Identifier,
+    pub timestamp: DateTime<Utc>,
+    pub type_name: String,
+    /// The event as dispatched, before any handler ran. `None` for events without a
+    /// [`snapshot`](Event::snapshot), which can't be replayed.
+    pub snapshot: Option<serde_json::Value>,
+}
+
+/// Appends every event the [`EventBus`](crate::event::EventBus) dispatches to a file, one JSON
+/// [`RecordedEvent`] per line.
+pub struct EventRecorder {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl EventRecorder {
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open event recording {}: {}", path.display(), e))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn record(&self, id: &Identifier, event: &dyn Event) {
+        let entry = RecordedEvent {
+            id: id.clone(),
+            timestamp: Utc::now(),
+            type_name: event.type_name().to_string(),
+            snapshot: event.snapshot(),
+        };
+        let mut line = match serde_json::to_vec(&entry) {
+            Ok(line) => line,
+            Err(e) => {
+                error!(
+                    "EventRecorder: Failed to encode event {}.{}: {}",
+                    id.0, id.1, e
+                );
+                return;
+            }
+        };
+        line.push(b'\n');
+        let Ok(mut file) = self.file.lock() else {
+            error!("EventRecorder: Recording lock poisoned");
+            return;
+        };
+        if let Err(e) = file.write_all(&line) {
+            error!(
+                "EventRecorder: Failed to write to {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Reads a recording written by [`EventRecorder`].
+pub fn read_recording(path: &Path) -> Result<Vec<RecordedEvent>, String> {
+    let file = File::open(path)
+        .map_err(|e| format!("Failed to open event recording {}: {}", path.display(), e))?;
+    let mut events = Vec::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event = serde_json::from_str(&line)
+            .map_err(|e| format!("{}:{}: {}", path.display(), number + 1, e))?;
+        events.push(event);
+    }
+    Ok(events)
+}
+
+type Decoder = fn(serde_json::Value) -> Result<Box<dyn Event>, String>;
+
+fn decode<E: Event + DeserializeOwned>(
+    snapshot: serde_json::Value,
+) -> Result<Box<dyn Event>, String> {
+    serde_json::from_value::<E>(snapshot)
+        .map(|event| Box::new(event) as Box<dyn Event>)
+        .map_err(|e| e.to_string())
+}
+
+/// What the handlers did to a replayed event.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayOutcome {
+    pub id: Identifier,
+    pub cancelled: bool,
+    pub before: serde_json::Value,
+    pub after: Option<serde_json::Value>,
+}
+
+impl ReplayOutcome {
+    /// Whether a handler changed the event, e.g. rewrote a payload.
+    pub fn changed(&self) -> bool {
+        self.after.as_ref() != Some(&self.before)
+    }
+}
+
+/// Replays recordings against mods loaded into a fresh [`EngineAPI::test_default`].
+///
+/// Events are rebuilt from their snapshots by the type registered for their id, the core
+/// events are known, events of mods need [`register`](Self::register).
+pub struct ReplayHarness {
+    pub api: EngineAPI,
+    decoders: HashMap<Identifier, Decoder>,
+}
+
+impl Default for ReplayHarness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReplayHarness {
+    pub fn new() -> Self {
+        let mut api = EngineAPI::test_default();
+        Events::init(&mut api);
+        let mut harness = Self {
+            api,
+            decoders: HashMap::new(),
+        };
+        harness.register::<ShutdownEvent>(ID("core", "shutdown_event"));
+        harness.register::<TaskCreateEvent>(ID("core", "task_create"));
+        harness.register::<TaskCreatedEvent>(ID("core", "task_created"));
+        harness.register::<TaskAcquireEvent>(ID("core", "task_acquire"));
+        harness.register::<TaskAcquiredEvent>(ID("core", "task_acquired"));
+        harness.register::<TaskPublishEvent>(ID("core", "task_publish"));
+        harness.register::<TaskPublishedEvent>(ID("core", "task_published"));
+        harness.register::<TaskDeleteEvent>(ID("core", "task_delete"));
+        harness.register::<TaskDeletedEvent>(ID("core", "task_deleted"));
+        harness.register::<TaskLeaseExpiredEvent>(ID("core", "task_lease_expired"));
+        harness
+    }
+
+    /// Replays recorded `id` events as `E`.
+    pub fn register<E: Event + DeserializeOwned>(&mut self, id: Identifier) {
+        self.decoders.insert(id, decode::<E>);
+    }
+
+    /// Loads a mod archive or library like the server does.
+    pub fn load_mod(&mut self, path: &Path) -> Result<(), String> {
+        self.api
+            .with_lib_manager(|lib_manager, api| lib_manager.load_path(path, api))
+    }
+
+    /// Dispatches every replayable event to the handlers, async ones included, returning one
+    /// outcome per replayed event. Must not be called from within a tokio runtime.
+    pub fn replay(&self, recording: &[RecordedEvent]) -> Result<Vec<ReplayOutcome>, String> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())?;
+        let mut outcomes = Vec::new();
+        for recorded in recording {
+            let (namespace, name) = &recorded.id;
+            let Some(before) = recorded.snapshot.clone() else {
+                warn!(
+                    "Replay: Skipping {}.{}, it has no snapshot",
+                    namespace, name
+                );
+                continue;
+            };
+            let Some(decoder) = self.decoders.get(&recorded.id) else {
+                warn!(
+                    "Replay: Skipping {}.{}, its type is not registered",
+                    namespace, name
+                );
+                continue;
+            };
+            let mut event = decoder(before.clone())
+                .map_err(|e| format!("Failed to decode {}.{}: {}", namespace, name, e))?;
+            let handlers = self.api.event_bus.handlers(&recorded.id);
+            let cancelled = runtime.block_on(dispatch_async(&handlers, event.as_mut()));
+            outcomes.push(ReplayOutcome {
+                id: recorded.id.clone(),
+                cancelled,
+                before,
+                after: event.snapshot(),
+            });
+        }
+        Ok(outcomes)
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::{Identifier, task::StoredTask, tenant::Tenants};
+
+/// Bytes of payload a submitter with weight 1 may be handed per deficit round robin turn.
+pub const DRR_QUANTUM: usize = 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SchedulingPolicy {
+    /// Hand out tasks strictly in the order they were created.
+    #[default]
+    Fifo,
+    /// Each submitter gets `weight` tasks per round.
+    WeightedRoundRobin,
+    /// Each submitter gets `weight * DRR_QUANTUM` bytes of task payload per round.
+    DeficitRoundRobin,
+}
+
+/// Group a task's creator is scheduled in and that group's weight.
+///
+/// Members of a tenant share the tenant's weight, everyone else is scheduled on their own.
+pub fn share_of(tenants: &Tenants, uid: &str) -> (String, u32) {
+    match tenants.of_uid(uid) {
+        Some(tenant) => (format!("tenant:{}", tenant.id), tenant.weight.max(1)),
+        None => (format!("uid:{}", uid), 1),
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct TaskTypeState {
+    current: Option<String>,
+    served: u32,
+    deficits: HashMap<String, usize>,
+}
+
+/// Picks which queued task of a task type is handed out next.
+#[derive(Debug, Clone, Default)]
+pub struct Scheduler {
+    states: HashMap<Identifier, TaskTypeState>,
+}
+
+impl Scheduler {
+    /// Returns the index in `queue` of the task to hand out next, `None` if the queue is empty.
+    pub fn pick<F>(
+        &mut self,
+        policy: SchedulingPolicy,
+        key: &Identifier,
+        queue: &[StoredTask],
+        share: F,
+    ) -> Option<usize>
+    where
+        F: Fn(&str) -> (String, u32),
+    {
+        if queue.is_empty() {
+            return None;
+        }
+        // Oldest task and weight of every group with queued tasks, in a stable order.
+        let mut groups: BTreeMap<String, (usize, u32)> = BTreeMap::new();
+        for (index, task) in queue.iter().enumerate() {
+            let (group, weight) = share(&task.created_by);
+            groups.entry(group).or_insert((index, weight));
+        }
+        let state = self.states.entry(key.clone()).or_default();
+        state.deficits.retain(|group, _| groups.contains_key(group));
+        let names: Vec<&String> = groups.keys().collect();
+        let next_group = |current: &Option<String>| -> String {
+            match current {
+                Some(current) => names
+                    .iter()
+                    .find(|name| name.as_str() > current.as_str())
+                    .unwrap_or(&names[0])
+                    .to_string(),
+                None => names[0].to_string(),
+            }
+        };
+        let group = match policy {
+            SchedulingPolicy::Fifo => return Some(0),
+            SchedulingPolicy::WeightedRoundRobin => {
+                let keep = state.current.as_ref().is_some_and(|current| {
+                    groups
+                        .get(current)
+                        .is_some_and(|(_, weight)| state.served < *weight)
+                });
+                if !keep {
+                    state.current = Some(next_group(&state.current));
+                    state.served = 0;
+                }
+                state.served += 1;
+                state.current.clone().unwrap()
+            }
+            SchedulingPolicy::DeficitRoundRobin => {
+                let cost = |group: &str| queue[groups[group].0].bytes.len().max(1);
+                loop {
+                    if let Some(current) = state.current.clone()
+                        && let Some(deficit) = state.deficits.get_mut(&current)
+                        && *deficit >= cost(&current)
+                    {
+                        *deficit -= cost(&current);
+                        break current;
+                    }
+                    let group = next_group(&state.current);
+                    *state.deficits.entry(group.clone()).or_default() +=
+                        groups[&group].1 as usize * DRR_QUANTUM;
+                    state.current = Some(group);
+                }
+            }
+        };
+        debug!(
+            "Scheduler: Handing out {}.{} task of {}",
+            key.0, key.1, group
+        );
+        Some(groups[&group].0)
+    }
+}
@@ -0,0 +1,103 @@
+use std::{any::Any, collections::HashMap, sync::Arc};
+
+use semver::{Version, VersionReq};
+use tracing::debug;
+
+use crate::{Identifier, api::EngineAPI};
+
+/// Functionality a mod exposes to other mods, e.g. a shared cache or a result store.
+///
+/// Services are looked up by their concrete type, to expose an interface instead register a
+/// boxed trait object like `Box<dyn Cache>`. The hooks default to doing nothing.
+pub trait Service: Any + Send + Sync {
+    /// Called once the mod that registered the service is loaded. Mods depending on it are
+    /// loaded afterwards and can look the service up from their `run`.
+    fn started(&self, _api: &EngineAPI) {}
+    /// Called before the service is removed, when its mod is unloaded or the engine shuts down.
+    /// Mods depending on it are unloaded first.
+    fn stopping(&self, _api: &EngineAPI) {}
+}
+
+impl<T: Service + ?Sized> Service for Box<T> {
+    fn started(&self, api: &EngineAPI) {
+        (**self).started(api)
+    }
+    fn stopping(&self, api: &EngineAPI) {
+        (**self).stopping(api)
+    }
+}
+
+#[derive(Clone)]
+pub struct RegisteredService {
+    pub service: Arc<dyn Any + Send + Sync>,
+    /// The same service, to call its hooks.
+    pub hooks: Arc<dyn Service>,
+    /// Version of the interface the service implements, checked against the requirement of
+    /// [`ServiceRegistry::get`].
+    pub version: Version,
+    pub type_name: &'static str,
+}
+
+impl RegisteredService {
+    pub fn new<T: Service>(service: T, version: Version) -> Self {
+        let service = Arc::new(service);
+        Self {
+            service: service.clone(),
+            hooks: service,
+            version,
+            type_name: std::any::type_name::<T>(),
+        }
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct ServiceRegistry {
+    pub services: HashMap<Identifier, RegisteredService>,
+    pub owners: HashMap<Identifier, String>, // mod_id that registered each service.
+}
+
+impl ServiceRegistry {
+    pub fn register<T: Service>(&mut self, service: T, version: Version, identifier: Identifier) {
+        debug!(
+            "ServiceRegistry: Registering service {}.{} {}",
+            identifier.0, identifier.1, version
+        );
+        self.services
+            .insert(identifier, RegisteredService::new(service, version));
+    }
+
+    /// The service registered under `identifier`, if its version satisfies `version` and it is
+    /// a `T`.
+    pub fn get<T: Service>(
+        &self,
+        identifier: &Identifier,
+        version: &VersionReq,
+    ) -> Result<Arc<T>, String> {
+        let (namespace, name) = identifier;
+        let registered = self
+            .services
+            .get(identifier)
+            .ok_or_else(|| format!("Service {}.{} is not registered", namespace, name))?;
+        if !version.matches(&registered.version) {
+            return Err(format!(
+                "Service {}.{} {} does not satisfy {}",
+                namespace, name, registered.version, version
+            ));
+        }
+        registered.service.clone().downcast::<T>().map_err(|_| {
+            format!(
+                "Service {}.{} is a {}, not a {}",
+                namespace,
+                name,
+                registered.type_name,
+                std::any::type_name::<T>()
+            )
+        })
+    }
+
+    pub fn version(&self, identifier: &Identifier) -> Option<&Version> {
+        self.services
+            .get(identifier)
+            .map(|registered| &registered.version)
+    }
+}
@@ -12,13 +12,26 @@ use tracing::{error, instrument, warn};
 pub struct StoredTask {
     pub bytes: Vec<u8>,
     pub id: String,
+    pub created_by: String,
 }
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct StoredExecutingTask {
     pub bytes: Vec<u8>,
     pub id: String,
     pub user_id: String,
+    pub created_by: String,
     pub given_at: DateTime<Utc>,
+    pub lease_seconds: u64, // Lease granted to `user_id` on acquiring the task, 0 for the configured default.
+}
+impl StoredExecutingTask {
+    /// Whether the lease ran out by `now`, using `default_lease` for tasks leased without a length.
+    pub fn lease_expired(&self, now: DateTime<Utc>, default_lease: u64) -> bool {
+        let lease = match self.lease_seconds {
+            0 => default_lease,
+            lease => lease,
+        };
+        (now - self.given_at).num_seconds() > lease as i64
+    }
 }
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct TaskQueue {
@@ -33,6 +46,133 @@ pub struct ExecutingTaskQueue {
     pub tasks: HashMap<Identifier, Vec<StoredExecutingTask>>,
 }
 
+/// Layout version of the stored task queues, kept in the database under [`STORE_VERSION_KEY`].
+///
+/// Databases without the key were written before tasks recorded `created_by` and are read as
+/// version 1, with every task owned by `""`. Such tasks belong to no uid or tenant: only admins
+/// can list or delete them and they count toward no quota. Version 2 leases carry no
+/// `lease_seconds` and expire after the configured default.
+pub const STORE_VERSION: u32 = 3;
+pub const STORE_VERSION_KEY: &str = "store_version";
+
+/// Task layouts of older databases, converted when the queues are loaded.
+pub mod legacy {
+    use std::collections::HashMap;
+
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Serialize};
+
+    use crate::Identifier;
+
+    #[derive(Debug, Default, Clone, Serialize, Deserialize)]
+    pub struct StoredTaskV1 {
+        pub bytes: Vec<u8>,
+        pub id: String,
+    }
+    #[derive(Debug, Default, Clone, Serialize, Deserialize)]
+    pub struct StoredExecutingTaskV1 {
+        pub bytes: Vec<u8>,
+        pub id: String,
+        pub user_id: String,
+        pub given_at: DateTime<Utc>,
+    }
+    #[derive(Debug, Default, Clone, Serialize, Deserialize)]
+    pub struct StoredExecutingTaskV2 {
+        pub bytes: Vec<u8>,
+        pub id: String,
+        pub user_id: String,
+        pub created_by: String,
+        pub given_at: DateTime<Utc>,
+    }
+    #[derive(Debug, Default, Clone, Serialize, Deserialize)]
+    pub struct TaskQueueV1 {
+        pub tasks: HashMap<Identifier, Vec<StoredTaskV1>>,
+    }
+    #[derive(Debug, Default, Clone, Serialize, Deserialize)]
+    pub struct ExecutingTaskQueueV1 {
+        pub tasks: HashMap<Identifier, Vec<StoredExecutingTaskV1>>,
+    }
+    #[derive(Debug, Default, Clone, Serialize, Deserialize)]
+    pub struct ExecutingTaskQueueV2 {
+        pub tasks: HashMap<Identifier, Vec<StoredExecutingTaskV2>>,
+    }
+}
+
+impl From<legacy::StoredTaskV1> for StoredTask {
+    fn from(task: legacy::StoredTaskV1) -> Self {
+        Self {
+            bytes: task.bytes,
+            id: task.id,
+            created_by: String::new(),
+        }
+    }
+}
+impl From<legacy::StoredExecutingTaskV1> for StoredExecutingTask {
+    fn from(task: legacy::StoredExecutingTaskV1) -> Self {
+        Self {
+            bytes: task.bytes,
+            id: task.id,
+            user_id: task.user_id,
+            created_by: String::new(),
+            given_at: task.given_at,
+            lease_seconds: 0,
+        }
+    }
+}
+impl From<legacy::StoredExecutingTaskV2> for StoredExecutingTask {
+    fn from(task: legacy::StoredExecutingTaskV2) -> Self {
+        Self {
+            bytes: task.bytes,
+            id: task.id,
+            user_id: task.user_id,
+            created_by: task.created_by,
+            given_at: task.given_at,
+            lease_seconds: 0,
+        }
+    }
+}
+
+fn convert<Old: Into<New>, New>(
+    tasks: HashMap<Identifier, Vec<Old>>,
+) -> HashMap<Identifier, Vec<New>> {
+    tasks
+        .into_iter()
+        .map(|(id, tasks)| (id, tasks.into_iter().map(Into::into).collect()))
+        .collect()
+}
+
+impl TaskQueue {
+    /// Decodes a queue stored in the layout of `version`, see [`STORE_VERSION`].
+    pub fn decode(bytes: &[u8], version: u32) -> postcard::Result<Self> {
+        match version {
+            1 => postcard::from_bytes::<legacy::TaskQueueV1>(bytes).map(|queue| Self {
+                tasks: convert(queue.tasks),
+            }),
+            _ => postcard::from_bytes(bytes),
+        }
+    }
+}
+impl SolvedTasks {
+    /// Decodes solved tasks stored in the layout of `version`, see [`STORE_VERSION`].
+    pub fn decode(bytes: &[u8], version: u32) -> postcard::Result<Self> {
+        TaskQueue::decode(bytes, version).map(|queue| Self { tasks: queue.tasks })
+    }
+}
+impl ExecutingTaskQueue {
+    /// Decodes leased tasks stored in the layout of `version`, see [`STORE_VERSION`].
+    pub fn decode(bytes: &[u8], version: u32) -> postcard::Result<Self> {
+        match version {
+            1 => postcard::from_bytes::<legacy::ExecutingTaskQueueV1>(bytes).map(|queue| Self {
+                tasks: convert(queue.tasks),
+            }),
+            2 => postcard::from_bytes::<legacy::ExecutingTaskQueueV2>(bytes).map(|queue| Self {
+                tasks: convert(queue.tasks),
+            }),
+            _ => postcard::from_bytes(bytes),
+        }
+    }
+}
+
 pub trait Verifiable {
     fn verify(&self, b: Vec<u8>) -> bool;
 }
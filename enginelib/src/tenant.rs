@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use sled::{Db, Tree};
+use tracing::{debug, error, info};
+
+use crate::task::{ExecutingTaskQueue, TaskQueue};
+
+/// Name of the sled tree tenants are persisted in.
+pub const TENANT_TREE: &str = "tenants";
+
+fn default_weight() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tenant {
+    pub id: String,
+    pub namespaces: Vec<String>, // Task namespaces owned by this tenant.
+    pub credentials: HashMap<String, String>, // uid -> token of every member.
+    pub max_queued: Option<usize>, // Max tasks all members together may have queued.
+    pub max_leased: Option<usize>, // Max tasks all members together may be executing.
+    pub lease_seconds: Option<u64>, // Overrides `lease_seconds` for tasks leased by members.
+    pub weight: u32,             // Share of shared task types handed to this tenant's tasks.
+}
+
+impl Default for Tenant {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            namespaces: Vec::new(),
+            credentials: HashMap::new(),
+            max_queued: None,
+            max_leased: None,
+            lease_seconds: None,
+            weight: default_weight(),
+        }
+    }
+}
+
+impl Tenant {
+    pub fn is_member(&self, uid: &str) -> bool {
+        self.credentials.contains_key(uid)
+    }
+    pub fn owns(&self, namespace: &str) -> bool {
+        self.namespaces.iter().any(|ns| ns == namespace)
+    }
+    /// Number of queued tasks created by any member.
+    pub fn queued(&self, queue: &TaskQueue) -> usize {
+        queue
+            .tasks
+            .values()
+            .flatten()
+            .filter(|t| self.is_member(&t.created_by))
+            .count()
+    }
+    /// Number of tasks currently leased by any member.
+    pub fn leased(&self, queue: &ExecutingTaskQueue) -> usize {
+        queue
+            .tasks
+            .values()
+            .flatten()
+            .filter(|t| self.is_member(&t.user_id))
+            .count()
+    }
+}
+
+/// Tenants persisted in their own sled tree and cached in memory.
+#[derive(Debug, Clone)]
+pub struct Tenants {
+    tree: Tree,
+    pub tenants: HashMap<String, Tenant>,
+}
+
+impl Tenants {
+    pub fn new(db: &Db) -> Self {
+        let tree = db.open_tree(TENANT_TREE).unwrap();
+        let mut tenants = HashMap::new();
+        for (key, value) in tree.iter().filter_map(|res| res.ok()) {
+            match postcard::from_bytes::<Tenant>(&value) {
+                Ok(tenant) => {
+                    tenants.insert(tenant.id.clone(), tenant);
+                }
+                Err(e) => error!(
+                    "Tenants: Failed to load tenant {}: {}",
+                    String::from_utf8_lossy(&key),
+                    e
+                ),
+            }
+        }
+        debug!("Tenants: Loaded {} tenant(s)", tenants.len());
+        Self { tree, tenants }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Tenant> {
+        self.tenants.get(id)
+    }
+
+    /// Tenant the given uid is a member of.
+    pub fn of_uid(&self, uid: &str) -> Option<&Tenant> {
+        self.tenants.values().find(|t| t.is_member(uid))
+    }
+
+    /// Tenant owning the given task namespace, `None` for shared namespaces.
+    pub fn of_namespace(&self, namespace: &str) -> Option<&Tenant> {
+        self.tenants.values().find(|t| t.owns(namespace))
+    }
+
+    /// Creates or replaces a tenant. Namespaces and members can only belong to one tenant.
+    pub fn put(&mut self, tenant: Tenant) -> Result<(), String> {
+        if tenant.id.is_empty() {
+            return Err("Tenant id must not be empty".into());
+        }
+        for other in self.tenants.values().filter(|t| t.id != tenant.id) {
+            if let Some(ns) = tenant.namespaces.iter().find(|ns| other.owns(ns)) {
+                return Err(format!(
+                    "Namespace {} is already owned by tenant {}",
+                    ns, other.id
+                ));
+            }
+            if let Some(uid) = tenant.credentials.keys().find(|uid| other.is_member(uid)) {
+                return Err(format!(
+                    "User {} is already a member of tenant {}",
+                    uid, other.id
+                ));
+            }
+        }
+        let bytes = postcard::to_allocvec(&tenant).map_err(|e| e.to_string())?;
+        self.tree
+            .insert(tenant.id.as_bytes(), bytes)
+            .map_err(|e| e.to_string())?;
+        info!("Tenants: Stored tenant {}", tenant.id);
+        self.tenants.insert(tenant.id.clone(), tenant);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, id: &str) -> Option<Tenant> {
+        if let Err(e) = self.tree.remove(id.as_bytes()) {
+            error!("Tenants: Failed to remove tenant {}: {}", id, e);
+        }
+        self.tenants.remove(id)
+    }
+
+    /// Checks that `uid` presented its tenant's credentials and may use `namespace`.
+    ///
+    /// Users outside of any tenant may only use shared namespaces, members may use
+    /// shared namespaces and the ones owned by their tenant.
+    pub fn authorize(&self, uid: &str, challenge: &str, namespace: &str) -> Result<(), String> {
+        let tenant = self.of_uid(uid);
+        if let Some(tenant) = tenant
+            && tenant.credentials.get(uid).map(String::as_str) != Some(challenge)
+        {
+            return Err(format!("Invalid credentials for tenant {}", tenant.id));
+        }
+        match self.of_namespace(namespace) {
+            Some(owner) if tenant.is_none_or(|t| t.id != owner.id) => Err(format!(
+                "Namespace {} belongs to tenant {}",
+                namespace, owner.id
+            )),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! Sandboxed tasks compiled to WebAssembly.
+//!
+//! A `.rustforge.tar` package containing a [`WASM_MANIFEST`] instead of a native library is
+//! loaded as a set of WASM tasks. Every task module exports its `memory` and:
+//!
+//! - `alloc(len: i32) -> i32`, returning a buffer the engine writes inputs into.
+//! - `verify(ptr: i32, len: i32) -> i32`, non zero if the bytes are a valid task.
+//! - `serialize(ptr: i32, len: i32) -> i64`, turning the task's toml into its bytes.
+//! - `run(ptr: i32, len: i32) -> i64`, executing the task and returning its new bytes.
+//! - optionally `deserialize(ptr: i32, len: i32) -> i64`, turning the task's bytes into toml.
+//!
+//! Outputs are packed as `ptr << 32 | len`, negative values signal an error. Every call runs in
+//! a fresh instance limited by [`WasmLimits`], so guests can neither keep state nor exhaust the
+//! host.
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    fs,
+    path::{Component, Path},
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error};
+use wasmi::{
+    Config, Engine, Instance, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder,
+};
+
+use crate::{
+    Identifier, Registry,
+    api::EngineAPI,
+    plugin::LibraryMetadata,
+    task::{Task, Verifiable},
+};
+
+/// Manifest describing the tasks of a WASM package.
+pub const WASM_MANIFEST: &str = "wasm.toml";
+
+/// Resources a single call into a WASM task may use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WasmLimits {
+    pub fuel: u64,
+    pub max_memory: usize, // Bytes.
+    pub max_output: usize, // Bytes a call may return.
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WasmTaskManifest {
+    pub name: String,   // Registered as `<mod_id>:<name>`.
+    pub module: String, // Path of the .wasm file inside the package.
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WasmManifest {
+    #[serde(flatten)]
+    pub metadata: LibraryMetadata,
+    #[serde(default)]
+    pub tasks: Vec<WasmTaskManifest>,
+}
+
+/// Compiled task module together with the limits every call runs under.
+pub struct WasmModule {
+    engine: Engine,
+    module: Module,
+    limits: WasmLimits,
+}
+
+impl Debug for WasmModule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WasmModule")
+            .field("limits", &self.limits)
+            .finish()
+    }
+}
+
+impl WasmModule {
+    pub fn new(wasm: &[u8], limits: WasmLimits) -> Result<Self, String> {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, wasm).map_err(|e| e.to_string())?;
+        for export in ["memory", "alloc", "verify", "serialize", "run"] {
+            if module.get_export(export).is_none() {
+                return Err(format!("Module does not export {}", export));
+            }
+        }
+        Ok(Self {
+            engine,
+            module,
+            limits,
+        })
+    }
+
+    pub fn verify(&self, bytes: &[u8]) -> Result<bool, String> {
+        let (mut store, instance, _, ptr, len) = self.instantiate(bytes)?;
+        let valid = instance
+            .get_typed_func::<(i32, i32), i32>(&store, "verify")
+            .and_then(|func| func.call(&mut store, (ptr, len)))
+            .map_err(|e| format!("verify failed: {}", e))?;
+        Ok(valid != 0)
+    }
+
+    /// Calls one of the `(ptr, len) -> packed output` exports.
+    pub fn call(&self, export: &str, input: &[u8]) -> Result<Vec<u8>, String> {
+        let (mut store, instance, memory, ptr, len) = self.instantiate(input)?;
+        let packed = instance
+            .get_typed_func::<(i32, i32), i64>(&store, export)
+            .and_then(|func| func.call(&mut store, (ptr, len)))
+            .map_err(|e| format!("{} failed: {}", export, e))?;
+        if packed < 0 {
+            return Err(format!("{} returned error {}", export, packed));
+        }
+        let (ptr, len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+        if len > self.limits.max_output {
+            return Err(format!(
+                "{} returned {} bytes, more than the {} allowed",
+                export, len, self.limits.max_output
+            ));
+        }
+        if ptr
+            .checked_add(len)
+            .is_none_or(|end| end > memory.data(&store).len())
+        {
+            return Err(format!("{} returned a buffer outside its memory", export));
+        }
+        let mut output = vec![0; len];
+        memory
+            .read(&store, ptr, &mut output)
+            .map_err(|e| format!("{} returned an invalid buffer: {}", export, e))?;
+        Ok(output)
+    }
+
+    pub fn exports(&self, export: &str) -> bool {
+        self.module.get_export(export).is_some()
+    }
+
+    /// Creates a fresh, limited instance with `input` copied into its memory.
+    fn instantiate(
+        &self,
+        input: &[u8],
+    ) -> Result<(Store<StoreLimits>, Instance, Memory, i32, i32), String> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(self.limits.max_memory)
+            .build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store
+            .set_fuel(self.limits.fuel)
+            .map_err(|e| e.to_string())?;
+        let instance = Linker::<StoreLimits>::new(&self.engine)
+            .instantiate(&mut store, &self.module)
+            .and_then(|pre| pre.start(&mut store))
+            .map_err(|e| format!("Failed to instantiate: {}", e))?;
+        let memory = instance
+            .get_memory(&store, "memory")
+            .ok_or("Module does not export memory")?;
+        let len = i32::try_from(input.len()).map_err(|_| "Input too large")?;
+        let ptr = instance
+            .get_typed_func::<i32, i32>(&store, "alloc")
+            .and_then(|alloc| alloc.call(&mut store, len))
+            .map_err(|e| format!("alloc failed: {}", e))?;
+        memory
+            .write(&mut store, ptr as usize, input)
+            .map_err(|e| format!("alloc returned an invalid buffer: {}", e))?;
+        Ok((store, instance, memory, ptr, len))
+    }
+}
+
+/// Adapter letting a WASM task be used like any native [`Task`].
+#[derive(Clone)]
+pub struct WasmTask {
+    id: Identifier,
+    module: Arc<WasmModule>,
+    bytes: Vec<u8>,
+}
+
+impl WasmTask {
+    pub fn new(id: Identifier, module: Arc<WasmModule>) -> Self {
+        Self {
+            id,
+            module,
+            bytes: Vec::new(),
+        }
+    }
+}
+
+impl Debug for WasmTask {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WasmTask")
+            .field("id", &self.id)
+            .field("bytes", &self.bytes.len())
+            .finish()
+    }
+}
+
+impl Verifiable for WasmTask {
+    fn verify(&self, b: Vec<u8>) -> bool {
+        self.module.verify(&b).unwrap_or_else(|e| {
+            error!("WasmTask: {}.{}: {}", self.id.0, self.id.1, e);
+            false
+        })
+    }
+}
+
+impl Task for WasmTask {
+    fn get_id(&self) -> Identifier {
+        self.id.clone()
+    }
+    fn clone_box(&self) -> Box<dyn Task> {
+        Box::new(self.clone())
+    }
+    fn run_cpu(&mut self) {
+        match self.module.call("run", &self.bytes) {
+            Ok(bytes) => self.bytes = bytes,
+            Err(e) => error!("WasmTask: {}.{}: {}", self.id.0, self.id.1, e),
+        }
+    }
+    fn to_bytes(&self) -> Vec<u8> {
+        self.bytes.clone()
+    }
+    fn from_bytes(&self, bytes: &[u8]) -> Box<dyn Task> {
+        Box::new(Self {
+            bytes: bytes.to_vec(),
+            ..self.clone()
+        })
+    }
+    fn from_toml(&self, d: String) -> Box<dyn Task> {
+        let bytes = self
+            .module
+            .call("serialize", d.as_bytes())
+            .unwrap_or_else(|e| {
+                error!("WasmTask: {}.{}: {}", self.id.0, self.id.1, e);
+                Vec::new()
+            });
+        self.from_bytes(&bytes)
+    }
+    fn to_toml(&self) -> String {
+        if !self.module.exports("deserialize") {
+            return String::new();
+        }
+        match self.module.call("deserialize", &self.bytes) {
+            Ok(toml) => String::from_utf8_lossy(&toml).into_owned(),
+            Err(e) => {
+                error!("WasmTask: {}.{}: {}", self.id.0, self.id.1, e);
+                String::new()
+            }
+        }
+    }
+}
+
+/// A WASM package whose modules are compiled but not yet registered.
+#[derive(Debug)]
+pub struct WasmPackage {
+    pub metadata: LibraryMetadata,
+    pub tasks: Vec<(Identifier, Arc<WasmModule>)>,
+}
+
+impl WasmPackage {
+    /// Reads the manifest of an extracted package and compiles its modules.
+    pub fn open(dir: &Path, limits: WasmLimits) -> Result<Self, String> {
+        let manifest = fs::read_to_string(dir.join(WASM_MANIFEST))
+            .map_err(|e| format!("Failed to read {}: {}", WASM_MANIFEST, e))?;
+        let manifest: WasmManifest =
+            toml::from_str(&manifest).map_err(|e| format!("Invalid {}: {}", WASM_MANIFEST, e))?;
+        let mut tasks = Vec::new();
+        for task in &manifest.tasks {
+            let module = Path::new(&task.module);
+            if !module
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+            {
+                return Err(format!(
+                    "Module {} is not a relative path inside the package",
+                    task.module
+                ));
+            }
+            let wasm = fs::read(dir.join(module))
+                .map_err(|e| format!("Failed to read {}: {}", task.module, e))?;
+            let module = WasmModule::new(&wasm, limits)
+                .map_err(|e| format!("Invalid module {}: {}", task.module, e))?;
+            let id = (manifest.metadata.mod_id.clone(), task.name.clone());
+            tasks.push((id, Arc::new(module)));
+        }
+        Ok(Self {
+            metadata: manifest.metadata,
+            tasks,
+        })
+    }
+
+    /// Registers every task of the package.
+    pub fn register(&self, api: &mut EngineAPI) {
+        for (id, module) in &self.tasks {
+            debug!("WasmPackage: Registering task {}.{}", id.0, id.1);
+            api.task_registry.register(
+                Arc::new(WasmTask::new(id.clone(), module.clone())),
+                id.clone(),
+            );
+        }
+    }
+}
+
+/// Loaded WASM packages, kept next to the native libraries of the `LibraryManager`.
+#[derive(Debug, Default, Clone)]
+pub struct WasmManager {
+    pub packages: HashMap<String, Arc<LibraryMetadata>>,
+}
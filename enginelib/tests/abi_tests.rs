@@ -0,0 +1,52 @@
+use enginelib::abi::{ABI_VERSION, AbiVersion, ModHandshake};
+use tracing_test::traced_test;
+
+fn version(major: u32, minor: u32, patch: u32) -> AbiVersion {
+    AbiVersion {
+        major,
+        minor,
+        patch,
+    }
+}
+
+#[traced_test]
+#[test]
+fn test_abi_compatibility() {
+    let engine = version(1, 2, 3);
+    assert!(version(1, 2, 0).is_compatible_with(&engine));
+    assert!(version(1, 0, 9).is_compatible_with(&engine));
+    assert!(!version(1, 3, 0).is_compatible_with(&engine));
+    assert!(!version(2, 0, 0).is_compatible_with(&engine));
+    // Before 1.0 every minor release may break.
+    assert!(version(0, 4, 1).is_compatible_with(&version(0, 4, 0)));
+    assert!(!version(0, 3, 0).is_compatible_with(&version(0, 4, 0)));
+}
+
+#[traced_test]
+#[test]
+fn test_handshake_check() {
+    let current = ModHandshake::current();
+    assert!(current.check().is_ok());
+    assert_eq!(current.rustc_version(), enginelib::RUSTC_VERSION);
+
+    let patched = ModHandshake {
+        abi_version: version(ABI_VERSION.major, ABI_VERSION.minor, ABI_VERSION.patch + 1),
+        ..current
+    };
+    assert!(patched.check().is_ok());
+    let newer = ModHandshake {
+        abi_version: version(ABI_VERSION.major + 1, 0, 0),
+        ..current
+    };
+    assert!(newer.check().unwrap_err().contains("ABI version mismatch"));
+    let other_rustc = ModHandshake {
+        rustc_version: [b'1'; 64],
+        ..current
+    };
+    assert!(other_rustc.check().unwrap_err().contains("Rustc"));
+    let garbage = ModHandshake {
+        magic: *b"NOTAMOD!",
+        ..current
+    };
+    assert!(garbage.check().is_err());
+}
@@ -0,0 +1,105 @@
+use enginelib::{
+    api::EngineAPI,
+    audit::{AuditEntry, AuditFilter, AuditOutcome},
+    chrono::{Duration, Utc},
+    events::ID,
+};
+use tracing_test::traced_test;
+
+#[traced_test]
+#[test]
+fn test_audit_log_query() {
+    let api = EngineAPI::test_default();
+    let task = ID("test", "test_task");
+    api.audit_log.record(AuditEntry::new(
+        "alice",
+        "CreateTask",
+        task.clone(),
+        "1",
+        AuditOutcome::Success,
+    ));
+    api.audit_log.record(AuditEntry::new(
+        "bob",
+        "AquireTask",
+        task.clone(),
+        "1",
+        AuditOutcome::Success,
+    ));
+    api.audit_log.record(AuditEntry::new(
+        "bob",
+        "DeleteTask",
+        ID("test", "other_task"),
+        "2",
+        AuditOutcome::Failure("PermissionDenied: Invalid Auth".into()),
+    ));
+    api.audit_log.record(
+        AuditEntry::new("admin", "PutTenant", ID("", ""), "", AuditOutcome::Success)
+            .with_subject("acme"),
+    );
+
+    let all = api.audit_log.query(&AuditFilter::default());
+    assert_eq!(all.len(), 4);
+    assert_eq!(all[0].rpc, "CreateTask");
+    assert_eq!(all[2].rpc, "DeleteTask");
+
+    let by_uid = api.audit_log.query(&AuditFilter {
+        uid: Some("bob".into()),
+        ..Default::default()
+    });
+    assert_eq!(by_uid.len(), 2);
+
+    let by_task = api.audit_log.query(&AuditFilter {
+        target: Some(task),
+        task_id: Some("1".into()),
+        ..Default::default()
+    });
+    assert_eq!(by_task.len(), 2);
+
+    let by_subject = api.audit_log.query(&AuditFilter {
+        subject: Some("acme".into()),
+        ..Default::default()
+    });
+    assert_eq!(by_subject.len(), 1);
+    assert_eq!(by_subject[0].rpc, "PutTenant");
+    assert!(by_subject[0].task_id.is_empty());
+
+    let future = api.audit_log.query(&AuditFilter {
+        since: Some(Utc::now() + Duration::hours(1)),
+        ..Default::default()
+    });
+    assert!(future.is_empty());
+
+    let limited = api.audit_log.query(&AuditFilter {
+        limit: Some(1),
+        ..Default::default()
+    });
+    assert_eq!(limited.len(), 1);
+}
+
+#[traced_test]
+#[test]
+fn test_audit_log_retention() {
+    let api = EngineAPI::test_default();
+    let mut old = AuditEntry::new(
+        "alice",
+        "CreateTask",
+        ID("test", "test_task"),
+        "1",
+        AuditOutcome::Success,
+    );
+    old.timestamp = Utc::now() - Duration::days(10);
+    api.audit_log.record(old);
+    api.audit_log.record(AuditEntry::new(
+        "alice",
+        "CreateTask",
+        ID("test", "test_task"),
+        "2",
+        AuditOutcome::Success,
+    ));
+
+    assert_eq!(api.audit_log.purge(0), 0);
+    assert_eq!(api.audit_log.purge(5), 1);
+    let remaining = api.audit_log.query(&AuditFilter::default());
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].task_id, "2");
+}
@@ -0,0 +1,117 @@
+use std::any::Any;
+
+use enginelib::{
+    api::EngineAPI,
+    cabi::Host,
+    event::Event,
+    events::ID,
+    plugin::LibraryMetadata,
+    task::{Runner, Task, Verifiable},
+};
+use macros::{Verifiable, c_module, c_task};
+use serde::{Deserialize, Serialize};
+use tracing_test::traced_test;
+
+#[c_task]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Verifiable)]
+struct CounterTask {
+    value: i32,
+}
+
+impl Task for CounterTask {
+    fn get_id(&self) -> (String, String) {
+        ID("cabi", "counter")
+    }
+    fn clone_box(&self) -> Box<dyn Task> {
+        Box::new(self.clone())
+    }
+    fn run_cpu(&mut self) {
+        self.value += 1;
+    }
+    fn to_bytes(&self) -> Vec<u8> {
+        postcard::to_allocvec(self).unwrap()
+    }
+    fn from_bytes(&self, bytes: &[u8]) -> Box<dyn Task> {
+        Box::new(postcard::from_bytes::<CounterTask>(bytes).unwrap())
+    }
+    fn from_toml(&self, d: String) -> Box<dyn Task> {
+        Box::new(toml::from_str::<CounterTask>(&d).unwrap())
+    }
+    fn to_toml(&self) -> String {
+        toml::to_string(self).unwrap()
+    }
+}
+
+#[derive(Clone, Debug)]
+struct StopEvent {
+    cancelled: bool,
+}
+
+impl Event for StopEvent {
+    fn clone_box(&self) -> Box<dyn Event> {
+        Box::new(self.clone())
+    }
+    fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+    fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+    fn get_id(&self) -> (String, String) {
+        ID("cabi", "stop")
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+fn test_metadata() -> LibraryMetadata {
+    LibraryMetadata {
+        mod_id: "cabi_test".into(),
+        mod_version: "1.0.0".into(),
+        ..Default::default()
+    }
+}
+
+#[c_module(test_metadata)]
+fn run(host: &mut Host) {
+    host.register_task::<CounterTask>();
+    host.register_event_handler(ID("cabi", "stop"), |id| id.1 == "stop");
+}
+
+#[traced_test]
+#[test]
+fn test_c_abi_plugin() {
+    let metadata = ENGINE_CABI_PLUGIN.read_metadata().unwrap();
+    assert_eq!(metadata.mod_id, "cabi_test");
+
+    let mut api = EngineAPI::test_default();
+    ENGINE_CABI_PLUGIN.init(&mut api, None).unwrap();
+
+    let prototype = api.task_registry.tasks[&ID("cabi", "counter")].clone();
+    let bytes = postcard::to_allocvec(&CounterTask { value: 41 }).unwrap();
+    assert!(prototype.verify(bytes.clone()));
+    assert!(!prototype.verify(vec![0xff; 8]));
+
+    let mut task = prototype.from_bytes(&bytes);
+    task.run(Some(Runner::CPU));
+    let copy = task.clone_box();
+    drop(task);
+    assert_eq!(
+        postcard::from_bytes::<CounterTask>(&copy.to_bytes())
+            .unwrap()
+            .value,
+        42
+    );
+    assert_eq!(
+        prototype.from_toml(copy.to_toml()).to_bytes(),
+        copy.to_bytes()
+    );
+
+    let mut event = StopEvent { cancelled: false };
+    api.event_bus.handle(ID("cabi", "stop"), &mut event);
+    assert!(event.cancelled);
+}
@@ -229,6 +229,7 @@ fn test_task_serialization() {
     let stored_task = StoredTask {
         bytes: serialized,
         id: "id".into(),
+        created_by: "".into(),
     };
 
     // Deserialize
@@ -243,3 +244,371 @@ fn test_task_serialization() {
     let final_task: TestTask = postcard::from_bytes(&bytes).unwrap();
     assert_eq!(final_task.value, 42);
 }
+
+#[derive(Clone, Debug)]
+struct CountEvent {
+    value: i32,
+    cancelled: bool,
+}
+
+#[derive(Clone, Debug)]
+struct OtherEvent {
+    cancelled: bool,
+}
+
+macro_rules! impl_test_event {
+    ($event:ty, $id:expr) => {
+        impl Event for $event {
+            fn clone_box(&self) -> Box<dyn Event> {
+                Box::new(self.clone())
+            }
+            fn cancel(&mut self) {
+                self.cancelled = true;
+            }
+            fn is_cancelled(&self) -> bool {
+                self.cancelled
+            }
+            fn get_id(&self) -> (String, String) {
+                $id
+            }
+            fn as_any(&self) -> &dyn Any {
+                self
+            }
+            fn as_any_mut(&mut self) -> &mut dyn Any {
+                self
+            }
+        }
+    };
+}
+impl_test_event!(CountEvent, ID("test", "count"));
+impl_test_event!(OtherEvent, ID("test", "other"));
+
+#[traced_test]
+#[test]
+fn test_typed_subscribe() {
+    let mut api = EngineAPI::test_default();
+    let count_id = ID("test", "count");
+    assert!(
+        api.event_bus
+            .subscribe(|event: &mut CountEvent| event.value += 1)
+            .is_err()
+    );
+    api.event_bus.event_registry.register(
+        Arc::new(CountEvent {
+            value: 0,
+            cancelled: false,
+        }),
+        count_id.clone(),
+    );
+    assert_eq!(
+        api.event_bus
+            .subscribe(|event: &mut CountEvent| event.value += 10)
+            .unwrap(),
+        vec![count_id.clone()]
+    );
+
+    RegisterEventHandler!(OtherHandler, OtherEvent, |event: &mut OtherEvent| {
+        event.cancel();
+    });
+    assert!(
+        api.event_bus
+            .register_handler(OtherHandler, count_id.clone())
+            .is_err()
+    );
+    // Registered without the check, the mismatch is logged and the handler skipped.
+    api.event_bus
+        .event_handler_registry
+        .register_handler(OtherHandler, count_id.clone());
+
+    let mut event = CountEvent {
+        value: 0,
+        cancelled: false,
+    };
+    api.event_bus.handle(count_id, &mut event);
+    assert_eq!(event.value, 10);
+    assert!(!event.cancelled);
+}
+
+#[traced_test]
+#[test]
+fn test_handler_priorities_and_cancellation() {
+    use enginelib::event::{EventPriority, HandlerOptions};
+    use std::sync::Mutex;
+
+    let mut api = EngineAPI::test_default();
+    let count_id = ID("test", "count");
+    api.event_bus.event_registry.register(
+        Arc::new(CountEvent {
+            value: 0,
+            cancelled: false,
+        }),
+        count_id.clone(),
+    );
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let mut subscribe = |name: &'static str, priority, receive_cancelled, cancel: bool| {
+        let order = order.clone();
+        api.event_bus
+            .subscribe_with(
+                HandlerOptions {
+                    priority,
+                    receive_cancelled,
+                },
+                move |event: &mut CountEvent| {
+                    order.lock().unwrap().push(name);
+                    if cancel && event.value == 0 {
+                        event.cancel();
+                    }
+                },
+            )
+            .unwrap();
+    };
+    subscribe("monitor", EventPriority::Monitor, true, false);
+    subscribe("high", EventPriority::High, false, false);
+    subscribe("normal", EventPriority::Normal, false, true);
+    subscribe("lowest", EventPriority::Lowest, false, false);
+    subscribe("normal_late", EventPriority::Normal, false, false);
+
+    let mut event = CountEvent {
+        value: 0,
+        cancelled: false,
+    };
+    assert!(api.event_bus.handle(count_id.clone(), &mut event));
+    assert_eq!(*order.lock().unwrap(), ["lowest", "normal", "monitor"]);
+
+    order.lock().unwrap().clear();
+    let mut event = CountEvent {
+        value: 1,
+        cancelled: false,
+    };
+    assert!(!api.event_bus.handle(count_id, &mut event));
+    assert_eq!(
+        *order.lock().unwrap(),
+        ["lowest", "normal", "normal_late", "high", "monitor"]
+    );
+}
+
+#[traced_test]
+#[test]
+fn test_async_handlers() {
+    use enginelib::{
+        RegisterAsyncEventHandler,
+        event::{EventPriority, HandlerOptions},
+        events::{Events, auth_event::AuthEvent},
+    };
+
+    let mut api = EngineAPI::test_default();
+    let count_id = ID("test", "count");
+    api.event_bus.event_registry.register(
+        Arc::new(CountEvent {
+            value: 0,
+            cancelled: false,
+        }),
+        count_id.clone(),
+    );
+    RegisterAsyncEventHandler!(SlowHandler, CountEvent, |event| {
+        tokio::task::yield_now().await;
+        event.value += 5;
+    });
+    api.event_bus
+        .event_handler_registry
+        .register_async_handler_with(
+            SlowHandler,
+            count_id.clone(),
+            HandlerOptions {
+                priority: EventPriority::Low,
+                ..Default::default()
+            },
+        );
+    api.event_bus
+        .subscribe(|event: &mut CountEvent| event.value *= 2)
+        .unwrap();
+
+    // The sync path blocks on the async handler, with or without a runtime around it
+    let mut event = CountEvent {
+        value: 1,
+        cancelled: false,
+    };
+    api.event_bus.handle(count_id.clone(), &mut event);
+    assert_eq!(event.value, 12);
+    for runtime in [
+        tokio::runtime::Runtime::new().unwrap(),
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap(),
+    ] {
+        let mut event = CountEvent {
+            value: 1,
+            cancelled: false,
+        };
+        runtime.block_on(async { api.event_bus.handle(count_id.clone(), &mut event) });
+        assert_eq!(event.value, 12);
+    }
+
+    RegisterAsyncEventHandler!(SecretHandler, AuthEvent, |event| {
+        tokio::task::yield_now().await;
+        if event.challenge == "secret" {
+            *event.output.write().unwrap() = true;
+        }
+    });
+    api.event_bus
+        .event_handler_registry
+        .register_async_handler(SecretHandler, ID("core", "auth_event"));
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let mut event = CountEvent {
+        value: 1,
+        cancelled: false,
+    };
+    runtime.block_on(api.event_bus.handle_async(count_id, &mut event));
+    assert_eq!(event.value, 12);
+
+    let api = tokio::sync::RwLock::new(api);
+    assert!(runtime.block_on(Events::CheckAuthAsync(&api, "uid".into(), "secret".into())));
+    assert!(!runtime.block_on(Events::CheckAuthAsync(&api, "uid".into(), "wrong".into())));
+}
+
+#[traced_test]
+#[test]
+fn test_task_events() {
+    use enginelib::{
+        RegisterTaskCreateEventHandler,
+        events::{
+            Events,
+            task_event::{TaskCreateEvent, TaskLeaseExpiredEvent},
+        },
+    };
+
+    let mut api = EngineAPI::test_default();
+    Events::init(&mut api);
+    RegisterTaskCreateEventHandler!(UppercaseHandler, |event: &mut TaskCreateEvent| {
+        event.payload.make_ascii_uppercase();
+    });
+    api.event_bus
+        .event_handler_registry
+        .register_handler(UppercaseHandler, ID("core", "task_create"));
+    // The prototypes are registered, so typed subscriptions resolve.
+    api.event_bus
+        .subscribe(|event: &mut TaskCreateEvent| {
+            if event.uid == "blocked" {
+                event.cancel();
+            }
+        })
+        .unwrap();
+    api.event_bus
+        .subscribe(|event: &mut TaskLeaseExpiredEvent| event.cancel())
+        .unwrap();
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let api = tokio::sync::RwLock::new(api);
+    let task = ID("test", "task");
+    let mut event = TaskCreateEvent::new(task.clone(), "1".into(), "uid".into(), b"abc".to_vec());
+    assert!(!runtime.block_on(Events::dispatch(&api, &mut event)));
+    assert_eq!(event.payload, b"ABC");
+
+    let mut event = TaskCreateEvent::new(task.clone(), "2".into(), "blocked".into(), Vec::new());
+    assert!(runtime.block_on(Events::dispatch(&api, &mut event)));
+
+    // Notifications can't be cancelled.
+    let mut event = TaskLeaseExpiredEvent::new(task, "3".into(), "uid".into(), Vec::new());
+    assert!(!runtime.block_on(Events::dispatch(&api, &mut event)));
+}
+
+#[traced_test]
+#[test]
+fn test_shutdown() {
+    use enginelib::{
+        chrono::Utc,
+        events::{Events, shutdown_event::ShutdownEvent},
+        task::StoredExecutingTask,
+    };
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let mut api = EngineAPI::test_default();
+    Events::init(&mut api);
+    api.cfg.config_toml.requeue_on_shutdown = true;
+    let task = ID("test", "task");
+    api.executing_tasks.tasks.insert(
+        task.clone(),
+        vec![StoredExecutingTask {
+            bytes: vec![1],
+            user_id: "worker".into(),
+            created_by: "uid".into(),
+            given_at: Utc::now(),
+            lease_seconds: 0,
+            id: "1".into(),
+        }],
+    );
+    let seen = Arc::new(AtomicUsize::new(usize::MAX));
+    let handler_seen = seen.clone();
+    api.event_bus
+        .subscribe(move |event: &mut ShutdownEvent| {
+            handler_seen.store(event.requeued, Ordering::SeqCst)
+        })
+        .unwrap();
+
+    let api = tokio::sync::RwLock::new(api);
+    tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(EngineAPI::shutdown(&api));
+    assert_eq!(seen.load(Ordering::SeqCst), 1);
+    let api = api.into_inner();
+    assert!(api.shutting_down);
+    assert!(!api.executing_tasks.tasks.contains_key(&task));
+    assert_eq!(api.task_queue.tasks[&task][0].id, "1");
+}
+
+#[traced_test]
+#[test]
+fn test_event_recording_and_replay() {
+    use enginelib::{
+        events::{Events, task_event::TaskCreateEvent},
+        replay::{EventRecorder, ReplayHarness, read_recording},
+    };
+
+    let path = std::env::temp_dir().join(format!("engine_events_{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let mut api = EngineAPI::test_default();
+    Events::init(&mut api);
+    api.event_bus.recorder = Some(EventRecorder::create(&path).unwrap());
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let api = tokio::sync::RwLock::new(api);
+    let task = ID("test", "task");
+    for (uid, payload) in [("uid", b"abc"), ("blocked", b"def")] {
+        let mut event =
+            TaskCreateEvent::new(task.clone(), "1".into(), uid.into(), payload.to_vec());
+        runtime.block_on(Events::dispatch(&api, &mut event));
+    }
+    runtime.block_on(Events::CheckAuthAsync(&api, "uid".into(), "secret".into()));
+    drop(runtime);
+
+    let recording = read_recording(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(recording.len(), 3);
+    assert_eq!(recording[0].id, ID("core", "task_create"));
+    // Auth events carry secrets and are recorded without a snapshot.
+    assert_eq!(recording[2].id, ID("core", "auth_event"));
+    assert!(recording[2].snapshot.is_none());
+
+    let mut harness = ReplayHarness::new();
+    harness
+        .api
+        .event_bus
+        .subscribe(|event: &mut TaskCreateEvent| {
+            if event.uid == "blocked" {
+                event.cancel();
+            } else {
+                event.payload.make_ascii_uppercase();
+            }
+        })
+        .unwrap();
+    let outcomes = harness.replay(&recording).unwrap();
+    assert_eq!(outcomes.len(), 2);
+    assert!(outcomes[0].changed() && !outcomes[0].cancelled);
+    assert_eq!(
+        outcomes[0].after.as_ref().unwrap()["payload"],
+        serde_json::json!(b"ABC")
+    );
+    assert!(outcomes[1].cancelled);
+}
@@ -0,0 +1,129 @@
+use std::{collections::HashMap, time::Duration};
+
+use enginelib::{
+    config::ConfigTomlServer,
+    events::ID,
+    limits::{LimitsConfig, RateLimit, RateLimiter, queued_by},
+    task::{StoredTask, TaskQueue},
+};
+use tracing_test::traced_test;
+
+#[traced_test]
+#[test]
+fn test_rate_limiter_burst() {
+    let limiter = RateLimiter::default();
+    let limit = RateLimit {
+        per_second: 1.0,
+        burst: 2,
+    };
+    assert!(limiter.check("alice", "CreateTask", Some(&limit)).is_ok());
+    assert!(limiter.check("alice", "CreateTask", Some(&limit)).is_ok());
+    let retry_after = limiter
+        .check("alice", "CreateTask", Some(&limit))
+        .unwrap_err();
+    assert!(retry_after.as_secs_f64() <= 1.0);
+    // Buckets are separate per uid and per RPC.
+    assert!(limiter.check("bob", "CreateTask", Some(&limit)).is_ok());
+    assert!(limiter.check("alice", "AquireTask", Some(&limit)).is_ok());
+    // No configured limit means no limiting.
+    for _ in 0..100 {
+        assert!(limiter.check("alice", "GetTasks", None).is_ok());
+    }
+}
+
+#[traced_test]
+#[test]
+fn test_uid_limit_overrides() {
+    let cfg: ConfigTomlServer = toml::from_str(
+        r#"
+        [limits]
+        max_queued = 10
+        max_leased = 2
+        [limits.rate_limits]
+        CreateTask = { per_second = 5.0, burst = 10 }
+        AquireTask = { per_second = 1.0, burst = 1 }
+        [uid_limits.alice]
+        max_queued = 100
+        [uid_limits.alice.rate_limits]
+        CreateTask = { per_second = 50.0, burst = 100 }
+        "#,
+    )
+    .unwrap();
+    let alice = cfg.limits_for("alice");
+    assert_eq!(alice.max_queued, Some(100));
+    assert_eq!(alice.max_leased, Some(2));
+    assert_eq!(alice.rate_limits["CreateTask"].burst, 100);
+    assert_eq!(alice.rate_limits["AquireTask"].burst, 1);
+    let bob = cfg.limits_for("bob");
+    assert_eq!(bob.max_queued, Some(10));
+    assert_eq!(bob.rate_limits["CreateTask"].burst, 10);
+
+    let defaults = LimitsConfig::default();
+    assert!(defaults.max_queued.is_none() && defaults.rate_limits.is_empty());
+}
+
+#[traced_test]
+#[test]
+fn test_invalid_rate_limits() {
+    for limit in [
+        "{ per_second = 0.0, burst = 1 }",
+        "{ per_second = -1.0, burst = 1 }",
+        "{ per_second = 1.0, burst = 0 }",
+    ] {
+        let global = format!("[limits.rate_limits]\nCreateTask = {}", limit);
+        assert!(ConfigTomlServer::parse(&global).is_err(), "{}", global);
+        let uid = format!("[uid_limits.alice.rate_limits]\nCreateTask = {}", limit);
+        assert!(ConfigTomlServer::parse(&uid).is_err(), "{}", uid);
+    }
+    assert!(
+        ConfigTomlServer::parse(
+            "[limits.rate_limits]\nCreateTask = { per_second = 0.5, burst = 1 }"
+        )
+        .is_ok()
+    );
+
+    // A rate too slow to express as a wait doesn't panic the limiter.
+    let limiter = RateLimiter::default();
+    let limit = RateLimit {
+        per_second: 1e-300,
+        burst: 1,
+    };
+    assert!(limiter.check("alice", "CreateTask", Some(&limit)).is_ok());
+    assert_eq!(
+        limiter.check("alice", "CreateTask", Some(&limit)),
+        Err(Duration::MAX)
+    );
+}
+
+#[traced_test]
+#[test]
+fn test_queued_by() {
+    let mut tasks = HashMap::new();
+    tasks.insert(
+        ID("test", "test_task"),
+        vec![
+            StoredTask {
+                bytes: vec![],
+                id: "1".into(),
+                created_by: "alice".into(),
+            },
+            StoredTask {
+                bytes: vec![],
+                id: "2".into(),
+                created_by: "bob".into(),
+            },
+        ],
+    );
+    tasks.insert(
+        ID("test", "other_task"),
+        vec![StoredTask {
+            bytes: vec![],
+            id: "3".into(),
+            created_by: "alice".into(),
+        }],
+    );
+    let queue = TaskQueue { tasks };
+    assert_eq!(queued_by(&queue, "alice"), 2);
+    assert_eq!(queued_by(&queue, "bob"), 1);
+    assert_eq!(queued_by(&queue, "carol"), 0);
+}
@@ -0,0 +1,136 @@
+use std::fs;
+
+use enginelib::{
+    config::ConfigTomlServer,
+    package::{
+        PACKAGE_MANIFEST, PackageBuilder, generate_signing_key, public_key_hex, sha256_hex,
+        verify_package,
+    },
+    plugin::LibraryMetadata,
+};
+use tracing_test::traced_test;
+
+fn metadata() -> LibraryMetadata {
+    LibraryMetadata {
+        mod_id: "signed".into(),
+        mod_version: "1.0.0".into(),
+        ..Default::default()
+    }
+}
+
+fn trusting(keys: Vec<String>, allow_unsigned_mods: bool) -> ConfigTomlServer {
+    ConfigTomlServer {
+        trusted_keys: keys,
+        allow_unsigned_mods,
+        ..Default::default()
+    }
+}
+
+/// Rebuilds `package` with the contents of one entry replaced.
+fn repack(package: &[u8], replace: (&str, &[u8])) -> Vec<u8> {
+    let mut archive = tar::Archive::new(package);
+    let mut builder = tar::Builder::new(Vec::new());
+    for entry in archive.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let name = entry.path().unwrap().to_string_lossy().into_owned();
+        let mut data = Vec::new();
+        std::io::Read::read_to_end(&mut entry, &mut data).unwrap();
+        if name == replace.0 {
+            data = replace.1.to_vec();
+        }
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, &name, data.as_slice())
+            .unwrap();
+    }
+    builder.into_inner().unwrap()
+}
+
+#[traced_test]
+#[test]
+fn test_signed_package() {
+    let key = generate_signing_key().unwrap();
+    let package = PackageBuilder::new(metadata())
+        .file("mod.so", b"library".to_vec())
+        .build(Some(&key))
+        .unwrap();
+
+    let manifest = verify_package(&package, &trusting(vec![public_key_hex(&key)], false))
+        .unwrap()
+        .unwrap();
+    assert_eq!(manifest.metadata.mod_id, "signed");
+    assert_eq!(manifest.files["mod.so"], sha256_hex(b"library"));
+    assert!(manifest.check_metadata(&metadata()).is_ok());
+
+    let other = generate_signing_key().unwrap();
+    let untrusted = trusting(vec![public_key_hex(&other)], false);
+    assert!(
+        verify_package(&package, &untrusted)
+            .unwrap_err()
+            .contains("untrusted key")
+    );
+    assert!(verify_package(&package, &trusting(vec![public_key_hex(&other)], true)).is_ok());
+
+    let dir = std::env::temp_dir().join(format!("engine_package_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("mod.so"), b"library").unwrap();
+    assert!(manifest.check_files(&dir).is_ok());
+    fs::write(dir.join("mod.so"), b"swapped").unwrap();
+    assert!(manifest.check_files(&dir).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[traced_test]
+#[test]
+fn test_rejected_packages() {
+    let key = generate_signing_key().unwrap();
+    let cfg = trusting(vec![public_key_hex(&key)], true);
+    let package = PackageBuilder::new(metadata())
+        .file("mod.so", b"library".to_vec())
+        .build(Some(&key))
+        .unwrap();
+
+    let tampered = repack(&package, ("mod.so", b"malware"));
+    assert!(
+        verify_package(&tampered, &cfg)
+            .unwrap_err()
+            .contains("does not match")
+    );
+    let manifest = String::from_utf8(
+        tar::Archive::new(package.as_slice())
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .find(|entry| entry.path().unwrap().to_str() == Some(PACKAGE_MANIFEST))
+            .map(|mut entry| {
+                let mut data = Vec::new();
+                std::io::Read::read_to_end(&mut entry, &mut data).unwrap();
+                data
+            })
+            .unwrap(),
+    )
+    .unwrap();
+    let forged = manifest.replace("1.0.0", "2.0.0");
+    assert!(
+        verify_package(
+            &repack(&package, (PACKAGE_MANIFEST, forged.as_bytes())),
+            &cfg
+        )
+        .unwrap_err()
+        .contains("Invalid package signature")
+    );
+
+    let unsigned = PackageBuilder::new(metadata())
+        .file("mod.so", b"library".to_vec())
+        .build(None)
+        .unwrap();
+    let strict = trusting(vec![public_key_hex(&key)], false);
+    assert!(
+        verify_package(&unsigned, &strict)
+            .unwrap_err()
+            .contains("not signed")
+    );
+    assert!(verify_package(&unsigned, &cfg).unwrap().is_some());
+}
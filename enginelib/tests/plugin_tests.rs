@@ -0,0 +1,655 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, SystemTime},
+};
+
+use enginelib::{
+    Registry,
+    api::EngineAPI,
+    cgrpc::{CgrpcRequest, CgrpcResponse},
+    config::{ConfigTomlServer, ModConfig},
+    event::{Event, EventHandler},
+    events::ID,
+    plugin::{
+        ConflictPolicy, LibraryDependency, LibraryManager, LibraryMetadata, ModChange,
+        ModContributions, ModRegistrations, ModWatcher, RegistrySnapshot, module_paths,
+    },
+    semver::{Version, VersionReq},
+    service::Service,
+    task::{Task, Verifiable},
+};
+use serde::Deserialize;
+use tracing_test::traced_test;
+
+fn touch(path: &PathBuf) {
+    fs::write(path, b"").unwrap();
+}
+
+#[traced_test]
+#[test]
+fn test_module_paths_order() {
+    let root = std::env::temp_dir().join(format!("engine_mods_{}", std::process::id()));
+    let first = root.join("first");
+    let second = root.join("second");
+    fs::create_dir_all(&first).unwrap();
+    fs::create_dir_all(&second).unwrap();
+    touch(&first.join("b.rustforge.tar"));
+    touch(&first.join("a.rustforge.tar"));
+    touch(&first.join("notes.tar"));
+    touch(&second.join("c.rustforge.tar"));
+    let explicit = root.join("dev.so");
+    touch(&explicit);
+    let wasm = root.join("wasm_mod");
+    fs::create_dir_all(&wasm).unwrap();
+
+    let cfg = ConfigTomlServer {
+        mods_dirs: vec![second.clone(), root.join("missing"), first.clone()],
+        mod_paths: vec![
+            explicit.clone(),
+            wasm.clone(),
+            root.join("missing.so"),
+            first.join("a.rustforge.tar"),
+        ],
+        ..Default::default()
+    };
+    assert_eq!(
+        module_paths(&cfg),
+        vec![
+            second.join("c.rustforge.tar"),
+            first.join("a.rustforge.tar"),
+            first.join("b.rustforge.tar"),
+            explicit,
+            wasm,
+        ]
+    );
+    fs::remove_dir_all(&root).unwrap();
+}
+
+fn metadata(mod_id: &str, version: &str, deps: &[(&str, &str)]) -> LibraryMetadata {
+    LibraryMetadata {
+        mod_id: mod_id.into(),
+        mod_version: version.into(),
+        mod_dependencies: deps
+            .iter()
+            .map(|(mod_id, version_req)| LibraryDependency {
+                mod_id: mod_id.to_string(),
+                version_req: version_req.to_string(),
+                ..Default::default()
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+#[traced_test]
+#[test]
+fn test_resolve_load_order() {
+    let manager = LibraryManager::default();
+    let mods = vec![
+        metadata("app", "1.0.0", &[("core", "^1.2"), ("util", "")]),
+        metadata("util", "0.3.0", &[("core", ">=1.0.0")]),
+        metadata("core", "1.4.2", &[]),
+        metadata("standalone", "2.0.0", &[]),
+    ];
+    let (order, errors) = manager.resolve_load_order(&mods);
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert_eq!(order, vec![2, 1, 0, 3]);
+}
+
+#[traced_test]
+#[test]
+fn test_resolve_load_order_errors() {
+    let manager = LibraryManager::default();
+    let mods = vec![
+        metadata("missing_dep", "1.0.0", &[("nowhere", "")]),
+        metadata("too_new", "1.0.0", &[("core", "^2")]),
+        metadata("core", "1.4.2", &[]),
+        metadata("a", "1.0.0", &[("b", "")]),
+        metadata("b", "1.0.0", &[("a", "")]),
+        metadata("on_cycle", "1.0.0", &[("a", "")]),
+    ];
+    let (order, errors) = manager.resolve_load_order(&mods);
+    assert_eq!(order, vec![2]);
+    assert_eq!(errors.len(), 4, "{:?}", errors);
+    assert!(errors[0].contains("nowhere") && errors[0].contains("not available"));
+    assert!(errors[1].contains("does not satisfy ^2"));
+    assert!(errors.contains(&"Dependency cycle: a -> b -> a".to_string()));
+    assert!(errors.iter().any(|e| e.contains("on_cycle")));
+}
+
+#[traced_test]
+#[test]
+fn test_mod_watcher() {
+    let dir = std::env::temp_dir().join(format!("engine_watch_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let kept = dir.join("kept.rustforge.tar");
+    touch(&kept);
+    let cfg = ConfigTomlServer {
+        mods_dirs: vec![dir.clone()],
+        ..Default::default()
+    };
+    let mut watcher = ModWatcher::new(&cfg);
+    assert!(watcher.poll(&cfg).is_empty());
+
+    let added = dir.join("added.rustforge.tar");
+    touch(&added);
+    fs::File::options()
+        .write(true)
+        .open(&kept)
+        .unwrap()
+        .set_modified(SystemTime::now() + Duration::from_secs(60))
+        .unwrap();
+    assert_eq!(
+        watcher.poll(&cfg),
+        vec![ModChange::Added(added.clone()), ModChange::Modified(kept)]
+    );
+    fs::remove_file(&added).unwrap();
+    assert_eq!(watcher.poll(&cfg), vec![ModChange::Removed(added)]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[traced_test]
+#[test]
+fn test_unsigned_library() {
+    let dir = std::env::temp_dir().join(format!("engine_unsigned_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let library = dir.join("libbare.so");
+    touch(&library);
+
+    let mut api = EngineAPI::test_default();
+    let mut manager = LibraryManager::default();
+    let error = manager.load_path(&library, &mut api).unwrap_err();
+    assert!(error.contains("allow_unsigned_mods"), "{}", error);
+    let error = manager
+        .load_library(library.to_str().unwrap(), &mut api)
+        .unwrap_err();
+    assert!(error.contains("allow_unsigned_mods"), "{}", error);
+
+    // Allowed, the empty file now fails as a library
+    api.cfg.config_toml.allow_unsigned_mods = true;
+    let error = manager.load_path(&library, &mut api).unwrap_err();
+    assert!(!error.contains("allow_unsigned_mods"), "{}", error);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+struct NoopHandler;
+
+impl EventHandler for NoopHandler {
+    fn handle(&self, _event: &mut dyn Event) {}
+}
+
+#[traced_test]
+#[test]
+fn test_mod_registrations() {
+    let mut api = EngineAPI::test_default();
+    let registry = &mut api.event_bus.event_handler_registry;
+    registry.register_handler(NoopHandler, ID("core", "kept"));
+    registry.register_handler(NoopHandler, ID("core", "shared"));
+    let before = RegistrySnapshot::capture(&api);
+
+    let registry = &mut api.event_bus.event_handler_registry;
+    registry.register_handler(NoopHandler, ID("core", "shared"));
+    registry.register_handler(NoopHandler, ID("test", "own"));
+    let registrations = ModRegistrations::since(&before, &api);
+    assert_eq!(registrations.handlers.len(), 2);
+
+    registrations.remove(&mut api);
+    let handlers = &api.event_bus.event_handler_registry.event_handlers;
+    assert_eq!(handlers[&ID("core", "kept")].len(), 1);
+    assert_eq!(handlers[&ID("core", "shared")].len(), 1);
+    assert!(!handlers.contains_key(&ID("test", "own")));
+}
+
+#[derive(Debug, Clone)]
+struct OwnedTask {
+    owner: &'static str,
+}
+
+impl Verifiable for OwnedTask {
+    fn verify(&self, _b: Vec<u8>) -> bool {
+        true
+    }
+}
+
+impl Task for OwnedTask {
+    fn get_id(&self) -> (String, String) {
+        ID("shared", "task")
+    }
+    fn clone_box(&self) -> Box<dyn Task> {
+        Box::new(self.clone())
+    }
+    fn run_cpu(&mut self) {}
+    fn to_bytes(&self) -> Vec<u8> {
+        self.owner.as_bytes().to_vec()
+    }
+    fn from_bytes(&self, _bytes: &[u8]) -> Box<dyn Task> {
+        Box::new(self.clone())
+    }
+    fn from_toml(&self, _d: String) -> Box<dyn Task> {
+        Box::new(self.clone())
+    }
+    fn to_toml(&self) -> String {
+        String::new()
+    }
+}
+
+/// Registers the shared task as "first", then as "second", returning the result of the claim.
+fn register_twice(policy: ConflictPolicy, second_priority: i64) -> (EngineAPI, Result<(), String>) {
+    let mut api = EngineAPI::test_default();
+    api.cfg.config_toml.registration_conflicts = policy;
+    api.cfg
+        .config_toml
+        .mod_priorities
+        .insert("second".into(), second_priority);
+    let before = RegistrySnapshot::capture(&api);
+    api.task_registry
+        .register(Arc::new(OwnedTask { owner: "first" }), ID("shared", "task"));
+    LibraryManager::claim("first", &before, &mut api).unwrap();
+
+    let before = RegistrySnapshot::capture(&api);
+    api.task_registry.register(
+        Arc::new(OwnedTask { owner: "second" }),
+        ID("shared", "task"),
+    );
+    api.event_bus
+        .event_handler_registry
+        .register_handler(NoopHandler, ID("core", "start_event"));
+    api.cgrpc_registry.register(
+        Arc::new(|_: CgrpcRequest| CgrpcResponse::ok(Vec::new())),
+        ID("second", "ping"),
+    );
+    let result = LibraryManager::claim("second", &before, &mut api).map(|_| ());
+    (api, result)
+}
+
+fn owner_of_task(api: &EngineAPI) -> (String, Vec<u8>) {
+    let id = ID("shared", "task");
+    (
+        api.task_registry.owners[&id].clone(),
+        api.task_registry.tasks[&id].to_bytes(),
+    )
+}
+
+#[traced_test]
+#[test]
+fn test_registration_conflicts() {
+    let (api, result) = register_twice(ConflictPolicy::Warn, 0);
+    assert!(result.is_ok());
+    assert_eq!(owner_of_task(&api), ("second".into(), b"second".to_vec()));
+    assert_eq!(
+        ModContributions::of(&api, "second"),
+        ModContributions {
+            tasks: vec![ID("shared", "task")],
+            events: vec![],
+            handlers: vec![ID("core", "start_event")],
+            cgrpc_handlers: vec![ID("second", "ping")],
+            services: vec![],
+        }
+    );
+    assert_eq!(
+        ModContributions::of(&api, "first"),
+        ModContributions::default()
+    );
+
+    let (api, result) = register_twice(ConflictPolicy::Error, 0);
+    assert!(result.unwrap_err().contains("already registered by first"));
+    assert_eq!(owner_of_task(&api), ("first".into(), b"first".to_vec()));
+    assert!(
+        api.event_bus
+            .event_handler_registry
+            .event_handlers
+            .is_empty()
+    );
+
+    let (api, result) = register_twice(ConflictPolicy::Priority, 0);
+    assert!(result.is_ok());
+    assert_eq!(owner_of_task(&api), ("first".into(), b"first".to_vec()));
+    assert_eq!(ModContributions::of(&api, "second").tasks, vec![]);
+
+    let (api, _) = register_twice(ConflictPolicy::Priority, 1);
+    assert_eq!(owner_of_task(&api), ("second".into(), b"second".to_vec()));
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default)]
+struct GreeterConfig {
+    greeting: String,
+    repeat: u32,
+}
+
+impl Default for GreeterConfig {
+    fn default() -> Self {
+        Self {
+            greeting: "hello".into(),
+            repeat: 1,
+        }
+    }
+}
+
+impl ModConfig for GreeterConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.repeat == 0 {
+            return Err("repeat must be at least 1".into());
+        }
+        Ok(())
+    }
+}
+
+#[traced_test]
+#[test]
+fn test_mod_config() {
+    let cfg: ConfigTomlServer = toml::from_str(
+        r#"
+        mod_paths = ["dev.so"]
+        [mods.greeter]
+        repeat = 3
+        [mods.broken]
+        repeat = 0
+        [mods.mistyped]
+        repeat = "twice"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(cfg.mod_paths, vec![PathBuf::from("dev.so")]);
+    assert_eq!(
+        cfg.mod_config::<GreeterConfig>("greeter").unwrap(),
+        GreeterConfig {
+            greeting: "hello".into(),
+            repeat: 3,
+        }
+    );
+    assert_eq!(
+        cfg.mod_config::<GreeterConfig>("missing").unwrap(),
+        GreeterConfig::default()
+    );
+    assert!(cfg.mod_config::<GreeterConfig>("broken").is_err());
+    assert!(cfg.mod_config::<GreeterConfig>("mistyped").is_err());
+    // The list form predating the sections is still read as `mod_paths`.
+    let legacy = ConfigTomlServer::parse(
+        r#"
+        cgrpc_token = "token"
+        mod_paths = ["first.so"]
+        mods = ["dev.so"]
+        "#,
+    )
+    .unwrap();
+    assert_eq!(
+        legacy.mod_paths,
+        vec![PathBuf::from("first.so"), PathBuf::from("dev.so")]
+    );
+    assert_eq!(legacy.cgrpc_token.as_deref(), Some("token"));
+    assert!(legacy.mods.is_empty());
+
+    let mut api = EngineAPI::test_default();
+    api.cfg.config_toml = cfg;
+    assert_eq!(api.mod_config::<GreeterConfig>("greeter").repeat, 3);
+    assert!(api.mod_config_errors.is_empty());
+    assert_eq!(
+        api.mod_config::<GreeterConfig>("broken"),
+        GreeterConfig::default()
+    );
+    assert_eq!(api.mod_config_errors["broken"].len(), 1);
+}
+
+#[traced_test]
+#[test]
+fn test_cgrpc_registry() {
+    use enginelib::{RegisterCgrpcHandler, cgrpc::CgrpcCode};
+
+    RegisterCgrpcHandler!(EchoHandler, test, echo, |request: CgrpcRequest| {
+        if request.payload.is_empty() {
+            return CgrpcResponse::error(CgrpcCode::InvalidArgument, "empty payload");
+        }
+        CgrpcResponse::ok(request.payload).with_metadata("handled-by", "echo")
+    });
+    let mut api = EngineAPI::test_default();
+    api.cgrpc_registry
+        .register(Arc::new(EchoHandler), EchoHandler::id());
+
+    assert!(api.cgrpc_registry.get(&ID("test", "missing")).is_none());
+    let handler = api.cgrpc_registry.get(&ID("test", "echo")).unwrap();
+    let response = handler.handle(CgrpcRequest {
+        payload: b"hi".to_vec(),
+        ..Default::default()
+    });
+    assert_eq!(response.payload, b"hi");
+    assert_eq!(response.metadata["handled-by"], "echo");
+    assert_eq!(
+        handler.handle(CgrpcRequest::default()).code,
+        CgrpcCode::InvalidArgument
+    );
+}
+
+#[traced_test]
+#[test]
+fn test_typed_cgrpc_handlers() {
+    use enginelib::{
+        RegisterCgrpcHandler,
+        cgrpc::{CgrpcCode, ContentType},
+    };
+    use serde::Serialize;
+
+    #[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+    struct Add {
+        a: u32,
+        b: u32,
+    }
+    RegisterCgrpcHandler!(
+        AddHandler,
+        test,
+        add,
+        Add => u32,
+        |add: Add, _: &CgrpcRequest| add.a.checked_add(add.b).ok_or_else(|| {
+            CgrpcResponse::error(CgrpcCode::OutOfRange, "overflow")
+        })
+    );
+    let mut api = EngineAPI::test_default();
+    api.cgrpc_registry
+        .register(Arc::new(AddHandler), AddHandler::id());
+    api.cgrpc_registry.register_typed(
+        |name: String, request: &CgrpcRequest| Ok(format!("{} from {}", name, request.uid)),
+        ID("test", "greet"),
+    );
+
+    let add = api.cgrpc_registry.get(&AddHandler::id()).unwrap();
+    let schemas = add.schemas().unwrap();
+    assert!(schemas.request.type_name.ends_with("Add"));
+    assert_eq!(schemas.request.example, r#"{"a":0,"b":0}"#);
+    assert_eq!(schemas.response.type_name, "u32");
+
+    for content_type in [ContentType::Postcard, ContentType::Json] {
+        let response = add.handle(CgrpcRequest {
+            content_type,
+            payload: content_type.encode(&Add { a: 2, b: 3 }).unwrap(),
+            ..Default::default()
+        });
+        assert_eq!(content_type.decode::<u32>(&response.payload), Ok(5));
+    }
+    let response = add.handle(CgrpcRequest {
+        content_type: ContentType::Json,
+        payload: br#"{"a":4294967295,"b":1}"#.to_vec(),
+        ..Default::default()
+    });
+    assert_eq!(response.code, CgrpcCode::OutOfRange);
+    let response = add.handle(CgrpcRequest {
+        content_type: ContentType::Json,
+        payload: b"not json".to_vec(),
+        ..Default::default()
+    });
+    assert_eq!(response.code, CgrpcCode::InvalidArgument);
+
+    let greet = api.cgrpc_registry.get(&ID("test", "greet")).unwrap();
+    let response = greet.handle(CgrpcRequest {
+        uid: "alice".into(),
+        content_type: ContentType::Json,
+        payload: br#""hello""#.to_vec(),
+        ..Default::default()
+    });
+    assert_eq!(response.payload, br#""hello from alice""#);
+    assert!(ContentType::parse("text/plain").is_err());
+    assert_eq!(ContentType::parse(""), Ok(ContentType::Postcard));
+}
+
+#[traced_test]
+#[test]
+fn test_cgrpc_stream_handlers() {
+    use enginelib::{
+        RegisterCgrpcStreamHandler,
+        cgrpc::{CgrpcCode, CgrpcStream},
+    };
+    use tokio::sync::mpsc;
+
+    RegisterCgrpcStreamHandler!(
+        EchoStream,
+        test,
+        echo,
+        |mut stream: CgrpcStream| async move {
+            while let Some(frame) = stream.incoming.recv().await {
+                if frame.is_empty() {
+                    return Err(CgrpcResponse::error(
+                        CgrpcCode::InvalidArgument,
+                        "empty frame",
+                    ));
+                }
+                stream.outgoing.send(frame).await.ok();
+            }
+            Ok(())
+        }
+    );
+    let mut api = EngineAPI::test_default();
+    api.cgrpc_registry
+        .register(Arc::new(EchoStream), EchoStream::id());
+    api.cgrpc_registry.register_stream(
+        |stream: CgrpcStream| async move {
+            stream.outgoing.send(stream.uid.into_bytes()).await.ok();
+            Ok(())
+        },
+        ID("test", "whoami"),
+    );
+
+    let open = |handler_id: &str, uid: &str, frames: Vec<Vec<u8>>| {
+        let (in_tx, incoming) = mpsc::channel(8);
+        let (outgoing, mut out_rx) = mpsc::channel(8);
+        for frame in frames {
+            in_tx.try_send(frame).unwrap();
+        }
+        drop(in_tx);
+        let handler = api.cgrpc_registry.get(&ID("test", handler_id)).unwrap();
+        assert!(handler.streaming());
+        let handling = handler.handle_stream(CgrpcStream {
+            handler: ID("test", handler_id),
+            uid: uid.into(),
+            metadata: Default::default(),
+            incoming,
+            outgoing,
+        });
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let result = handling.await;
+            let mut sent = Vec::new();
+            while let Some(frame) = out_rx.recv().await {
+                sent.push(frame);
+            }
+            (result, sent)
+        })
+    };
+
+    let (result, sent) = open("echo", "uid", vec![b"a".to_vec(), b"b".to_vec()]);
+    assert!(result.is_ok());
+    assert_eq!(sent, vec![b"a".to_vec(), b"b".to_vec()]);
+    let (result, sent) = open("echo", "uid", vec![b"a".to_vec(), Vec::new()]);
+    assert_eq!(result.unwrap_err().code, CgrpcCode::InvalidArgument);
+    assert_eq!(sent, vec![b"a".to_vec()]);
+    let (_, sent) = open("whoami", "alice", Vec::new());
+    assert_eq!(sent, vec![b"alice".to_vec()]);
+
+    // Stream handlers don't answer unary calls.
+    let echo = api.cgrpc_registry.get(&EchoStream::id()).unwrap();
+    assert_eq!(
+        echo.handle(CgrpcRequest::default()).code,
+        CgrpcCode::Unimplemented
+    );
+}
+
+trait Cache: Service {
+    fn get(&self, key: &str) -> Option<String>;
+}
+
+#[derive(Debug, Default)]
+struct MemoryCache {
+    started: AtomicUsize,
+    stopped: AtomicUsize,
+}
+
+impl Service for MemoryCache {
+    fn started(&self, _api: &EngineAPI) {
+        self.started.fetch_add(1, Ordering::SeqCst);
+    }
+    fn stopping(&self, _api: &EngineAPI) {
+        self.stopped.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl Cache for MemoryCache {
+    fn get(&self, key: &str) -> Option<String> {
+        Some(key.to_uppercase())
+    }
+}
+
+#[traced_test]
+#[test]
+fn test_service_registry() {
+    let mut api = EngineAPI::test_default();
+    let before = RegistrySnapshot::capture(&api);
+    api.service_registry.register(
+        MemoryCache::default(),
+        Version::new(1, 2, 0),
+        ID("cache", "memory"),
+    );
+    let cache: Box<dyn Cache> = Box::new(MemoryCache::default());
+    api.service_registry
+        .register(cache, Version::new(2, 0, 0), ID("cache", "cache"));
+    let registrations = LibraryManager::claim("cache", &before, &mut api).unwrap();
+    registrations.start_services(&api);
+    assert_eq!(
+        ModContributions::of(&api, "cache").services,
+        vec![ID("cache", "cache"), ID("cache", "memory")]
+    );
+
+    let registry = &api.service_registry;
+    let memory = registry
+        .get::<MemoryCache>(&ID("cache", "memory"), &VersionReq::parse("^1.1").unwrap())
+        .unwrap();
+    assert_eq!(memory.started.load(Ordering::SeqCst), 1);
+    let cache = registry
+        .get::<Box<dyn Cache>>(&ID("cache", "cache"), &VersionReq::parse("^2").unwrap())
+        .unwrap();
+    assert_eq!(cache.get("key").as_deref(), Some("KEY"));
+    assert!(
+        registry
+            .get::<MemoryCache>(&ID("cache", "memory"), &VersionReq::parse("^2").unwrap())
+            .unwrap_err()
+            .contains("does not satisfy")
+    );
+    assert!(
+        registry
+            .get::<MemoryCache>(&ID("cache", "cache"), &VersionReq::STAR)
+            .unwrap_err()
+            .contains("not a")
+    );
+    assert!(
+        registry
+            .get::<MemoryCache>(&ID("cache", "missing"), &VersionReq::STAR)
+            .is_err()
+    );
+
+    registrations.stop_services(&api);
+    registrations.remove(&mut api);
+    assert_eq!(memory.stopped.load(Ordering::SeqCst), 1);
+    assert!(api.service_registry.services.is_empty());
+    assert!(api.service_registry.owners.is_empty());
+}
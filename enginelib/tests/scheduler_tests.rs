@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use enginelib::{
+    api::EngineAPI,
+    events::ID,
+    scheduler::{DRR_QUANTUM, SchedulingPolicy},
+    task::StoredTask,
+    tenant::Tenant,
+};
+use tracing_test::traced_test;
+
+fn task(id: usize, created_by: &str, size: usize) -> StoredTask {
+    StoredTask {
+        bytes: vec![0; size],
+        id: id.to_string(),
+        created_by: created_by.into(),
+    }
+}
+
+/// Queues 100 tasks of a flooding submitter followed by 2 of a second one.
+fn flooded_api(policy: SchedulingPolicy) -> EngineAPI {
+    let mut api = EngineAPI::test_default();
+    api.cfg.config_toml.scheduling_policy = policy;
+    let mut tasks: Vec<StoredTask> = (0..100).map(|i| task(i, "flood", DRR_QUANTUM)).collect();
+    tasks.push(task(100, "small", DRR_QUANTUM));
+    tasks.push(task(101, "small", DRR_QUANTUM));
+    api.task_queue.tasks.insert(ID("test", "test_task"), tasks);
+    api
+}
+
+/// Positions at which `uid`'s tasks were handed out.
+fn positions(api: &mut EngineAPI, uid: &str) -> Vec<usize> {
+    let key = ID("test", "test_task");
+    let mut positions = Vec::new();
+    let mut position = 0;
+    while let Some(task) = api.take_next_task(&key) {
+        if task.created_by == uid {
+            positions.push(position);
+        }
+        position += 1;
+    }
+    positions
+}
+
+#[traced_test]
+#[test]
+fn test_fifo_keeps_order() {
+    let mut api = flooded_api(SchedulingPolicy::Fifo);
+    assert_eq!(positions(&mut api, "small"), vec![100, 101]);
+}
+
+#[traced_test]
+#[test]
+fn test_round_robin_prevents_starvation() {
+    let mut api = flooded_api(SchedulingPolicy::WeightedRoundRobin);
+    assert_eq!(positions(&mut api, "small"), vec![1, 3]);
+    let mut api = flooded_api(SchedulingPolicy::DeficitRoundRobin);
+    assert_eq!(positions(&mut api, "small"), vec![1, 3]);
+}
+
+#[traced_test]
+#[test]
+fn test_tenant_weight() {
+    let mut api = flooded_api(SchedulingPolicy::WeightedRoundRobin);
+    api.tenants
+        .put(Tenant {
+            id: "heavy".into(),
+            credentials: HashMap::from([("small".to_string(), "token".to_string())]),
+            weight: 3,
+            ..Default::default()
+        })
+        .unwrap();
+    let key = ID("test", "test_task");
+    api.task_queue
+        .tasks
+        .get_mut(&key)
+        .unwrap()
+        .extend((102..110).map(|i| task(i, "small", DRR_QUANTUM)));
+    // "small" gets three tasks for every one of "flood".
+    assert_eq!(positions(&mut api, "small")[..6], [0, 1, 2, 4, 5, 6]);
+}
+
+#[traced_test]
+#[test]
+fn test_deficit_round_robin_accounts_for_size() {
+    let mut api = EngineAPI::test_default();
+    api.cfg.config_toml.scheduling_policy = SchedulingPolicy::DeficitRoundRobin;
+    let key = ID("test", "test_task");
+    let mut tasks: Vec<StoredTask> = (0..4).map(|i| task(i, "big", DRR_QUANTUM)).collect();
+    tasks.extend((4..12).map(|i| task(i, "small", DRR_QUANTUM / 4)));
+    api.task_queue.tasks.insert(key, tasks);
+    // Every turn hands out a quantum worth of bytes: one big task or four small ones.
+    assert_eq!(positions(&mut api, "big"), vec![0, 5, 10, 11]);
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, Utc};
+use enginelib::{
+    api::{EngineAPI, postcard},
+    events::ID,
+    task::{
+        STORE_VERSION, STORE_VERSION_KEY, StoredExecutingTask,
+        legacy::{
+            ExecutingTaskQueueV1, ExecutingTaskQueueV2, StoredExecutingTaskV1,
+            StoredExecutingTaskV2, StoredTaskV1, TaskQueueV1,
+        },
+    },
+};
+use tracing_test::traced_test;
+
+#[traced_test]
+#[test]
+fn test_load_store_without_owners() {
+    let mut api = EngineAPI::test_default();
+    let key = ID("dev", "task");
+    let queued = TaskQueueV1 {
+        tasks: HashMap::from([(
+            key.clone(),
+            vec![StoredTaskV1 {
+                bytes: vec![1, 2, 3],
+                id: "queued".into(),
+            }],
+        )]),
+    };
+    let leased = ExecutingTaskQueueV1 {
+        tasks: HashMap::from([(
+            key.clone(),
+            vec![StoredExecutingTaskV1 {
+                bytes: vec![4],
+                id: "leased".into(),
+                user_id: "worker".into(),
+                given_at: Utc::now(),
+            }],
+        )]),
+    };
+    let db = api.db.clone();
+    db.insert("tasks", postcard::to_allocvec(&queued).unwrap())
+        .unwrap();
+    db.insert("executing_tasks", postcard::to_allocvec(&leased).unwrap())
+        .unwrap();
+    db.insert("solved_tasks", postcard::to_allocvec(&queued).unwrap())
+        .unwrap();
+
+    EngineAPI::init_db(&mut api);
+
+    let task = &api.task_queue.tasks[&key][0];
+    assert_eq!(task.id, "queued");
+    assert_eq!(task.bytes, vec![1, 2, 3]);
+    assert_eq!(task.created_by, "");
+    let task = &api.executing_tasks.tasks[&key][0];
+    assert_eq!(task.id, "leased");
+    assert_eq!(task.user_id, "worker");
+    assert_eq!(task.created_by, "");
+    assert_eq!(api.solved_tasks.tasks[&key][0].id, "queued");
+    assert_eq!(
+        db.get(STORE_VERSION_KEY).unwrap().unwrap().as_ref(),
+        STORE_VERSION.to_le_bytes()
+    );
+
+    // Loading again reads the converted queues in the current layout
+    EngineAPI::init_db(&mut api);
+    assert_eq!(api.task_queue.tasks[&key][0].id, "queued");
+    assert_eq!(api.executing_tasks.tasks[&key][0].user_id, "worker");
+}
+
+#[traced_test]
+#[test]
+fn test_load_unreadable_store() {
+    let mut api = EngineAPI::test_default();
+    let db = api.db.clone();
+    db.insert(STORE_VERSION_KEY, &STORE_VERSION.to_le_bytes())
+        .unwrap();
+    db.insert("tasks", vec![0xff; 4]).unwrap();
+
+    EngineAPI::init_db(&mut api);
+
+    assert!(api.task_queue.tasks.is_empty());
+    assert_eq!(
+        db.get("tasks_unreadable").unwrap().unwrap().as_ref(),
+        [0xff; 4]
+    );
+}
+
+#[traced_test]
+#[test]
+fn test_load_leases_without_length() {
+    let mut api = EngineAPI::test_default();
+    let key = ID("dev", "task");
+    let leased = ExecutingTaskQueueV2 {
+        tasks: HashMap::from([(
+            key.clone(),
+            vec![StoredExecutingTaskV2 {
+                bytes: vec![4],
+                id: "leased".into(),
+                user_id: "worker".into(),
+                created_by: "user".into(),
+                given_at: Utc::now(),
+            }],
+        )]),
+    };
+    let db = api.db.clone();
+    db.insert(STORE_VERSION_KEY, &2u32.to_le_bytes()).unwrap();
+    db.insert("executing_tasks", postcard::to_allocvec(&leased).unwrap())
+        .unwrap();
+
+    EngineAPI::init_db(&mut api);
+
+    let task = &api.executing_tasks.tasks[&key][0];
+    assert_eq!(task.created_by, "user");
+    assert_eq!(task.lease_seconds, 0);
+}
+
+#[traced_test]
+#[test]
+fn test_lease_expired() {
+    let now = Utc::now();
+    let task = StoredExecutingTask {
+        given_at: now - Duration::seconds(100),
+        lease_seconds: 60,
+        ..Default::default()
+    };
+    // The lease granted on acquiring wins over the default
+    assert!(task.lease_expired(now, 3600));
+    let task = StoredExecutingTask {
+        lease_seconds: 3600,
+        ..task
+    };
+    assert!(!task.lease_expired(now, 60));
+    let task = StoredExecutingTask {
+        lease_seconds: 0,
+        ..task
+    };
+    assert!(task.lease_expired(now, 60));
+    assert!(!task.lease_expired(now, 3600));
+}
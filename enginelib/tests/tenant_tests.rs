@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use enginelib::{
+    api::EngineAPI,
+    tenant::{Tenant, Tenants},
+};
+use tracing_test::traced_test;
+
+fn tenant(id: &str, namespace: &str, uid: &str, token: &str) -> Tenant {
+    Tenant {
+        id: id.into(),
+        namespaces: vec![namespace.into()],
+        credentials: HashMap::from([(uid.to_string(), token.to_string())]),
+        ..Default::default()
+    }
+}
+
+#[traced_test]
+#[test]
+fn test_tenant_boundary() {
+    let mut api = EngineAPI::test_default();
+    api.tenants
+        .put(tenant("red", "red_ns", "alice", "alice_token"))
+        .unwrap();
+    api.tenants
+        .put(tenant("blue", "blue_ns", "bob", "bob_token"))
+        .unwrap();
+
+    assert!(
+        api.tenants
+            .authorize("alice", "alice_token", "red_ns")
+            .is_ok()
+    );
+    assert!(
+        api.tenants
+            .authorize("alice", "alice_token", "shared")
+            .is_ok()
+    );
+    assert!(api.tenants.authorize("alice", "wrong", "red_ns").is_err());
+    assert!(
+        api.tenants
+            .authorize("alice", "alice_token", "blue_ns")
+            .is_err()
+    );
+    // Users outside of every tenant only get shared namespaces.
+    assert!(api.tenants.authorize("carol", "", "shared").is_ok());
+    assert!(api.tenants.authorize("carol", "", "red_ns").is_err());
+}
+
+#[traced_test]
+#[test]
+fn test_tenant_conflicts_and_persistence() {
+    let mut api = EngineAPI::test_default();
+    api.tenants
+        .put(tenant("red", "red_ns", "alice", "alice_token"))
+        .unwrap();
+    assert!(
+        api.tenants
+            .put(tenant("blue", "red_ns", "bob", "bob_token"))
+            .is_err()
+    );
+    assert!(
+        api.tenants
+            .put(tenant("blue", "blue_ns", "alice", "bob_token"))
+            .is_err()
+    );
+    assert!(api.tenants.put(tenant("", "blue_ns", "bob", "x")).is_err());
+    // Replacing a tenant with its own namespaces is fine.
+    api.tenants
+        .put(tenant("red", "red_ns", "alice", "new_token"))
+        .unwrap();
+
+    let reloaded = Tenants::new(&api.db);
+    assert_eq!(reloaded.tenants.len(), 1);
+    assert_eq!(
+        reloaded.get("red").unwrap().credentials["alice"],
+        "new_token"
+    );
+
+    assert!(api.tenants.remove("red").is_some());
+    assert!(Tenants::new(&api.db).tenants.is_empty());
+}
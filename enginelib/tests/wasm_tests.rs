@@ -0,0 +1,196 @@
+use std::{fs, path::Path, sync::Arc};
+
+use enginelib::{
+    api::EngineAPI,
+    events::ID,
+    plugin::LibraryManager,
+    task::{Runner, StoredExecutingTask, Task, Verifiable},
+    wasm::{WASM_MANIFEST, WasmLimits, WasmModule, WasmTask},
+};
+use tracing_test::traced_test;
+
+const LIMITS: WasmLimits = WasmLimits {
+    fuel: 1_000_000,
+    max_memory: 1024 * 1024,
+    max_output: 1024,
+};
+
+// Keeps a little endian u32 counter, `run` increments it and `serialize` copies its input.
+const COUNTER: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "alloc") (param $len i32) (result i32) (i32.const 1024))
+  (func (export "verify") (param $ptr i32) (param $len i32) (result i32)
+    (i32.eq (local.get $len) (i32.const 4)))
+  (func $pack (param $ptr i32) (param $len i32) (result i64)
+    (i64.or
+      (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+      (i64.extend_i32_u (local.get $len))))
+  (func (export "serialize") (param $ptr i32) (param $len i32) (result i64)
+    (call $pack (local.get $ptr) (local.get $len)))
+  (func (export "run") (param $ptr i32) (param $len i32) (result i64)
+    (i32.store (local.get $ptr) (i32.add (i32.load (local.get $ptr)) (i32.const 1)))
+    (call $pack (local.get $ptr) (i32.const 4))))
+"#;
+
+fn counter() -> Arc<WasmModule> {
+    Arc::new(WasmModule::new(&wat::parse_str(COUNTER).unwrap(), LIMITS).unwrap())
+}
+
+#[traced_test]
+#[test]
+fn test_wasm_task() {
+    let task = WasmTask::new(ID("wasm", "counter"), counter());
+    assert!(task.verify(41u32.to_le_bytes().to_vec()));
+    assert!(!task.verify(vec![0; 3]));
+
+    let mut task = task.from_bytes(&41u32.to_le_bytes());
+    task.run(Some(Runner::CPU));
+    assert_eq!(task.to_bytes(), 42u32.to_le_bytes());
+    assert_eq!(task.from_toml("toml".into()).to_bytes(), b"toml".to_vec());
+    assert_eq!(task.to_toml(), "");
+}
+
+#[traced_test]
+#[test]
+fn test_wasm_limits() {
+    let looping = COUNTER.replace(
+        "(i32.store (local.get $ptr)",
+        "(loop $forever (br $forever))\n    (i32.store (local.get $ptr)",
+    );
+    let module = Arc::new(WasmModule::new(&wat::parse_str(looping).unwrap(), LIMITS).unwrap());
+    assert!(module.call("run", &[0; 4]).is_err());
+    let mut task = WasmTask::new(ID("wasm", "looping"), module).from_bytes(&[7, 0, 0, 0]);
+    task.run(Some(Runner::CPU));
+    assert_eq!(task.to_bytes(), vec![7, 0, 0, 0]);
+
+    let greedy = COUNTER.replace(
+        "(memory (export \"memory\") 1)",
+        "(memory (export \"memory\") 32)",
+    );
+    let module = Arc::new(WasmModule::new(&wat::parse_str(greedy).unwrap(), LIMITS).unwrap());
+    assert!(module.verify(&[0; 4]).is_err());
+    assert!(!WasmTask::new(ID("wasm", "greedy"), module).verify(vec![0; 4]));
+
+    // Outputs are checked before the host allocates for them
+    let module = Arc::new(WasmModule::new(&wat::parse_str(COUNTER).unwrap(), LIMITS).unwrap());
+    assert!(module.call("serialize", &[0; 1024]).is_ok());
+    assert!(module.call("serialize", &[0; 1025]).is_err());
+    let outside = COUNTER
+        .replace("(i32.const 1024))", "(i32.const 65534))")
+        .replace(
+            "(call $pack (local.get $ptr) (local.get $len))",
+            "(call $pack (local.get $ptr) (i32.const 4))",
+        );
+    let module = Arc::new(WasmModule::new(&wat::parse_str(outside).unwrap(), LIMITS).unwrap());
+    let error = module.call("serialize", &[0; 2]).unwrap_err();
+    assert!(error.contains("outside its memory"), "{}", error);
+}
+
+fn write_package(dir: &Path, mod_id: &str, deps: &str) {
+    fs::create_dir_all(dir).unwrap();
+    fs::write(dir.join("counter.wasm"), wat::parse_str(COUNTER).unwrap()).unwrap();
+    fs::write(
+        dir.join(WASM_MANIFEST),
+        format!(
+            r#"
+mod_id = "{mod_id}"
+mod_name = "Wasm Test"
+mod_version = "1.0.0"
+{deps}
+[[tasks]]
+name = "counter"
+module = "counter.wasm"
+"#
+        ),
+    )
+    .unwrap();
+}
+
+#[traced_test]
+#[test]
+fn test_wasm_package() {
+    let dir = std::env::temp_dir().join(format!("engine_wasm_{}", std::process::id()));
+    write_package(&dir, "wasm_test", "");
+
+    let mut api = EngineAPI::test_default();
+    let mut manager = LibraryManager::default();
+    // Directories are unsigned
+    assert!(manager.load_wasm_package(&dir, &mut api).is_err());
+    assert!(manager.loaded("wasm_test").is_none());
+    api.cfg.config_toml.allow_unsigned_mods = true;
+    let mut manager = LibraryManager::default();
+    manager.load_wasm_package(&dir, &mut api).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(manager.loaded("wasm_test").unwrap().mod_version, "1.0.0");
+    let prototype = api.task_registry.tasks[&ID("wasm_test", "counter")].clone();
+    let mut task = prototype.from_bytes(&1u32.to_le_bytes());
+    task.run(Some(Runner::CPU));
+    assert_eq!(task.to_bytes(), 2u32.to_le_bytes());
+}
+
+#[traced_test]
+#[test]
+fn test_wasm_package_escape() {
+    let dir = std::env::temp_dir().join(format!("engine_wasm_escape_{}", std::process::id()));
+    for module in ["../counter.wasm", "/tmp/counter.wasm", "./counter.wasm"] {
+        write_package(&dir, "wasm_escape", "");
+        let manifest = fs::read_to_string(dir.join(WASM_MANIFEST)).unwrap();
+        let manifest = manifest.replace("\"counter.wasm\"", &format!("{:?}", module));
+        fs::write(dir.join(WASM_MANIFEST), manifest).unwrap();
+
+        let mut api = EngineAPI::test_default();
+        api.cfg.config_toml.allow_unsigned_mods = true;
+        let mut manager = LibraryManager::default();
+        assert!(manager.load_wasm_package(&dir, &mut api).is_err());
+        assert!(manager.loaded("wasm_escape").is_none());
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[traced_test]
+#[test]
+fn test_unload_and_reload() {
+    let root = std::env::temp_dir().join(format!("engine_reload_{}", std::process::id()));
+    let base = root.join("base");
+    let app = root.join("app");
+    write_package(&base, "base", "");
+    write_package(&app, "app", "[[mod_dependencies]]\nmod_id = \"base\"\n");
+
+    let mut api = EngineAPI::test_default();
+    api.cfg.config_toml.allow_unsigned_mods = true;
+    let mut manager = LibraryManager::default();
+    assert!(manager.load_paths(vec![app, base], &mut api).is_empty());
+    let key = ID("base", "counter");
+    api.executing_tasks.tasks.insert(
+        key.clone(),
+        vec![StoredExecutingTask {
+            bytes: 1u32.to_le_bytes().to_vec(),
+            id: "leased".into(),
+            user_id: "worker".into(),
+            created_by: "user".into(),
+            given_at: chrono::Utc::now(),
+            lease_seconds: 0,
+        }],
+    );
+
+    assert!(
+        manager
+            .unload("base", &mut api)
+            .unwrap_err()
+            .contains("required by app")
+    );
+    manager.reload("base", &mut api).unwrap();
+    assert!(manager.loaded("app").is_some());
+    assert!(api.task_registry.tasks.contains_key(&ID("app", "counter")));
+    assert_eq!(api.task_queue.tasks[&key][0].id, "leased");
+    assert!(!api.executing_tasks.tasks.contains_key(&key));
+
+    manager.unload("app", &mut api).unwrap();
+    manager.unload("base", &mut api).unwrap();
+    assert!(manager.loaded("base").is_none());
+    assert!(api.task_registry.tasks.is_empty());
+    assert!(manager.unload("base", &mut api).is_err());
+    fs::remove_dir_all(&root).unwrap();
+}